@@ -0,0 +1,10 @@
+#![no_main]
+
+use exxos_kult_extract::format::{Extract, PlanarScreen, PrefixedScreen};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PlanarScreen::new().extract(data);
+    let _ = PlanarScreen::with_size(320, 200).extract(data);
+    let _ = PrefixedScreen.extract(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use exxos_kult_extract::format::{Detect, Extract, Sprites};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if Sprites::new().detect(data).is_ok() {
+        let _ = Sprites::new().extract(data);
+    }
+});
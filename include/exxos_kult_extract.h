@@ -0,0 +1,74 @@
+#ifndef EXXOS_KULT_EXTRACT_H
+#define EXXOS_KULT_EXTRACT_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The decoded frames of a sprite sheet.
+typedef struct KultSheet KultSheet;
+
+// Decodes a fullscreen image into `out`, which must hold at least
+// `width * height * 4` bytes. The size of the image is stored in `width`
+// and `height` whether `out` is large enough or not, so passing a null
+// `out` queries it.
+//
+// # Safety
+//
+// `src` must point to `len` readable bytes, `out` to `out_len` writable
+// bytes or be null, and `width` and `height` must be valid or null.
+int32_t kult_decode_fullscreen(const uint8_t *src,
+                               size_t len,
+                               uint8_t *out,
+                               size_t out_len,
+                               size_t *width,
+                               size_t *height);
+
+// Decodes a sprite sheet, returning null if it is not one. The data is
+// not referenced after the call. Free the sheet with [`kult_sheet_free`].
+//
+// # Safety
+//
+// `src` must point to `len` readable bytes.
+struct KultSheet *kult_sheet_open(const uint8_t *src, size_t len);
+
+// The number of frames that decoded. Frames are numbered from 0 in this
+// interface, skipping frames that did not decode.
+//
+// # Safety
+//
+// `sheet` must come from [`kult_sheet_open`] and not have been freed.
+size_t kult_sheet_frame_count(const struct KultSheet *sheet);
+
+// Stores the size of frame `n` in `width` and `height`.
+//
+// # Safety
+//
+// `sheet` must come from [`kult_sheet_open`] and not have been freed, and
+// `width` and `height` must be valid or null.
+int32_t kult_sheet_frame_size(const struct KultSheet *sheet,
+                              size_t n,
+                              size_t *width,
+                              size_t *height);
+
+// Writes frame `n` into `out`, which must hold at least
+// `width * height * 4` bytes.
+//
+// # Safety
+//
+// `sheet` must come from [`kult_sheet_open`] and not have been freed, and
+// `out` must point to `out_len` writable bytes.
+int32_t kult_sheet_frame_rgba(const struct KultSheet *sheet,
+                              size_t n,
+                              uint8_t *out,
+                              size_t out_len);
+
+// Frees a sheet. Null is ignored.
+//
+// # Safety
+//
+// `sheet` must come from [`kult_sheet_open`] and not have been freed.
+void kult_sheet_free(struct KultSheet *sheet);
+
+#endif  /* EXXOS_KULT_EXTRACT_H */
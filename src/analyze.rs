@@ -0,0 +1,158 @@
+//! Palette usage statistics, used to decide on palette and transparency
+//! settings per asset, and other checks on decoded frames.
+
+use std::collections::HashMap;
+
+use crate::format::IndexedFrame;
+
+pub struct FrameStats {
+    /// Number of pixels per palette index.
+    pub histogram: [usize; 16],
+    /// Number of index 0 pixels that cannot be reached from the edge of
+    /// the frame through other index 0 pixels. Many of these suggest index
+    /// 0 is used as a color rather than as transparency.
+    pub interior_zero: usize,
+}
+
+pub fn frame_stats(frame: &IndexedFrame) -> FrameStats {
+    let mut histogram = [0; 16];
+    for &v in &frame.pixels {
+        histogram[v as usize & 0x0f] += 1;
+    }
+
+    let (w, h) = (frame.width, frame.height);
+
+    // Flood fill index 0 from the edges, whatever is left is interior.
+    let mut reached = vec![false; w * h];
+    let mut stack = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            if (x == 0 || y == 0 || x == w - 1 || y == h - 1) && frame.pixels[y * w + x] == 0 {
+                reached[y * w + x] = true;
+                stack.push((x, y));
+            }
+        }
+    }
+
+    while let Some((x, y)) = stack.pop() {
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        for (nx, ny) in neighbours {
+            if nx < w && ny < h && !reached[ny * w + nx] && frame.pixels[ny * w + nx] == 0 {
+                reached[ny * w + nx] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    let interior_zero = frame
+        .pixels
+        .iter()
+        .zip(&reached)
+        .filter(|(&v, &r)| v == 0 && !r)
+        .count();
+
+    FrameStats {
+        histogram,
+        interior_zero,
+    }
+}
+
+/// Remembers frames seen so far, to find duplicates across files.
+#[derive(Default)]
+pub struct DuplicateFinder<K> {
+    seen: HashMap<(usize, usize, Vec<u8>), K>,
+}
+
+impl<K: Clone> DuplicateFinder<K> {
+    pub fn new() -> DuplicateFinder<K> {
+        DuplicateFinder {
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns the key of an earlier identical frame, or records this one
+    /// under `key`.
+    pub fn check(&mut self, frame: &IndexedFrame, key: K) -> Option<K> {
+        let k = (frame.width, frame.height, frame.pixels.clone());
+        if let Some(earlier) = self.seen.get(&k) {
+            return Some(earlier.clone());
+        }
+        self.seen.insert(k, key);
+        None
+    }
+}
+
+/// For each frame, the index of the first identical frame before it.
+pub fn duplicates(frames: &[IndexedFrame]) -> Vec<Option<usize>> {
+    let mut finder = DuplicateFinder::new();
+    frames.iter().map(|f| finder.check(f, f.index)).collect()
+}
+
+/// Which pixels of `frame` differ from the frame before it, row by row.
+/// Pixels outside of `previous` count as changed.
+pub fn changed_pixels(previous: &IndexedFrame, frame: &IndexedFrame) -> Vec<bool> {
+    let mut changed = Vec::with_capacity(frame.width * frame.height);
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let before = (x < previous.width && y < previous.height)
+                .then(|| previous.pixels[y * previous.width + x]);
+            changed.push(before != Some(frame.pixels[y * frame.width + x]));
+        }
+    }
+    changed
+}
+
+/// How a screen repeats itself, which suggests it was decoded at the
+/// wrong size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repetition {
+    /// The right half is a copy of the left half.
+    Horizontal,
+    /// The bottom half is a copy of the top half.
+    Vertical,
+    /// The right half is a mirror image of the left half.
+    Mirrored,
+}
+
+impl Repetition {
+    /// What the frame looks like, and the size it may actually have.
+    pub fn describe(self, width: usize, height: usize) -> String {
+        let (what, size) = match self {
+            Repetition::Horizontal => ("right half repeats the left half", (width / 2, height)),
+            Repetition::Vertical => ("bottom half repeats the top half", (width, height / 2)),
+            Repetition::Mirrored => ("right half mirrors the left half", (width / 2, height)),
+        };
+        format!(
+            "The {what}, it may be a {}x{} image padded to {width}x{height}.",
+            size.0, size.1
+        )
+    }
+}
+
+/// Finds halves of a frame that are copies or mirror images of each
+/// other. Frames of a single color are left alone, as they match any way.
+pub fn repetition(frame: &IndexedFrame) -> Option<Repetition> {
+    let (w, h) = (frame.width, frame.height);
+    let pixels = &frame.pixels;
+    let first = pixels.first()?;
+    if pixels.iter().all(|v| v == first) {
+        return None;
+    }
+
+    let rows = || pixels.chunks(w);
+    if w.is_multiple_of(2) && rows().all(|row| row[..w / 2] == row[w / 2..]) {
+        return Some(Repetition::Horizontal);
+    }
+    if h.is_multiple_of(2) && pixels[..w * h / 2] == pixels[w * h / 2..] {
+        return Some(Repetition::Vertical);
+    }
+    if w.is_multiple_of(2) && rows().all(|row| row[..w / 2].iter().eq(row[w / 2..].iter().rev())) {
+        return Some(Repetition::Mirrored);
+    }
+    None
+}
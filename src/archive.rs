@@ -0,0 +1,524 @@
+//! ZIP and LHA archives, which copies of the game are often kept in, so
+//! their members can be extracted without unpacking them first.
+//!
+//! ZIP members may be stored or deflated. LHA members may be stored
+//! (`-lh0-`, `-lz4-`) or compressed with the static Huffman methods of LHA
+//! 2 and later (`-lh5-`, `-lh6-`, `-lh7-`); the adaptive Huffman `-lh1-` of
+//! the older LHarc is not supported. LHA headers of levels 0 to 2 are read.
+
+use std::io::{Error, ErrorKind};
+
+// Far larger than any game file, this only guards against allocating huge
+// buffers for broken headers.
+const MAX_MEMBER_SIZE: usize = 1 << 24;
+
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const ZIP_END_OF_DIRECTORY: &[u8] = b"PK\x05\x06";
+const ZIP_END_SIZE: usize = 22;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Lha,
+}
+
+pub struct Member {
+    /// Path within the archive, with `/` separating directories.
+    pub name: String,
+    /// The contents, or why they could not be unpacked.
+    pub data: Result<Vec<u8>, Error>,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn truncated() -> Error {
+    invalid("archive is truncated")
+}
+
+fn le_u16(src: &[u8], pos: usize) -> Result<usize, Error> {
+    let b = src.get(pos..pos + 2).ok_or_else(truncated)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn le_u32(src: &[u8], pos: usize) -> Result<usize, Error> {
+    let b = src.get(pos..pos + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes(b.try_into().unwrap()) as usize)
+}
+
+fn bytes(src: &[u8], pos: usize, len: usize) -> Result<&[u8], Error> {
+    src.get(pos..pos.checked_add(len).ok_or_else(truncated)?)
+        .ok_or_else(truncated)
+}
+
+fn crc32(src: &[u8]) -> u32 {
+    !src.iter().fold(!0, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| {
+            (c >> 1) ^ (0xedb88320 & (c & 1).wrapping_neg())
+        })
+    })
+}
+
+fn crc16(src: &[u8]) -> u16 {
+    src.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ b as u16, |c, _| {
+            (c >> 1) ^ (0xa001 & (c & 1).wrapping_neg())
+        })
+    })
+}
+
+fn is_lha_method(method: &[u8]) -> bool {
+    matches!(method, [b'-', b'l', b'h' | b'z', _, b'-'])
+}
+
+/// Recognizes an archive by its first bytes.
+pub fn detect(src: &[u8]) -> Option<ArchiveKind> {
+    if src.starts_with(ZIP_LOCAL_HEADER) || src.starts_with(ZIP_END_OF_DIRECTORY) {
+        Some(ArchiveKind::Zip)
+    } else if src.get(2..7).is_some_and(is_lha_method) {
+        Some(ArchiveKind::Lha)
+    } else {
+        None
+    }
+}
+
+/// Reads the members of an archive. Directories are left out. A member
+/// that can't be unpacked has the reason instead of its data; an error is
+/// returned only if the archive itself is unreadable.
+pub fn members(src: &[u8]) -> Result<Vec<Member>, Error> {
+    match detect(src) {
+        Some(ArchiveKind::Zip) => zip_members(src),
+        Some(ArchiveKind::Lha) => lha_members(src),
+        None => Err(invalid("not a ZIP or LHA archive")),
+    }
+}
+
+/// Checks unpacked data against the size and checksum in its header.
+fn verify<F: FnOnce(&[u8]) -> bool>(
+    data: Vec<u8>,
+    size: usize,
+    checksum_matches: F,
+) -> Result<Vec<u8>, Error> {
+    if data.len() != size {
+        return Err(invalid("unpacked size does not match the header"));
+    }
+    if !checksum_matches(&data) {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(data)
+}
+
+fn zip_members(src: &[u8]) -> Result<Vec<Member>, Error> {
+    // The end of central directory record is followed by a comment of up
+    // to 64K.
+    let last = src.len().checked_sub(ZIP_END_SIZE).ok_or_else(truncated)?;
+    let end = (last.saturating_sub(0xffff)..=last)
+        .rev()
+        .find(|&pos| src[pos..].starts_with(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| invalid("ZIP central directory not found"))?;
+
+    let count = le_u16(src, end + 10)?;
+    let mut pos = le_u32(src, end + 16)?;
+    let mut members = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if !bytes(src, pos, 4)?.starts_with(ZIP_CENTRAL_HEADER) {
+            return Err(invalid("invalid ZIP central directory entry"));
+        }
+
+        let flags = le_u16(src, pos + 8)?;
+        let method = le_u16(src, pos + 10)?;
+        let crc = le_u32(src, pos + 16)? as u32;
+        let packed_size = le_u32(src, pos + 20)?;
+        let size = le_u32(src, pos + 24)?;
+        let name_len = le_u16(src, pos + 28)?;
+        let extra_len = le_u16(src, pos + 30)?;
+        let comment_len = le_u16(src, pos + 32)?;
+        let local = le_u32(src, pos + 42)?;
+        let name = String::from_utf8_lossy(bytes(src, pos + 46, name_len)?).replace('\\', "/");
+        pos += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+
+        let data = zip_member_data(src, local, flags, method, packed_size, size)
+            .and_then(|data| verify(data, size, |d| crc32(d) == crc));
+        members.push(Member { name, data });
+    }
+
+    Ok(members)
+}
+
+fn zip_member_data(
+    src: &[u8],
+    local: usize,
+    flags: usize,
+    method: usize,
+    packed_size: usize,
+    size: usize,
+) -> Result<Vec<u8>, Error> {
+    if flags & 1 != 0 {
+        return Err(Error::new(ErrorKind::InvalidData, "member is encrypted"));
+    }
+    if size > MAX_MEMBER_SIZE {
+        return Err(invalid("member too large"));
+    }
+    if !bytes(src, local, 4)?.starts_with(ZIP_LOCAL_HEADER) {
+        return Err(invalid("invalid ZIP local header"));
+    }
+
+    let start = local + 30 + le_u16(src, local + 26)? + le_u16(src, local + 28)?;
+    let packed = bytes(src, start, packed_size)?;
+
+    match method {
+        0 => Ok(packed.to_vec()),
+        8 => miniz_oxide::inflate::decompress_to_vec_with_limit(packed, size)
+            .map_err(|_| invalid("invalid deflate data")),
+        m => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported compression method {m}"),
+        )),
+    }
+}
+
+/// Appends an LHA path, whose directories may be separated by `\` or
+/// 0xff, to `name`.
+fn push_lha_path(name: &mut String, path: &[u8]) {
+    let path = path
+        .iter()
+        .map(|&b| if b == b'\\' || b == 0xff { b'/' } else { b })
+        .collect::<Vec<_>>();
+    name.push_str(&String::from_utf8_lossy(&path));
+}
+
+/// Reads the extended headers starting at `pos`, the first of which is
+/// `next` bytes long. Returns the position after them and the path they
+/// name, if any.
+fn lha_extended_headers(
+    h: &[u8],
+    mut pos: usize,
+    mut next: usize,
+) -> Result<(usize, Option<String>), Error> {
+    let mut dir = None;
+    let mut file = None;
+
+    while next != 0 {
+        if next < 3 {
+            return Err(invalid("invalid LHA extended header"));
+        }
+        let ext = bytes(h, pos, next)?;
+        let data = &ext[1..next - 2];
+        match ext[0] {
+            0x01 => file = Some(data),
+            0x02 => dir = Some(data),
+            _ => {}
+        }
+        pos += next;
+        next = le_u16(ext, next - 2)?;
+    }
+
+    let path = file.map(|file| {
+        let mut name = String::new();
+        if let Some(dir) = dir {
+            push_lha_path(&mut name, dir);
+            if !name.is_empty() && !name.ends_with('/') {
+                name.push('/');
+            }
+        }
+        push_lha_path(&mut name, file);
+        name
+    });
+
+    Ok((pos, path))
+}
+
+fn lha_members(src: &[u8]) -> Result<Vec<Member>, Error> {
+    let mut members = Vec::new();
+    let mut pos = 0;
+
+    // The archive ends with a zero byte, or just ends.
+    while src.get(pos).is_some_and(|&b| b != 0) {
+        let h = &src[pos..];
+        let method = bytes(h, 2, 5)?;
+        let mut packed_size = le_u32(h, 7)?;
+        let size = le_u32(h, 11)?;
+        let level = *h.get(20).ok_or_else(truncated)?;
+
+        let (start, mut name, crc) = match level {
+            0 | 1 => {
+                let header_size = h[0] as usize + 2;
+                let name_len = *h.get(21).ok_or_else(truncated)? as usize;
+                let mut name = String::new();
+                push_lha_path(&mut name, bytes(h, 22, name_len)?);
+                let crc = le_u16(h, 22 + name_len)?;
+
+                let mut start = header_size;
+                if level == 1 {
+                    // The size of the extended headers is included in the
+                    // packed size.
+                    let (end, path) =
+                        lha_extended_headers(h, header_size, le_u16(h, header_size - 2)?)?;
+                    packed_size = packed_size
+                        .checked_sub(end - header_size)
+                        .ok_or_else(|| invalid("invalid LHA header size"))?;
+                    start = end;
+                    if let Some(path) = path {
+                        name = path;
+                    }
+                }
+                (start, name, crc)
+            }
+            2 => {
+                let header_size = le_u16(h, 0)?;
+                let (_, path) = lha_extended_headers(h, 26, le_u16(h, 24)?)?;
+                (header_size, path.unwrap_or_default(), le_u16(h, 21)?)
+            }
+            level => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unsupported LHA header level {level}"),
+                ))
+            }
+        };
+
+        let packed = bytes(h, start, packed_size)?;
+        pos += start + packed_size;
+
+        if name.is_empty() {
+            name = format!("member{}", members.len());
+        }
+        if method == b"-lhd-" {
+            continue;
+        }
+
+        let data = lha_member_data(method, packed, size)
+            .and_then(|data| verify(data, size, |d| crc16(d) as usize == crc));
+        members.push(Member { name, data });
+    }
+
+    Ok(members)
+}
+
+fn lha_member_data(method: &[u8], packed: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    if size > MAX_MEMBER_SIZE {
+        return Err(invalid("member too large"));
+    }
+
+    match method {
+        b"-lh0-" | b"-lz4-" => Ok(packed.to_vec()),
+        b"-lh5-" => unlzh(packed, size, 13),
+        b"-lh6-" => unlzh(packed, size, 15),
+        b"-lh7-" => unlzh(packed, size, 16),
+        m => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "unsupported compression method {}",
+                String::from_utf8_lossy(m)
+            ),
+        )),
+    }
+}
+
+/// Reads bits most significant first. Reading past the end yields zeros,
+/// [`overrun`](Self::overrun) tells whether that happened.
+struct BitReader<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> usize {
+        let byte = self.src.get(self.pos / 8).copied().unwrap_or(0);
+        self.pos += 1;
+        (byte >> (7 - (self.pos - 1) % 8)) as usize & 1
+    }
+
+    fn bits(&mut self, n: usize) -> usize {
+        (0..n).fold(0, |v, _| (v << 1) | self.bit())
+    }
+
+    fn overrun(&self) -> bool {
+        self.pos > 8 * self.src.len()
+    }
+}
+
+const MAX_CODE_LEN: usize = 16;
+
+/// A canonical Huffman code, shorter codes first and symbols in order
+/// within a length.
+enum Huffman {
+    /// Every code is this symbol, without reading any bits.
+    Single(usize),
+    Codes {
+        counts: [usize; MAX_CODE_LEN + 1],
+        symbols: Vec<usize>,
+    },
+}
+
+impl Huffman {
+    fn from_lengths(lengths: &[usize]) -> Huffman {
+        let mut counts = [0; MAX_CODE_LEN + 1];
+        let mut symbols = Vec::new();
+        for (len, count) in counts.iter_mut().enumerate().skip(1) {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, &l)| l == len) {
+                *count += 1;
+                symbols.push(symbol);
+            }
+        }
+        Huffman::Codes { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<usize, Error> {
+        let (counts, symbols) = match self {
+            Huffman::Single(symbol) => return Ok(*symbol),
+            Huffman::Codes { counts, symbols } => (counts, symbols),
+        };
+
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &counts[1..] {
+            code |= r.bit();
+            if code - first < count {
+                return Ok(symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+// Code lengths of the symbol and position codes are themselves coded.
+const NT: usize = 19;
+const TBIT: usize = 5;
+// Literals, then match lengths from 3 to 256.
+const NC: usize = 256 + 256 - 2;
+const CBIT: usize = 9;
+const MIN_MATCH: usize = 3;
+
+/// Reads the lengths of a code of up to `n` symbols. Lengths of 7 and more
+/// continue in unary. After the first `skip` lengths may come up to three
+/// zeros.
+fn read_pt_lengths(
+    r: &mut BitReader,
+    n: usize,
+    nbit: usize,
+    skip: Option<usize>,
+) -> Result<Huffman, Error> {
+    let count = r.bits(nbit);
+    if count == 0 {
+        return Ok(Huffman::Single(r.bits(nbit)));
+    }
+    if count > n {
+        return Err(invalid("invalid LHA code table"));
+    }
+
+    let mut lengths = vec![0; n];
+    let mut i = 0;
+    while i < count {
+        let mut len = r.bits(3);
+        if len == 7 {
+            while r.bit() == 1 {
+                len += 1;
+                if len > MAX_CODE_LEN {
+                    return Err(invalid("invalid LHA code table"));
+                }
+            }
+        }
+        lengths[i] = len;
+        i += 1;
+
+        if Some(i) == skip {
+            i += r.bits(2);
+        }
+    }
+
+    Ok(Huffman::from_lengths(&lengths))
+}
+
+/// Reads the lengths of the literal and match length code, coded with `pt`.
+fn read_c_lengths(r: &mut BitReader, pt: &Huffman) -> Result<Huffman, Error> {
+    let count = r.bits(CBIT);
+    if count == 0 {
+        return Ok(Huffman::Single(r.bits(CBIT)));
+    }
+    if count > NC {
+        return Err(invalid("invalid LHA code table"));
+    }
+
+    let mut lengths = vec![0; NC];
+    let mut i = 0;
+    while i < count {
+        match pt.decode(r)? {
+            0 => i += 1,
+            1 => i += r.bits(4) + 3,
+            2 => i += r.bits(CBIT) + 20,
+            len => {
+                lengths[i] = len - 2;
+                i += 1;
+            }
+        }
+        if i > NC {
+            return Err(invalid("invalid LHA code table"));
+        }
+    }
+
+    Ok(Huffman::from_lengths(&lengths))
+}
+
+/// Decodes the `-lh5-` family, LZSS with a `1 << dicbit` byte window and
+/// Huffman codes that change every block.
+fn unlzh(src: &[u8], size: usize, dicbit: usize) -> Result<Vec<u8>, Error> {
+    let np = dicbit + 1;
+    let pbit = if np < 16 { 4 } else { 5 };
+
+    let mut r = BitReader { src, pos: 0 };
+    let mut out = Vec::with_capacity(size);
+
+    while out.len() < size {
+        let block_size = r.bits(16);
+        let pt = read_pt_lengths(&mut r, NT, TBIT, Some(3))?;
+        let c = read_c_lengths(&mut r, &pt)?;
+        let p = read_pt_lengths(&mut r, np, pbit, None)?;
+        if r.overrun() {
+            return Err(truncated());
+        }
+
+        for _ in 0..block_size {
+            if out.len() >= size {
+                break;
+            }
+
+            match c.decode(&mut r)? {
+                literal @ 0..256 => out.push(literal as u8),
+                code @ 256..NC => {
+                    let len = code - 256 + MIN_MATCH;
+                    let dist = match p.decode(&mut r)? {
+                        0 => 1,
+                        j if j < np => (1 << (j - 1)) + r.bits(j - 1) + 1,
+                        _ => return Err(invalid("invalid LHA match position")),
+                    };
+                    if dist > out.len() {
+                        return Err(invalid("LHA match before the start of the data"));
+                    }
+                    for _ in 0..len.min(size - out.len()) {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+                _ => return Err(invalid("invalid LHA code")),
+            }
+
+            if r.overrun() {
+                return Err(truncated());
+            }
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,168 @@
+//! Aseprite documents, so pixel artists can open the frames of a sheet
+//! on one timeline, in the colors they were extracted with.
+//!
+//! Documents are indexed with the 16 colors of the palette and a single
+//! layer holding one uncompressed cel per frame. Frames of different sizes
+//! sit in the top left corner of a canvas large enough for all of them.
+//! The pixel ratio is set to the 5:6 of the EGA screen, so the pixels
+//! are stored 1:1 but shown with the right aspect.
+//!
+//! The engine's animation sequence tables are not known, so there are no
+//! tags.
+
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+use crate::format::IndexedFrame;
+
+const HEADER_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_EDITABLE: u16 = 2;
+const LAYER_BACKGROUND: u16 = 8;
+
+/// Little-endian fields of the format.
+#[derive(Default)]
+struct Buf(Vec<u8>);
+
+impl Buf {
+    fn byte(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    fn word(&mut self, v: u16) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn dword(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn zeros(&mut self, n: usize) -> &mut Self {
+        self.0.resize(self.0.len() + n, 0);
+        self
+    }
+
+    fn string(&mut self, s: &str) -> &mut Self {
+        self.word(s.len() as u16);
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+}
+
+fn chunk(frame: &mut Vec<u8>, kind: u16, data: &Buf) {
+    frame.extend_from_slice(&(6 + data.0.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&kind.to_le_bytes());
+    frame.extend_from_slice(&data.0);
+}
+
+fn too_large(what: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("{what} too large for an Aseprite document"),
+    )
+}
+
+/// Writes `frames` as an Aseprite document showing each for `duration`
+/// milliseconds. Palette entries with an alpha of 0 make the document's
+/// transparent index, without one the layer is a background layer.
+pub fn write_aseprite<W: Write>(
+    w: &mut W,
+    frames: &[IndexedFrame],
+    pal: &[[u8; 4]; 16],
+    duration: u16,
+) -> Result<(), Error> {
+    let width = frames.iter().map(|f| f.width).max().unwrap_or(0);
+    let height = frames.iter().map(|f| f.height).max().unwrap_or(0);
+    let (Ok(width), Ok(height), Ok(count)) = (
+        u16::try_from(width),
+        u16::try_from(height),
+        u16::try_from(frames.len()),
+    ) else {
+        return Err(too_large("frames"));
+    };
+    let transparent = pal.iter().position(|c| c[3] == 0);
+
+    let mut body = Vec::new();
+    for (n, frame) in frames.iter().enumerate() {
+        let mut chunks = Vec::new();
+        let mut chunk_count = 0;
+        if n == 0 {
+            let mut palette = Buf::default();
+            palette.dword(pal.len() as u32).dword(0);
+            palette.dword(pal.len() as u32 - 1).zeros(8);
+            for [r, g, b, a] in pal {
+                palette.word(0).byte(*r).byte(*g).byte(*b).byte(*a);
+            }
+            chunk(&mut chunks, CHUNK_PALETTE, &palette);
+
+            let (flags, name) = match transparent {
+                Some(_) => (LAYER_VISIBLE | LAYER_EDITABLE, "Frames"),
+                None => (
+                    LAYER_VISIBLE | LAYER_EDITABLE | LAYER_BACKGROUND,
+                    "Background",
+                ),
+            };
+            let mut layer = Buf::default();
+            layer.word(flags).word(0).word(0).word(0).word(0).word(0);
+            layer.byte(0xff).zeros(3).string(name);
+            chunk(&mut chunks, CHUNK_LAYER, &layer);
+            chunk_count += 2;
+        }
+
+        let mut cel = Buf::default();
+        // Layer 0 at 0,0, opaque, then raw pixels at z-index 0.
+        cel.word(0).word(0).word(0).byte(0xff);
+        cel.word(0).word(0).zeros(5);
+        cel.word(frame.width as u16).word(frame.height as u16);
+        cel.0.extend_from_slice(&frame.pixels);
+        chunk(&mut chunks, CHUNK_CEL, &cel);
+        chunk_count += 1;
+
+        let size = FRAME_HEADER_SIZE + chunks.len();
+        let mut header = Buf::default();
+        header.dword(u32::try_from(size).map_err(|_| too_large("frame"))?);
+        header.word(FRAME_MAGIC).word(chunk_count);
+        header.word(duration).zeros(2).dword(chunk_count as u32);
+        body.extend_from_slice(&header.0);
+        body.extend_from_slice(&chunks);
+    }
+
+    let size = HEADER_SIZE + body.len();
+    let mut header = Buf::default();
+    header.dword(u32::try_from(size).map_err(|_| too_large("sheet"))?);
+    header.word(HEADER_MAGIC).word(count);
+    header.word(width).word(height);
+    // 8 bits per pixel, indexed, with valid layer opacity.
+    header.word(8).dword(1).word(duration).dword(0).dword(0);
+    header.byte(transparent.unwrap_or(0) as u8).zeros(3);
+    header.word(pal.len() as u16);
+    // Pixel ratio, then the grid: at 0,0 and 16x16 as Aseprite defaults to.
+    header.byte(5).byte(6);
+    header.word(0).word(0).word(16).word(16).zeros(84);
+
+    w.write_all(&header.0)?;
+    w.write_all(&body)
+}
+
+pub fn write_aseprite_to_file<P: AsRef<Path>>(
+    filename: P,
+    frames: &[IndexedFrame],
+    pal: &[[u8; 4]; 16],
+    duration: u16,
+) -> Result<(), Error> {
+    let mut w = BufWriter::new(File::create(filename)?);
+    write_aseprite(&mut w, frames, pal, duration)?;
+    w.flush()
+}
@@ -0,0 +1,119 @@
+//! Keeps decoded sprite sheets around for viewers and other library users
+//! that decode the same sheets over and over, like when scrubbing back
+//! and forth through an animation.
+//!
+//! Sheets are recognized by their contents, so the same data read from
+//! another file or read again is found as well. They are looked up by
+//! hash, and the bytes compared to rule out a collision.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Error;
+use std::sync::Arc;
+
+use crate::catalog::content_hash;
+use crate::format::{Extract, IndexedFrame, Problem, Sprites};
+use crate::sprite::SizeOrder;
+
+/// A sprite sheet as parsed and decoded by the cache.
+pub struct CachedSheet {
+    pub size_order: SizeOrder,
+    /// The frames that could be decoded, skipped frames left out.
+    pub frames: Vec<IndexedFrame>,
+}
+
+/// Decoded sprite sheets, the least recently used ones dropped once more
+/// than `capacity` are kept.
+pub struct SpriteSheetCache {
+    capacity: usize,
+    /// The bytes of each sheet and the sheet decoded from them.
+    sheets: HashMap<(u64, usize), (Vec<u8>, Arc<CachedSheet>)>,
+    /// Keys of `sheets`, least recently used first.
+    order: VecDeque<(u64, usize)>,
+}
+
+impl SpriteSheetCache {
+    pub fn new(capacity: usize) -> SpriteSheetCache {
+        SpriteSheetCache {
+            capacity,
+            sheets: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The decoded sheet `src`, decoding it only if it is not cached.
+    /// Sheets that fail to parse are not cached.
+    pub fn get(&mut self, src: &[u8]) -> Result<Arc<CachedSheet>, Error> {
+        let key = (content_hash(src), src.len());
+        if let Some((bytes, sheet)) = self.sheets.get(&key) {
+            if bytes == src {
+                let sheet = sheet.clone();
+                self.touch(key);
+                return Ok(sheet);
+            }
+        }
+
+        let extracted = Sprites::new().extract(src)?;
+        let size_order = if extracted.problems.contains(&Problem::LittleEndianSize) {
+            SizeOrder::LittleEndian
+        } else {
+            SizeOrder::BigEndian
+        };
+        let sheet = Arc::new(CachedSheet {
+            size_order,
+            frames: extracted.frames,
+        });
+
+        if self.capacity > 0 {
+            // A sheet colliding with the one cached under the same key
+            // takes its place.
+            if self.sheets.remove(&key).is_some() {
+                self.order.retain(|k| *k != key);
+            }
+            self.evict(self.capacity - 1);
+            self.sheets.insert(key, (src.to_vec(), sheet.clone()));
+            self.order.push_back(key);
+        }
+        Ok(sheet)
+    }
+
+    fn touch(&mut self, key: (u64, usize)) {
+        if let Some(pos) = self.order.iter().position(|k| *k == key) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(key);
+    }
+
+    /// Drops the least recently used sheets until at most `keep` are left.
+    fn evict(&mut self, keep: usize) {
+        while self.sheets.len() > keep {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.sheets.remove(&oldest);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes how many sheets are kept, dropping the least recently used
+    /// ones if there are more already.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(capacity);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sheets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sheets.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sheets.clear();
+        self.order.clear();
+    }
+}
@@ -0,0 +1,165 @@
+//! Human names for known Kult files.
+//!
+//! A catalog maps filenames, or hashes of the file contents for copies
+//! that were renamed, to descriptions, one entry per line:
+//!
+//! ```text
+//! # Sprites
+//! SPR07.EGA = "Raven idle animation"
+//! "fnv:cbf29ce484222325" = "Title screen"
+//! OVERLAY.EGA = "Inventory overlay" size=160x200
+//! ```
+//!
+//! Filenames are matched without regard to case. Hashes are the FNV-1a
+//! hash of the file as stored, as shown by `info`. A size after the
+//! description is the size of a screen that is padded to a standard
+//! layout, so it is decoded at its actual size. The built-in catalog is
+//! `data/catalog.toml`; entries loaded from another file take precedence,
+//! so the catalog can grow without a new build.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use crate::ega;
+
+const BUILTIN: &str = include_str!("../data/catalog.toml");
+
+const HASH_PREFIX: &str = "fnv:";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    description: String,
+    size: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    by_name: HashMap<String, Entry>,
+    by_hash: HashMap<u64, Entry>,
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {line}: {msg}"))
+}
+
+/// The 64-bit FNV-1a hash of `src`, which identifies files in a catalog.
+pub fn content_hash(src: &[u8]) -> u64 {
+    content_hash_parts(&[src])
+}
+
+/// The hash of `parts` one after the other, the same as [`content_hash`]
+/// of their concatenation without making it.
+pub fn content_hash_parts(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xcbf29ce484222325, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Turns a description into something usable in a filename, like
+/// `raven_idle_animation`.
+pub fn slug(description: &str) -> String {
+    description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+impl Catalog {
+    /// The catalog shipped with the crate.
+    pub fn builtin() -> Catalog {
+        Catalog::parse(BUILTIN).expect("built-in catalog is valid")
+    }
+
+    pub fn parse(text: &str) -> Result<Catalog, Error> {
+        let mut catalog = Catalog::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(n + 1, "expected `file = \"description\"`"));
+            };
+            let key = key.trim().trim_matches('"');
+            let Some((description, rest)) = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|v| v.split_once('"'))
+            else {
+                return Err(invalid(n + 1, "expected a quoted description"));
+            };
+            if key.is_empty() {
+                return Err(invalid(n + 1, "entry without a file"));
+            }
+
+            let mut entry = Entry {
+                description: description.to_string(),
+                size: None,
+            };
+            for word in rest.split_whitespace() {
+                match word.strip_prefix("size=").and_then(ega::parse_size) {
+                    Some(size) => entry.size = Some(size),
+                    None if word.starts_with("size=") => {
+                        return Err(invalid(n + 1, "size requires WxH, with W a multiple of 8"));
+                    }
+                    None => return Err(invalid(n + 1, &format!("unexpected `{word}`"))),
+                }
+            }
+
+            match key.strip_prefix(HASH_PREFIX) {
+                Some(hex) => {
+                    let hash =
+                        u64::from_str_radix(hex, 16).map_err(|_| invalid(n + 1, "invalid hash"))?;
+                    catalog.by_hash.insert(hash, entry);
+                }
+                None => {
+                    catalog.by_name.insert(key.to_ascii_uppercase(), entry);
+                }
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    /// Adds the entries of `other`, replacing existing ones.
+    pub fn merge(&mut self, other: Catalog) {
+        self.by_name.extend(other.by_name);
+        self.by_hash.extend(other.by_hash);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty() && self.by_hash.is_empty()
+    }
+
+    /// Identifies the entries, so changes to the catalog can be noticed.
+    pub fn hash(&self) -> u64 {
+        let mut by_name = self.by_name.iter().collect::<Vec<_>>();
+        by_name.sort_by_key(|(name, _)| *name);
+        let mut by_hash = self.by_hash.iter().collect::<Vec<_>>();
+        by_hash.sort_by_key(|(hash, _)| **hash);
+        content_hash(format!("{by_name:?}{by_hash:?}").as_bytes())
+    }
+
+    fn entry(&self, filename: &str, src: &[u8]) -> Option<&Entry> {
+        self.by_hash
+            .get(&content_hash(src))
+            .or_else(|| self.by_name.get(&filename.to_ascii_uppercase()))
+    }
+
+    /// The description of a file, by its contents or else its name.
+    pub fn lookup(&self, filename: &str, src: &[u8]) -> Option<&str> {
+        self.entry(filename, src).map(|e| e.description.as_str())
+    }
+
+    /// The actual size of a padded screen, found like the description.
+    pub fn size(&self, filename: &str, src: &[u8]) -> Option<(usize, usize)> {
+        self.entry(filename, src)?.size
+    }
+}
@@ -0,0 +1,113 @@
+//! Comparing decoded images with screenshots of the game, to check the
+//! decoders and palettes against what an emulator or real hardware shows.
+//!
+//! Screenshots have to show the image at whole multiples of its size, as
+//! DOSBox takes them without aspect correction: a 320x200 screen at
+//! 320x200, 640x400 and so on. Only the colors are compared, so
+//! transparent pixels of the decoded image count as their color.
+
+use std::io::{Error, ErrorKind};
+
+/// A decoded pixel that does not look the same in the screenshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub x: usize,
+    pub y: usize,
+    pub expected: [u8; 3],
+    /// The screenshot pixel furthest off, of the ones showing this pixel.
+    pub actual: [u8; 3],
+}
+
+impl Difference {
+    /// The largest difference of a color component.
+    pub fn amount(&self) -> u8 {
+        (0..3)
+            .map(|i| self.expected[i].abs_diff(self.actual[i]))
+            .max()
+            .unwrap()
+    }
+}
+
+pub struct Comparison {
+    /// How many screenshot pixels wide and high each decoded pixel is.
+    pub scale: (usize, usize),
+    /// The differing pixels, row by row.
+    pub differences: Vec<Difference>,
+    /// RGBA at the size of the decoded image: matching pixels in faded
+    /// gray, differing ones from red for slight differences to yellow for
+    /// the largest.
+    pub heatmap: Vec<u8>,
+}
+
+fn rgb(data: &[u8], width: usize, x: usize, y: usize) -> [u8; 3] {
+    let pos = 4 * (y * width + x);
+    [data[pos], data[pos + 1], data[pos + 2]]
+}
+
+/// Compares an RGBA `image` with an RGBA `screenshot` of it.
+pub fn compare(
+    image: &[u8],
+    width: usize,
+    height: usize,
+    screenshot: &[u8],
+    screenshot_width: usize,
+    screenshot_height: usize,
+) -> Result<Comparison, Error> {
+    if width == 0
+        || height == 0
+        || screenshot_width < width
+        || screenshot_height < height
+        || !screenshot_width.is_multiple_of(width)
+        || !screenshot_height.is_multiple_of(height)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "the screenshot is {screenshot_width}x{screenshot_height}, \
+                 not a multiple of the {width}x{height} image"
+            ),
+        ));
+    }
+    let scale = (screenshot_width / width, screenshot_height / height);
+
+    let mut differences = Vec::new();
+    let mut heatmap = Vec::with_capacity(4 * width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let expected = rgb(image, width, x, y);
+            let mut difference = Difference {
+                x,
+                y,
+                expected,
+                actual: expected,
+            };
+            for sy in y * scale.1..(y + 1) * scale.1 {
+                for sx in x * scale.0..(x + 1) * scale.0 {
+                    let actual = rgb(screenshot, screenshot_width, sx, sy);
+                    let candidate = Difference {
+                        actual,
+                        ..difference
+                    };
+                    if candidate.amount() > difference.amount() {
+                        difference = candidate;
+                    }
+                }
+            }
+
+            if difference.amount() == 0 {
+                let [r, g, b] = expected;
+                let gray = ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 4000) as u8;
+                heatmap.extend_from_slice(&[gray, gray, gray, 0xff]);
+            } else {
+                heatmap.extend_from_slice(&[0xff, difference.amount(), 0, 0xff]);
+                differences.push(difference);
+            }
+        }
+    }
+
+    Ok(Comparison {
+        scale,
+        differences,
+        heatmap,
+    })
+}
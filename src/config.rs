@@ -0,0 +1,172 @@
+//! Default options from a `kult-extract.toml` file, for pipelines that
+//! always pass the same options.
+//!
+//! Each line sets one option, named like on the command line without the
+//! dashes:
+//!
+//! ```text
+//! output = "sprites"
+//! engine = "godot"
+//! native = true
+//! remap = false
+//! gamma = 1.2
+//! ```
+//!
+//! The file is read as a small subset of TOML, which is all the options
+//! need: blank lines, comments starting with `#` on their own or after a
+//! value, and `name = value` lines. A value is `true` or `false`, a string
+//! in double quotes without escapes, or else taken as written, like a
+//! number. Tables (`[section]`), arrays and the other value types are
+//! rejected rather than read wrongly.
+//!
+//! The options become arguments ahead of those given on the command line,
+//! so the command line takes precedence. Flags set in the file can be
+//! turned off with `--no-<flag>`, see [`negation`].
+
+use std::env;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+pub const CONFIG_FILENAME: &str = "kult-extract.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Text(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Line number, option name and value of each setting.
+    entries: Vec<(usize, String, Value)>,
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {line}: {msg}"))
+}
+
+/// The config file to use: `kult-extract.toml` in the current directory,
+/// or else in the XDG config directory, `$XDG_CONFIG_HOME` or
+/// `~/.config`.
+pub fn find() -> Option<PathBuf> {
+    let local = PathBuf::from(CONFIG_FILENAME);
+    if local.is_file() {
+        return Some(local);
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join(CONFIG_FILENAME)).filter(|p| p.is_file())
+}
+
+/// The flag turning off `flag`: `no-<flag>`, or `<name>` for a flag
+/// named `no-<name>`.
+pub fn negation(flag: &str) -> String {
+    match flag.strip_prefix("no-") {
+        Some(name) => name.to_string(),
+        None => format!("no-{flag}"),
+    }
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, Error> {
+        let mut config = Config::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(invalid(n + 1, "tables are not supported"));
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(n + 1, "expected `option = value`"));
+            };
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(invalid(n + 1, "setting without an option"));
+            }
+
+            if !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(invalid(n + 1, &format!("invalid option name `{key}`")));
+            }
+
+            let value = value.trim();
+            let value = if let Some(quoted) = value.strip_prefix('"') {
+                let Some((text, rest)) = quoted.split_once('"') else {
+                    return Err(invalid(n + 1, "unterminated string"));
+                };
+                let rest = rest.trim_start();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(invalid(n + 1, "unexpected text after the string"));
+                }
+                Value::Text(text.to_string())
+            } else {
+                let value = value.split_once('#').map_or(value, |(v, _)| v).trim_end();
+                match value {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "" => return Err(invalid(n + 1, "setting without a value")),
+                    _ if value.starts_with(['[', '{', '\'']) => {
+                        return Err(invalid(
+                            n + 1,
+                            "only strings, numbers and booleans are supported",
+                        ))
+                    }
+                    _ => Value::Text(value.to_string()),
+                }
+            };
+            config.entries.push((n + 1, key.to_string(), value));
+        }
+
+        Ok(config)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Turns the settings into command line arguments. `options` are the
+    /// names of the options that take a value, `flags` those that don't.
+    /// A flag is set with `true`; one named `no-<name>` is set with
+    /// `<name> = false`, and setting a flag to its default does nothing.
+    pub fn to_args(&self, options: &[&str], flags: &[&str]) -> Result<Vec<String>, Error> {
+        let mut args = Vec::new();
+
+        for (line, key, value) in &self.entries {
+            let negated = negation(key);
+            match value {
+                Value::Text(text) if options.contains(&key.as_str()) => {
+                    args.push(format!("--{key}"));
+                    args.push(text.clone());
+                }
+                Value::Bool(set) if flags.contains(&key.as_str()) => {
+                    if *set {
+                        args.push(format!("--{key}"));
+                    }
+                }
+                Value::Bool(set) if flags.contains(&negated.as_str()) => {
+                    if !*set {
+                        args.push(format!("--{negated}"));
+                    }
+                }
+                _ if options.contains(&key.as_str()) => {
+                    return Err(invalid(*line, &format!("`{key}` requires a value")));
+                }
+                _ if flags.contains(&key.as_str()) || flags.contains(&negated.as_str()) => {
+                    return Err(invalid(*line, &format!("`{key}` requires true or false")));
+                }
+                _ => return Err(invalid(*line, &format!("unknown option `{key}`"))),
+            }
+        }
+
+        Ok(args)
+    }
+}
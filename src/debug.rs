@@ -0,0 +1,398 @@
+//! Annotated hexdumps, for working out why a particular file does not
+//! decode.
+//!
+//! The file is split into spans, like headers, pixel data and trailers, as
+//! the format it is detected as lays them out. Files no format accepts are
+//! laid out as sprite sheets, which is what most of the game's files are,
+//! ignoring the size in the sheet header if it is wrong.
+
+use std::io::{self, Write};
+
+use crate::ega;
+use crate::format::{FormatRegistry, REGISTER_WORDS_SIZE};
+use crate::hercules;
+use crate::sprite::{Entry, SizeOrder, SpriteSheet, FRAME_HEADER_SIZE, SHEET_HEADER_SIZE};
+
+/// Spans longer than this many rows are shortened unless the full dump is
+/// asked for.
+const COLLAPSED_ROWS: usize = 4;
+
+const ROW_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    Header,
+    FrameHeader,
+    Pixels,
+    Trailer,
+    Padding,
+    /// Data the decoder rejects or never gets to.
+    Problem,
+    /// Data the layout does not account for.
+    Unknown,
+}
+
+impl SpanKind {
+    fn color(self) -> &'static str {
+        match self {
+            SpanKind::Header => "\x1b[33m",
+            SpanKind::FrameHeader => "\x1b[36m",
+            SpanKind::Pixels => "",
+            SpanKind::Trailer => "\x1b[35m",
+            SpanKind::Padding | SpanKind::Unknown => "\x1b[2m",
+            SpanKind::Problem => "\x1b[31m",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub len: usize,
+    pub kind: SpanKind,
+    pub label: String,
+}
+
+#[derive(Default)]
+pub struct Annotated {
+    /// Consecutive spans covering the whole file.
+    pub spans: Vec<Span>,
+    /// What detection made of the file, and sizes that don't add up.
+    pub findings: Vec<String>,
+}
+
+impl Annotated {
+    fn span(&mut self, start: usize, len: usize, kind: SpanKind, label: String) {
+        if len > 0 {
+            self.spans.push(Span {
+                start,
+                len,
+                kind,
+                label,
+            });
+        }
+    }
+
+    /// Orders the spans and marks whatever they leave out.
+    fn fill_gaps(&mut self, len: usize) {
+        self.spans.sort_by_key(|s| s.start);
+
+        let mut gaps = Vec::new();
+        let mut pos = 0;
+        for span in &self.spans {
+            if span.start > pos {
+                gaps.push((pos, span.start - pos));
+            }
+            pos = pos.max(span.start + span.len);
+        }
+        if len > pos {
+            gaps.push((pos, len - pos));
+        }
+
+        for (start, len) in gaps {
+            self.span(
+                start,
+                len,
+                SpanKind::Unknown,
+                format!("{len} unaccounted bytes"),
+            );
+        }
+        self.spans.sort_by_key(|s| s.start);
+    }
+
+    /// Writes the findings followed by the dump of every span. Without
+    /// `full`, long spans are shortened.
+    pub fn write_hexdump<W: Write>(
+        &self,
+        w: &mut W,
+        src: &[u8],
+        color: bool,
+        full: bool,
+    ) -> io::Result<()> {
+        for finding in &self.findings {
+            writeln!(w, "{finding}")?;
+        }
+
+        let (reset, bold) = if color {
+            ("\x1b[0m", "\x1b[1m")
+        } else {
+            ("", "")
+        };
+
+        for span in &self.spans {
+            let start_color = if color { span.kind.color() } else { "" };
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{bold}{start_color}{:08x}  {}{reset}",
+                span.start, span.label
+            )?;
+
+            let data = &src[span.start..span.start + span.len];
+            let rows = data.len().div_ceil(ROW_SIZE);
+            let shown = if full || rows <= COLLAPSED_ROWS {
+                rows
+            } else {
+                COLLAPSED_ROWS - 1
+            };
+
+            for (n, row) in data.chunks(ROW_SIZE).take(shown).enumerate() {
+                let hex = row
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let ascii = row
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                    .collect::<String>();
+                writeln!(
+                    w,
+                    "  {:08x}  {start_color}{hex:<47}{reset}  {ascii}",
+                    span.start + n * ROW_SIZE
+                )?;
+            }
+            if shown < rows {
+                writeln!(
+                    w,
+                    "  ...       {} more bytes",
+                    data.len() - shown * ROW_SIZE
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `src` into spans, see the module documentation.
+pub fn annotate(src: &[u8], registry: &FormatRegistry) -> Annotated {
+    let mut a = Annotated::default();
+
+    let mut detected = None;
+    for format in registry.formats() {
+        match format.detect(src) {
+            Ok(()) => {
+                a.findings.push(format!(
+                    "{}: detected, {}",
+                    format.name(),
+                    format.description()
+                ));
+                detected = detected.or(Some(format.name()));
+            }
+            Err(reason) => a.findings.push(format!("{}: {reason}", format.name())),
+        }
+    }
+
+    match detected {
+        Some("screen") => annotate_screen(&mut a, src),
+        Some("prefixed-screen") => annotate_prefixed_screen(&mut a, src),
+        Some("hercules") => annotate_hercules(&mut a),
+        _ => annotate_sheet(&mut a, src),
+    }
+
+    a.fill_gaps(src.len());
+    a
+}
+
+fn annotate_planes(a: &mut Annotated, start: usize, width: usize, height: usize) {
+    let plane_size = ega::planar_size(width, height) / 4;
+    for plane in 0..4 {
+        a.span(
+            start + plane * plane_size,
+            plane_size,
+            SpanKind::Pixels,
+            format!("plane {plane} of a {width}x{height} screen, bit {plane} of each pixel"),
+        );
+    }
+}
+
+fn registers_label(registers: &[u8]) -> String {
+    registers
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn annotate_screen(a: &mut Annotated, src: &[u8]) {
+    let layout = ega::fullscreen_layout(src.len())
+        .or_else(|| ega::fullscreen_layout(src.len().saturating_sub(ega::PALETTE_REMAP_SIZE)));
+    let Some((width, height)) = layout else {
+        return;
+    };
+
+    annotate_planes(a, 0, width, height);
+
+    let size = ega::planar_size(width, height);
+    if let Some(registers) = src.get(size..).filter(|r| !r.is_empty()) {
+        a.span(
+            size,
+            registers.len(),
+            SpanKind::Header,
+            format!("palette registers {}", registers_label(registers)),
+        );
+    }
+}
+
+fn annotate_prefixed_screen(a: &mut Annotated, src: &[u8]) {
+    let start = src.len() - ega::FULLSCREEN_SIZE;
+    if start > REGISTER_WORDS_SIZE {
+        a.span(0, 2, SpanKind::Header, "header word, ignored".to_string());
+    }
+
+    let words = &src[start - REGISTER_WORDS_SIZE..start];
+    let registers = words.chunks(2).map(|w| w[0]).collect::<Vec<_>>();
+    a.span(
+        start - REGISTER_WORDS_SIZE,
+        REGISTER_WORDS_SIZE,
+        SpanKind::Header,
+        format!(
+            "palette registers {}, little-endian words",
+            registers_label(&registers)
+        ),
+    );
+
+    annotate_planes(a, start, ega::FULLSCREEN_WIDTH, ega::FULLSCREEN_HEIGHT);
+}
+
+fn annotate_hercules(a: &mut Annotated) {
+    let bank_size = hercules::HERCULES_SIZE / 4;
+    for bank in 0..4 {
+        a.span(
+            bank * bank_size,
+            bank_size,
+            SpanKind::Pixels,
+            format!(
+                "bank {bank}, rows {bank}, {}, {} and so on",
+                bank + 4,
+                bank + 8
+            ),
+        );
+    }
+}
+
+fn annotate_sheet(a: &mut Annotated, src: &[u8]) {
+    let Some(header) = src.get(..SHEET_HEADER_SIZE) else {
+        a.findings
+            .push("too small for a sprite sheet header".to_string());
+        return;
+    };
+
+    let header = header.try_into().unwrap();
+    let actual = src.len() - SHEET_HEADER_SIZE;
+    let (declared, order) = match SizeOrder::of(header, actual) {
+        Some(SizeOrder::LittleEndian) => (actual, "little-endian"),
+        _ => (u32::from_be_bytes(header) as usize, "big-endian"),
+    };
+    a.span(
+        0,
+        SHEET_HEADER_SIZE,
+        if declared == actual {
+            SpanKind::Header
+        } else {
+            SpanKind::Problem
+        },
+        format!("sheet size {declared}, {order}"),
+    );
+    if declared != actual {
+        a.findings.push(format!(
+            "sheet: declares {declared} bytes after the header, the file has {actual}"
+        ));
+    }
+
+    // Walk the frames even if the declared size is off, as far as both
+    // reach.
+    let body_len = declared.min(actual);
+    let mut sheet_src = src[..SHEET_HEADER_SIZE + body_len].to_vec();
+    sheet_src[..SHEET_HEADER_SIZE].copy_from_slice(&(body_len as u32).to_be_bytes());
+    let Ok(sheet) = SpriteSheet::parse(&sheet_src) else {
+        return;
+    };
+
+    let mut entries = sheet.entries();
+    loop {
+        let start = entries.offset();
+        let Some(entry) = entries.next() else {
+            break;
+        };
+        let end = entries.offset();
+
+        match entry {
+            Entry::Frame(f) => {
+                let size = FRAME_HEADER_SIZE + f.data.len();
+                let pixels = f.span * f.height;
+                a.span(
+                    start,
+                    FRAME_HEADER_SIZE,
+                    SpanKind::FrameHeader,
+                    format!(
+                        "frame {}: size {size}, {} words ({} pixels) wide, {} rows",
+                        f.index,
+                        f.span / 2,
+                        f.width,
+                        f.height
+                    ),
+                );
+                a.span(
+                    start + FRAME_HEADER_SIZE,
+                    pixels,
+                    SpanKind::Pixels,
+                    format!(
+                        "frame {} pixels, {} rows of {} bytes",
+                        f.index, f.height, f.span
+                    ),
+                );
+                let trailer = f.trailer().len();
+                let hitboxes = f.hitboxes().len();
+                a.span(
+                    start + FRAME_HEADER_SIZE + pixels,
+                    trailer,
+                    SpanKind::Trailer,
+                    match hitboxes {
+                        0 => format!("frame {} trailer, {trailer} bytes", f.index),
+                        n => format!("frame {} trailer, {n} hit boxes", f.index),
+                    },
+                );
+            }
+            Entry::Skipped { index, reason } => {
+                a.span(
+                    start,
+                    end - start,
+                    SpanKind::Problem,
+                    format!("frame {index}: {reason}, skipped"),
+                );
+                a.findings
+                    .push(format!("frame {index} at {start:#x}: {reason}"));
+            }
+            Entry::Stopped { index, reason } => {
+                a.span(
+                    start,
+                    SHEET_HEADER_SIZE + body_len - start,
+                    SpanKind::Problem,
+                    format!("frame {index}: {reason}, the rest is unreachable"),
+                );
+                a.findings
+                    .push(format!("frame {index} at {start:#x}: {reason}"));
+                break;
+            }
+            Entry::Padding { len } => {
+                a.span(
+                    start,
+                    len,
+                    SpanKind::Padding,
+                    format!("{len} bytes of padding"),
+                );
+            }
+        }
+    }
+
+    if actual > declared {
+        a.span(
+            SHEET_HEADER_SIZE + declared,
+            actual - declared,
+            SpanKind::Problem,
+            format!("{} bytes beyond the declared size", actual - declared),
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! Floppy disk images, so the files of the game disks can be extracted
+//! from a dump without mounting it or copying them off first.
+//!
+//! DOS `.img` and Atari ST `.st` images are the raw sectors of a FAT12
+//! file system, recognized by the BIOS parameter block in the boot sector.
+//! With the `adf` feature, Amiga `.adf` images of the original and the
+//! fast file system are read as well. Deleted files, long file names and
+//! checksums are ignored.
+
+use std::io::{Error, ErrorKind};
+
+const SECTOR_SIZE: usize = 512;
+
+// Directories nested deeper than this are taken to be loops.
+const MAX_DEPTH: usize = 16;
+
+// FAT12 cluster numbers from here on end a chain.
+const FAT12_END: usize = 0xff8;
+const FAT12_MAX_CLUSTERS: usize = 4084;
+
+const FAT_ATTR_VOLUME: u8 = 0x08;
+const FAT_ATTR_DIRECTORY: u8 = 0x10;
+const FAT_ATTR_LONG_NAME: u8 = 0x0f;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiskKind {
+    Fat12,
+    #[cfg(feature = "adf")]
+    Adf,
+}
+
+pub struct DiskFile {
+    /// Path on the disk, with `/` separating directories.
+    pub name: String,
+    /// The contents, or why they could not be read.
+    pub data: Result<Vec<u8>, Error>,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn le_u16(src: &[u8], pos: usize) -> usize {
+    u16::from_le_bytes([src[pos], src[pos + 1]]) as usize
+}
+
+fn le_u32(src: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes(src[pos..pos + 4].try_into().unwrap()) as usize
+}
+
+/// Names on disk are in the code page of the machine, which for file
+/// names of games rarely matters; bytes outside ASCII are taken as
+/// Latin-1.
+fn decode_name(name: &[u8]) -> String {
+    name.iter().map(|&b| b as char).collect()
+}
+
+/// Recognizes a disk image by its boot sector, or for Amiga disks by its
+/// boot block and size.
+pub fn detect(src: &[u8]) -> Option<DiskKind> {
+    #[cfg(feature = "adf")]
+    if adf::Adf::parse(src).is_some() {
+        return Some(DiskKind::Adf);
+    }
+    Fat12::parse(src).map(|_| DiskKind::Fat12)
+}
+
+/// Reads the files of a disk image, in the order of their directories.
+/// A file that can't be read has the reason instead of its data; an error
+/// is returned only if the file system itself is unreadable.
+pub fn files(src: &[u8]) -> Result<Vec<DiskFile>, Error> {
+    let mut files = Vec::new();
+    match detect(src) {
+        Some(DiskKind::Fat12) => {
+            let fat = Fat12::parse(src).unwrap();
+            fat.read_dir(fat.root, "", 0, &mut files);
+        }
+        #[cfg(feature = "adf")]
+        Some(DiskKind::Adf) => {
+            let adf = adf::Adf::parse(src).unwrap();
+            adf.read_dir(adf.root, "", 0, &mut files)?;
+        }
+        None => return Err(invalid("not a FAT12 disk image")),
+    }
+    Ok(files)
+}
+
+struct Fat12<'a> {
+    src: &'a [u8],
+    fat: &'a [u8],
+    root: &'a [u8],
+    data_start: usize,
+    cluster_size: usize,
+    clusters: usize,
+}
+
+impl<'a> Fat12<'a> {
+    fn parse(src: &'a [u8]) -> Option<Fat12<'a>> {
+        if src.len() < SECTOR_SIZE || !src.len().is_multiple_of(SECTOR_SIZE) {
+            return None;
+        }
+
+        let sector_size = le_u16(src, 11);
+        let sectors_per_cluster = src[13] as usize;
+        let reserved = le_u16(src, 14);
+        let fats = src[16] as usize;
+        let root_entries = le_u16(src, 17);
+        let total = match le_u16(src, 19) {
+            0 => le_u32(src, 32),
+            n => n,
+        };
+        let media = src[21];
+        let sectors_per_fat = le_u16(src, 22);
+
+        let valid = (128..=4096).contains(&sector_size)
+            && sector_size.is_power_of_two()
+            && sectors_per_cluster.is_power_of_two()
+            && reserved > 0
+            && (1..=2).contains(&fats)
+            && root_entries > 0
+            && matches!(media, 0xf0 | 0xf8..=0xff)
+            && sectors_per_fat > 0
+            && total * sector_size <= src.len();
+        if !valid {
+            return None;
+        }
+
+        let fat_start = reserved * sector_size;
+        let fat_size = sectors_per_fat * sector_size;
+        let root_start = fat_start + fats * fat_size;
+        let data_start = root_start + (32 * root_entries).next_multiple_of(sector_size);
+        let cluster_size = sectors_per_cluster * sector_size;
+        let clusters = (total * sector_size).checked_sub(data_start)? / cluster_size;
+        if clusters == 0 || clusters > FAT12_MAX_CLUSTERS || 2 * fat_size < 3 * (clusters + 2) {
+            return None;
+        }
+
+        Some(Fat12 {
+            src,
+            fat: &src[fat_start..fat_start + fat_size],
+            root: &src[root_start..root_start + 32 * root_entries],
+            data_start,
+            cluster_size,
+            clusters,
+        })
+    }
+
+    fn next_cluster(&self, cluster: usize) -> usize {
+        let v = le_u16(self.fat, cluster + cluster / 2);
+        if cluster.is_multiple_of(2) {
+            v & 0xfff
+        } else {
+            v >> 4
+        }
+    }
+
+    /// Reads the clusters of a chain, up to `size` bytes if given.
+    fn read_chain(&self, first: usize, size: Option<usize>) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let mut cluster = first;
+        while size.is_none_or(|size| data.len() < size) {
+            if !(2..self.clusters + 2).contains(&cluster) {
+                return match size {
+                    Some(_) => Err(invalid("file is shorter than its directory entry")),
+                    None => Err(invalid("invalid cluster chain")),
+                };
+            }
+            if data.len() >= self.clusters * self.cluster_size {
+                return Err(invalid("cluster chain loops"));
+            }
+            let start = self.data_start + (cluster - 2) * self.cluster_size;
+            data.extend_from_slice(&self.src[start..start + self.cluster_size]);
+
+            cluster = self.next_cluster(cluster);
+            if cluster >= FAT12_END && size.is_none() {
+                break;
+            }
+        }
+        if let Some(size) = size {
+            data.truncate(size);
+        }
+        Ok(data)
+    }
+
+    fn read_dir(&self, dir: &[u8], prefix: &str, depth: usize, files: &mut Vec<DiskFile>) {
+        for entry in dir.chunks_exact(32) {
+            let attr = entry[11];
+            match entry[0] {
+                0 => break,
+                0xe5 | b'.' => continue,
+                _ if attr == FAT_ATTR_LONG_NAME || attr & FAT_ATTR_VOLUME != 0 => continue,
+                _ => {}
+            }
+
+            let mut base = entry[..8].to_vec();
+            if base[0] == 0x05 {
+                base[0] = 0xe5;
+            }
+            let base = decode_name(&base);
+            let ext = decode_name(&entry[8..11]);
+            let mut name = format!("{prefix}{}", base.trim_end());
+            if !ext.trim_end().is_empty() {
+                name = format!("{name}.{}", ext.trim_end());
+            }
+
+            let first = le_u16(entry, 26);
+            if attr & FAT_ATTR_DIRECTORY != 0 {
+                let subdir = match depth {
+                    MAX_DEPTH => Err(invalid("directories nested too deep")),
+                    _ => self.read_chain(first, None),
+                };
+                match subdir {
+                    Ok(subdir) => self.read_dir(&subdir, &format!("{name}/"), depth + 1, files),
+                    Err(e) => files.push(DiskFile { name, data: Err(e) }),
+                }
+                continue;
+            }
+
+            let data = match le_u32(entry, 28) {
+                0 => Ok(Vec::new()),
+                size => self.read_chain(first, Some(size)),
+            };
+            files.push(DiskFile { name, data });
+        }
+    }
+}
+
+#[cfg(feature = "adf")]
+mod adf {
+    use std::io::Error;
+
+    use super::{decode_name, invalid, DiskFile, MAX_DEPTH, SECTOR_SIZE};
+
+    const DD_SIZE: usize = 80 * 2 * 11 * SECTOR_SIZE;
+    const HD_SIZE: usize = 2 * DD_SIZE;
+
+    const T_HEADER: u32 = 2;
+    const T_LIST: u32 = 16;
+    const ST_ROOT: u32 = 1;
+    const ST_USERDIR: u32 = 2;
+    const ST_FILE: u32 = -3i32 as u32;
+
+    // Offsets within header blocks.
+    const HIGH_SEQ: usize = 8;
+    const TABLE: usize = 24;
+    const TABLE_SIZE: usize = 72;
+    const BYTE_SIZE: usize = SECTOR_SIZE - 188;
+    const NAME: usize = SECTOR_SIZE - 80;
+    const HASH_CHAIN: usize = SECTOR_SIZE - 16;
+    const EXTENSION: usize = SECTOR_SIZE - 8;
+    const SEC_TYPE: usize = SECTOR_SIZE - 4;
+
+    // Data blocks of the original file system start with a header.
+    const OFS_HEADER: usize = 24;
+
+    fn be_u32(block: &[u8], pos: usize) -> u32 {
+        u32::from_be_bytes(block[pos..pos + 4].try_into().unwrap())
+    }
+
+    pub(super) struct Adf<'a> {
+        src: &'a [u8],
+        ffs: bool,
+        pub(super) root: usize,
+    }
+
+    impl<'a> Adf<'a> {
+        pub(super) fn parse(src: &'a [u8]) -> Option<Adf<'a>> {
+            if !src.starts_with(b"DOS") || src[3] > 7 || ![DD_SIZE, HD_SIZE].contains(&src.len()) {
+                return None;
+            }
+            let adf = Adf {
+                src,
+                ffs: src[3] & 1 != 0,
+                root: src.len() / SECTOR_SIZE / 2,
+            };
+            let root = adf.block(adf.root).ok()?;
+            (be_u32(root, 0) == T_HEADER && be_u32(root, SEC_TYPE) == ST_ROOT).then_some(adf)
+        }
+
+        fn block(&self, n: usize) -> Result<&'a [u8], Error> {
+            self.src
+                .get(n * SECTOR_SIZE..(n + 1) * SECTOR_SIZE)
+                .filter(|_| n > 1)
+                .ok_or_else(|| invalid("block number out of range"))
+        }
+
+        fn blocks(&self) -> usize {
+            self.src.len() / SECTOR_SIZE
+        }
+
+        pub(super) fn read_dir(
+            &self,
+            dir: usize,
+            prefix: &str,
+            depth: usize,
+            files: &mut Vec<DiskFile>,
+        ) -> Result<(), Error> {
+            let dir = self.block(dir)?;
+            for slot in 0..TABLE_SIZE {
+                let mut key = be_u32(dir, TABLE + 4 * slot) as usize;
+                let mut chained = 0;
+                while key != 0 {
+                    chained += 1;
+                    if chained > self.blocks() {
+                        return Err(invalid("hash chain loops"));
+                    }
+
+                    let header = self.block(key)?;
+                    if be_u32(header, 0) != T_HEADER {
+                        return Err(invalid("invalid directory entry"));
+                    }
+                    let len = (header[NAME] as usize).min(30);
+                    let name =
+                        format!("{prefix}{}", decode_name(&header[NAME + 1..NAME + 1 + len]));
+
+                    match be_u32(header, SEC_TYPE) {
+                        ST_USERDIR if depth < MAX_DEPTH => {
+                            if let Err(e) =
+                                self.read_dir(key, &format!("{name}/"), depth + 1, files)
+                            {
+                                files.push(DiskFile { name, data: Err(e) });
+                            }
+                        }
+                        ST_USERDIR => files.push(DiskFile {
+                            name,
+                            data: Err(invalid("directories nested too deep")),
+                        }),
+                        ST_FILE => files.push(DiskFile {
+                            name,
+                            data: self.read_file(header),
+                        }),
+                        // Links are left out.
+                        _ => {}
+                    }
+
+                    key = be_u32(header, HASH_CHAIN) as usize;
+                }
+            }
+            Ok(())
+        }
+
+        fn read_file(&self, header: &[u8]) -> Result<Vec<u8>, Error> {
+            let size = be_u32(header, BYTE_SIZE) as usize;
+            if size > self.src.len() {
+                return Err(invalid("file is larger than the disk"));
+            }
+
+            // The data blocks are listed in file headers and the extension
+            // blocks that follow, from the end of the table backwards.
+            let mut data = Vec::with_capacity(size);
+            let mut table = header;
+            let mut extensions = 0;
+            while data.len() < size {
+                let count = (be_u32(table, HIGH_SEQ) as usize).min(TABLE_SIZE);
+                for n in 0..count {
+                    let block =
+                        self.block(be_u32(table, TABLE + 4 * (TABLE_SIZE - 1 - n)) as usize)?;
+                    if self.ffs {
+                        data.extend_from_slice(block);
+                    } else {
+                        let len = (be_u32(block, 12) as usize).min(SECTOR_SIZE - OFS_HEADER);
+                        data.extend_from_slice(&block[OFS_HEADER..OFS_HEADER + len]);
+                    }
+                }
+
+                let next = be_u32(table, EXTENSION) as usize;
+                if data.len() >= size || next == 0 {
+                    break;
+                }
+                extensions += 1;
+                if extensions > self.blocks() {
+                    return Err(invalid("extension blocks loop"));
+                }
+                table = self.block(next)?;
+                if be_u32(table, 0) != T_LIST {
+                    return Err(invalid("invalid extension block"));
+                }
+            }
+
+            if data.len() < size {
+                return Err(invalid("file is shorter than its header"));
+            }
+            data.truncate(size);
+            Ok(data)
+        }
+    }
+}
@@ -0,0 +1,349 @@
+/// The size of a planar image and how far apart its four planes are, plane
+/// 0 holding the least significant bit of each pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub width: usize,
+    pub height: usize,
+    /// Bytes from the start of one plane to the next, at least
+    /// `width * height / 8`.
+    pub plane_size: usize,
+}
+
+impl Layout {
+    /// Planes right after each other, as Kult stores them.
+    pub const fn new(width: usize, height: usize) -> Layout {
+        Layout {
+            width,
+            height,
+            plane_size: width * height / 8,
+        }
+    }
+
+    /// Planes `plane_size` bytes apart, for files padding each plane, like
+    /// to the 8192 bytes of a plane in video memory.
+    pub const fn with_plane_size(self, plane_size: usize) -> Layout {
+        Layout { plane_size, ..self }
+    }
+
+    /// Size in bytes of the four planes.
+    pub const fn size(self) -> usize {
+        4 * self.plane_size
+    }
+
+    /// Decodes the image at the start of `src` to palette indices. Bytes
+    /// missing from a short `src` decode as zero.
+    pub fn decode_to_indexed(self, src: &[u8]) -> Vec<u8> {
+        let (width, height) = (self.width, self.height);
+        let byte = |i: usize| src.get(i).copied().unwrap_or(0);
+
+        let mut frame = vec![0u8; width * height];
+
+        for y in 0..height {
+            for x in 0..width {
+                let ofs = (width * y + x) / 8;
+                let bitofs = 7 - x % 8;
+
+                frame[y * width + x] = (0..4).fold(0, |v, plane| {
+                    v | ((byte(plane * self.plane_size + ofs) >> bitofs) & 1) << plane
+                });
+            }
+        }
+
+        frame
+    }
+
+    pub fn decode_to_rgba(self, src: &[u8], pal: &[[u8; 4]; 16]) -> Vec<u8> {
+        indexed_to_rgba_with_palette(&self.decode_to_indexed(src), pal)
+    }
+}
+
+/// The 320x200 16 color mode Kult runs in.
+pub const FULLSCREEN: Layout = Layout::new(320, 200);
+/// The 640x200 16 color mode.
+pub const HIRES: Layout = Layout::new(640, 200);
+/// The 640x350 16 color mode of the EGA with an enhanced color display.
+pub const HIRES_350: Layout = Layout::new(640, 350);
+
+pub const FULLSCREEN_WIDTH: usize = FULLSCREEN.width;
+pub const FULLSCREEN_HEIGHT: usize = FULLSCREEN.height;
+/// Size in bytes of a 320x200 planar screen.
+pub const FULLSCREEN_SIZE: usize = FULLSCREEN.size();
+
+/// The planar fullscreen layouts, which are told apart by their size.
+pub const FULLSCREEN_LAYOUTS: [(usize, usize); 3] = [
+    (FULLSCREEN.width, FULLSCREEN.height),
+    (HIRES.width, HIRES.height),
+    (HIRES_350.width, HIRES_350.height),
+];
+
+/// Size in bytes of a four plane image. Sizes too large to address
+/// saturate, so they never match a file.
+pub fn planar_size(width: usize, height: usize) -> usize {
+    width
+        .checked_mul(height)
+        .map_or(usize::MAX, |n| 4 * (n / 8))
+}
+
+/// Parses a planar image size, `WxH` with W a multiple of 8 as planes
+/// hold whole bytes per row.
+pub fn parse_size(s: &str) -> Option<(usize, usize)> {
+    let (w, h) = s.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0 && w % 8 == 0).then_some((w, h))
+}
+
+/// Returns the fullscreen layout matching a file size.
+pub fn fullscreen_layout(len: usize) -> Option<(usize, usize)> {
+    FULLSCREEN_LAYOUTS
+        .into_iter()
+        .find(|&(w, h)| planar_size(w, h) == len)
+}
+
+/// How a monitor shows the colors. Kult runs in a 200 line mode, in which
+/// the EGA outputs RGBI like a CGA: the palette registers select red, green
+/// and blue with bits 2, 1 and 0 and intensity with bit 4.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Monitor {
+    /// The IBM 5153 as measured by int10h.org, which shows dark yellow as
+    /// brown.
+    #[default]
+    Ibm5153,
+    /// Levels 0x00, 0x55, 0xaa and 0xff, keeping dark yellow.
+    GenericEga,
+    /// The 6-bit levels of the VGA DAC, with brown.
+    VgaDac,
+}
+
+// Based on https://int10h.org/blog/2022/06/ibm-5153-color-true-cga-palette/
+// Indexed by IRGB.
+const IBM5153_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xc4],
+    [0x00, 0xc4, 0x00],
+    [0x00, 0xc4, 0xc4],
+    [0xc4, 0x00, 0x00],
+    [0xc4, 0x00, 0xc4],
+    [0xc4, 0x7e, 0x00],
+    [0xc4, 0xc4, 0xc4],
+    [0x4e, 0x4e, 0x4e],
+    [0x4e, 0x4e, 0xdc],
+    [0x4e, 0xdc, 0x4e],
+    [0x4e, 0xf3, 0xf3],
+    [0xdc, 0x4e, 0x4e],
+    [0xf3, 0x4e, 0xf3],
+    [0xf3, 0xf3, 0x4e],
+    [0xff, 0xff, 0xff],
+];
+
+/// The IRGB color a palette register value is output as.
+const fn irgb(value: u8) -> usize {
+    (((value >> 4) & 1) << 3 | (value & 7)) as usize
+}
+
+impl Monitor {
+    pub fn from_name(name: &str) -> Option<Monitor> {
+        match name {
+            "ibm5153" => Some(Monitor::Ibm5153),
+            "generic-ega" => Some(Monitor::GenericEga),
+            "vga-dac" => Some(Monitor::VgaDac),
+            _ => None,
+        }
+    }
+
+    /// The color shown for a palette register value.
+    pub const fn color(self, value: u8) -> [u8; 3] {
+        let c = irgb(value);
+        let (on, intensity, brown) = match self {
+            Monitor::Ibm5153 => return IBM5153_COLORS[c],
+            Monitor::GenericEga => (0xaa, 0x55, false),
+            Monitor::VgaDac => (0xa8, 0x54, true),
+        };
+
+        if brown && c == 6 {
+            return [on, intensity, 0];
+        }
+
+        let i = if c & 8 != 0 { intensity } else { 0 };
+        let level = [i, on + i];
+        [level[(c >> 2) & 1], level[(c >> 1) & 1], level[c & 1]]
+    }
+}
+
+/// The palette registers as set up by the BIOS for 200 line modes.
+pub const DEFAULT_PALETTE_REGISTERS: [u8; 16] =
+    [0, 1, 2, 3, 4, 5, 6, 7, 16, 17, 18, 19, 20, 21, 22, 23];
+
+// Index 0 is transparent, and index 8 is black rather than dark gray
+// unless the file loads a color of its own into it.
+const fn kult_colors(mut pal: [[u8; 4]; 16], register_8: u8) -> [[u8; 4]; 16] {
+    pal[0][3] = 0;
+    if register_8 == DEFAULT_PALETTE_REGISTERS[8] {
+        pal[8] = [0x00, 0x00, 0x00, 0xff];
+    }
+    pal
+}
+
+/// The palette with the default registers on `monitor`, the same as
+/// [`palette`] with a gamma of 1.
+pub const fn default_palette(monitor: Monitor) -> [[u8; 4]; 16] {
+    let mut pal = [[0; 4]; 16];
+    let mut i = 0;
+    while i < 16 {
+        let [r, g, b] = monitor.color(DEFAULT_PALETTE_REGISTERS[i]);
+        pal[i] = [r, g, b, 0xff];
+        i += 1;
+    }
+    kult_colors(pal, DEFAULT_PALETTE_REGISTERS[8])
+}
+
+/// The palette with the default registers on an IBM 5153.
+pub const EGA_PAL: [[u8; 4]; 16] = default_palette(Monitor::Ibm5153);
+
+/// Computes the palette for a set of palette registers on a monitor.
+/// A `gamma` above 1 brightens the colors.
+pub fn palette(monitor: Monitor, registers: &[u8; 16], gamma: f64) -> [[u8; 4]; 16] {
+    let adjust = |c: u8| {
+        if gamma == 1.0 {
+            c
+        } else {
+            (255.0 * (c as f64 / 255.0).powf(1.0 / gamma)).round() as u8
+        }
+    };
+
+    let pal = registers.map(|v| {
+        let [r, g, b] = monitor.color(v).map(adjust);
+        [r, g, b, 0xff]
+    });
+    kult_colors(pal, registers[8])
+}
+
+/// A color transform applied to a palette, for previews of how the colors
+/// look to people with color vision deficiencies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    Grayscale,
+    Protanopia,
+    Deuteranopia,
+}
+
+// Full severity simulations from Machado, Oliveira and Fernandes, "A
+// Physiologically-based Model for Simulation of Color Vision Deficiency",
+// 2009. They apply to linear RGB.
+const PROTANOPIA: [[f64; 3]; 3] = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: [[f64; 3]; 3] = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+// Rec. 709 luminance.
+const GRAYSCALE: [[f64; 3]; 3] = [[0.2126, 0.7152, 0.0722]; 3];
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (255.0 * c).round() as u8
+}
+
+impl Transform {
+    pub fn from_name(name: &str) -> Option<Transform> {
+        match name {
+            "grayscale" => Some(Transform::Grayscale),
+            "protanopia" => Some(Transform::Protanopia),
+            "deuteranopia" => Some(Transform::Deuteranopia),
+            _ => None,
+        }
+    }
+
+    /// Transforms the colors of a palette, keeping their alpha.
+    pub fn apply(self, pal: &[[u8; 4]; 16]) -> [[u8; 4]; 16] {
+        let m = match self {
+            Transform::Grayscale => &GRAYSCALE,
+            Transform::Protanopia => &PROTANOPIA,
+            Transform::Deuteranopia => &DEUTERANOPIA,
+        };
+
+        pal.map(|[r, g, b, a]| {
+            let rgb = [r, g, b].map(srgb_to_linear);
+            let [r, g, b] = m.map(|row| linear_to_srgb((0..3).map(|i| row[i] * rgb[i]).sum()));
+            [r, g, b, a]
+        })
+    }
+}
+
+pub fn indexed_to_rgba(src: &[u8]) -> Vec<u8> {
+    indexed_to_rgba_with_palette(src, &EGA_PAL)
+}
+
+pub fn indexed_to_rgba_with_palette(src: &[u8], pal: &[[u8; 4]; 16]) -> Vec<u8> {
+    src.iter().flat_map(|&v| pal[v as usize & 0x0f]).collect()
+}
+
+/// Size of a table of the 16 EGA palette registers.
+pub const PALETTE_REMAP_SIZE: usize = 16;
+
+/// Parses a palette register table, each register holds a 6-bit value.
+pub fn parse_palette_remap(src: &[u8]) -> Option<[u8; 16]> {
+    let remap: [u8; 16] = src.try_into().ok()?;
+    remap.iter().all(|&v| v < 64).then_some(remap)
+}
+
+/// Decodes four consecutive bitplanes, plane 0 being the least significant
+/// bit. Each plane is `width * height / 8` bytes; bytes missing from a
+/// short `src` decode as zero.
+pub fn decode_planar_ega_to_indexed(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    Layout::new(width, height).decode_to_indexed(src)
+}
+
+pub fn decode_planar_ega_to_rgba(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    indexed_to_rgba(&decode_planar_ega_to_indexed(src, width, height))
+}
+
+/// Decodes packed 4bpp pixels, two per byte with the high nibble first.
+/// Rows are `span` bytes apart; `width` is the pixel width, which may be
+/// odd or smaller than `2 * span`, in which case the trailing padding
+/// nibbles of each row are ignored. Bytes missing from a short `src`, and
+/// pixels beyond the `2 * span` a row holds, decode as zero.
+pub fn decode_interleaved_ega_to_indexed(
+    src: &[u8],
+    span: usize,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let mut frame = vec![0u8; width * height];
+
+    for y in 0..height {
+        for x in 0..width.min(2 * span) {
+            let ofs = y * span + x / 2;
+            let b = src.get(ofs).copied().unwrap_or(0);
+            frame[y * width + x] = if x % 2 == 0 { b >> 4 } else { b & 0x0f };
+        }
+    }
+
+    frame
+}
+
+pub fn decode_interleaved_ega_to_rgba(
+    src: &[u8],
+    span: usize,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    indexed_to_rgba(&decode_interleaved_ega_to_indexed(src, span, width, height))
+}
@@ -0,0 +1,422 @@
+#[cfg(feature = "png")]
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[cfg(feature = "png")]
+use crate::analyze;
+use crate::ega::indexed_to_rgba_with_palette;
+use crate::format::IndexedFrame;
+#[cfg(feature = "png")]
+use crate::output::{write_rgba_to_png, OverwritePolicy, SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    Generic,
+    Godot,
+    Unity,
+}
+
+impl Engine {
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "generic" => Some(Engine::Generic),
+            "godot" => Some(Engine::Godot),
+            "unity" => Some(Engine::Unity),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasRect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+pub struct Atlas {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+    pub rects: Vec<AtlasRect>,
+}
+
+/// A frame of the atlas, as described in the metadata.
+pub struct AtlasEntry {
+    pub name: String,
+    pub rect: AtlasRect,
+    /// Relative to the frame.
+    pub hitboxes: Vec<AtlasRect>,
+    /// Name of the identical frame whose rect this frame shares.
+    pub duplicate_of: Option<String>,
+    /// Name of the frame this one is a horizontally mirrored copy of.
+    pub mirror_of: Option<String>,
+}
+
+// Frames are packed left to right in a single row, top aligned.
+pub fn build_atlas(frames: &[IndexedFrame], pal: &[[u8; 4]; 16]) -> Atlas {
+    let width = frames.iter().map(|f| f.width).sum::<usize>();
+    let height = frames.iter().map(|f| f.height).max().unwrap_or(0);
+
+    let mut data = vec![0u8; width * height];
+    let mut rects = Vec::with_capacity(frames.len());
+
+    let mut ax = 0;
+    for f in frames {
+        for y in 0..f.height {
+            let src = &f.pixels[y * f.width..(y + 1) * f.width];
+            let dst_ofs = y * width + ax;
+            data[dst_ofs..dst_ofs + f.width].copy_from_slice(src);
+        }
+        rects.push(AtlasRect {
+            x: ax,
+            y: 0,
+            w: f.width,
+            h: f.height,
+        });
+        ax += f.width;
+    }
+
+    Atlas {
+        width,
+        height,
+        data: indexed_to_rgba_with_palette(&data, pal),
+        rects,
+    }
+}
+
+pub(crate) fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Unity wants a stable guid per asset, derive one from the name so
+// re-running the extractor does not break references in a project.
+fn unity_guid(name: &str) -> String {
+    let mut h1: u64 = 0xcbf29ce484222325;
+    let mut h2: u64 = 0x84222325cbf29ce4;
+    for b in name.bytes() {
+        h1 = (h1 ^ b as u64).wrapping_mul(0x100000001b3);
+        h2 = (h2 ^ b as u64).wrapping_mul(0x100000001b3).rotate_left(5);
+    }
+    format!("{:016x}{:016x}", h1, h2)
+}
+
+fn write_rect<W: Write>(w: &mut W, r: &AtlasRect) -> Result<(), std::io::Error> {
+    write!(
+        w,
+        "\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}",
+        r.x, r.y, r.w, r.h
+    )
+}
+
+fn write_generic<W: Write>(
+    w: &mut W,
+    image_name: &str,
+    atlas_width: usize,
+    atlas_height: usize,
+    entries: &[AtlasEntry],
+) -> Result<(), std::io::Error> {
+    writeln!(w, "{{")?;
+    writeln!(w, "  \"image\": \"{}\",", json_escape(image_name))?;
+    writeln!(w, "  \"width\": {},", atlas_width)?;
+    writeln!(w, "  \"height\": {},", atlas_height)?;
+    writeln!(w, "  \"frames\": [")?;
+    for (n, e) in entries.iter().enumerate() {
+        write!(w, "    {{ \"name\": \"{}\", ", json_escape(&e.name))?;
+        write_rect(w, &e.rect)?;
+        if !e.hitboxes.is_empty() {
+            write!(w, ", \"hitboxes\": [")?;
+            for (i, r) in e.hitboxes.iter().enumerate() {
+                let sep = if i + 1 < e.hitboxes.len() { ", " } else { "" };
+                write!(w, "{{ ")?;
+                write_rect(w, r)?;
+                write!(w, " }}{sep}")?;
+            }
+            write!(w, "]")?;
+        }
+        if let Some(name) = &e.duplicate_of {
+            write!(w, ", \"duplicate_of\": \"{}\"", json_escape(name))?;
+        }
+        if let Some(name) = &e.mirror_of {
+            write!(w, ", \"mirror_of\": \"{}\"", json_escape(name))?;
+        }
+        let sep = if n + 1 < entries.len() { "," } else { "" };
+        writeln!(w, " }}{sep}")?;
+    }
+    writeln!(w, "  ]")?;
+    writeln!(w, "}}")?;
+    Ok(())
+}
+
+fn write_godot<W: Write>(
+    w: &mut W,
+    image_name: &str,
+    entries: &[AtlasEntry],
+) -> Result<(), std::io::Error> {
+    writeln!(
+        w,
+        "[gd_resource type=\"SpriteFrames\" load_steps={} format=3]",
+        entries.len() + 2
+    )?;
+    writeln!(w)?;
+    writeln!(
+        w,
+        "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"1\"]",
+        image_name
+    )?;
+    writeln!(w)?;
+
+    for (n, e) in entries.iter().enumerate() {
+        let r = &e.rect;
+        writeln!(
+            w,
+            "[sub_resource type=\"AtlasTexture\" id=\"AtlasTexture_{n}\"]"
+        )?;
+        writeln!(w, "atlas = ExtResource(\"1\")")?;
+        writeln!(w, "region = Rect2({}, {}, {}, {})", r.x, r.y, r.w, r.h)?;
+        writeln!(w)?;
+    }
+
+    // Mirrored frames make up an animation of their own.
+    let animations = [("default", false), ("flip", true)]
+        .into_iter()
+        .map(|(name, mirrored)| {
+            let frames = entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.mirror_of.is_some() == mirrored)
+                .map(|(n, _)| n)
+                .collect::<Vec<_>>();
+            (name, frames)
+        })
+        .filter(|(_, frames)| !frames.is_empty())
+        .collect::<Vec<_>>();
+
+    writeln!(w, "[resource]")?;
+    write!(w, "animations = [")?;
+    for (a, (name, frames)) in animations.iter().enumerate() {
+        writeln!(w, "{{")?;
+        writeln!(w, "\"frames\": [")?;
+        for (i, n) in frames.iter().enumerate() {
+            let sep = if i + 1 < frames.len() { "," } else { "" };
+            writeln!(w, "{{")?;
+            writeln!(w, "\"duration\": 1.0,")?;
+            writeln!(w, "\"texture\": SubResource(\"AtlasTexture_{n}\")")?;
+            writeln!(w, "}}{sep}")?;
+        }
+        writeln!(w, "],")?;
+        writeln!(w, "\"loop\": true,")?;
+        writeln!(w, "\"name\": &\"{name}\",")?;
+        writeln!(w, "\"speed\": 5.0")?;
+        let sep = if a + 1 < animations.len() { ", " } else { "" };
+        write!(w, "}}{sep}")?;
+    }
+    writeln!(w, "]")?;
+    Ok(())
+}
+
+fn write_unity<W: Write>(
+    w: &mut W,
+    image_name: &str,
+    atlas_height: usize,
+    entries: &[AtlasEntry],
+) -> Result<(), std::io::Error> {
+    writeln!(w, "fileFormatVersion: 2")?;
+    writeln!(w, "guid: {}", unity_guid(image_name))?;
+    writeln!(w, "TextureImporter:")?;
+    writeln!(w, "  serializedVersion: 12")?;
+    writeln!(w, "  mipmaps:")?;
+    writeln!(w, "    enableMipMap: 0")?;
+    writeln!(w, "  textureSettings:")?;
+    writeln!(w, "    filterMode: 0")?;
+    writeln!(w, "  spriteMode: 2")?;
+    writeln!(w, "  spritePixelsToUnits: 100")?;
+    writeln!(w, "  alphaIsTransparency: 1")?;
+    writeln!(w, "  textureType: 8")?;
+    writeln!(w, "  textureCompression: 0")?;
+    writeln!(w, "  spriteSheet:")?;
+    writeln!(w, "    serializedVersion: 2")?;
+    writeln!(w, "    sprites:")?;
+    for e in entries {
+        let r = &e.rect;
+        // Unity places the origin in the bottom left corner.
+        let y = atlas_height - r.y - r.h;
+        writeln!(w, "    - serializedVersion: 2")?;
+        writeln!(w, "      name: {}", e.name)?;
+        writeln!(w, "      rect:")?;
+        writeln!(w, "        serializedVersion: 2")?;
+        writeln!(w, "        x: {}", r.x)?;
+        writeln!(w, "        y: {}", y)?;
+        writeln!(w, "        width: {}", r.w)?;
+        writeln!(w, "        height: {}", r.h)?;
+        writeln!(w, "      alignment: 0")?;
+        writeln!(w, "      pivot: {{x: 0.5, y: 0.5}}")?;
+        writeln!(w, "      border: {{x: 0, y: 0, z: 0, w: 0}}")?;
+    }
+    Ok(())
+}
+
+pub fn atlas_filename(stem: &str) -> String {
+    format!("{stem}-atlas.png")
+}
+
+pub fn metadata_filename(engine: Engine, stem: &str, image_name: &str) -> String {
+    match engine {
+        Engine::Generic => format!("{stem}.json"),
+        Engine::Godot => format!("{stem}.tres"),
+        Engine::Unity => format!("{image_name}.meta"),
+    }
+}
+
+/// Writes the import metadata for an atlas image that has already been
+/// written to `dir/image_name`. All sizes are in output (scaled) pixels.
+pub fn write_metadata<P: AsRef<Path>>(
+    engine: Engine,
+    dir: P,
+    stem: &str,
+    image_name: &str,
+    atlas_width: usize,
+    atlas_height: usize,
+    entries: &[AtlasEntry],
+) -> Result<(), std::io::Error> {
+    let filename = dir
+        .as_ref()
+        .join(metadata_filename(engine, stem, image_name));
+
+    let mut w = BufWriter::new(File::create(filename)?);
+
+    match engine {
+        Engine::Generic => write_generic(&mut w, image_name, atlas_width, atlas_height, entries)?,
+        Engine::Godot => write_godot(&mut w, image_name, entries)?,
+        Engine::Unity => write_unity(&mut w, image_name, atlas_height, entries)?,
+    }
+
+    w.flush()
+}
+
+/// What [`write_atlas`] did with the atlas and its metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtlasOutput {
+    Written,
+    /// Left alone because they exist and the overwrite policy says so.
+    Skipped,
+    /// Nothing written, the frames have no pixels to pack.
+    Empty,
+}
+
+/// Packs the frames into an atlas, writes it as `<stem>-atlas.png` and
+/// writes the engine's import metadata next to it. With `dedupe`, identical
+/// frames are packed once and share their rect. With `mirror`, horizontally
+/// mirrored copies of the frames follow, named `<frame>-flip`.
+#[cfg(feature = "png")]
+#[allow(clippy::too_many_arguments)]
+pub fn write_atlas<P: AsRef<Path>>(
+    engine: Engine,
+    dir: P,
+    stem: &str,
+    frames: &[IndexedFrame],
+    pal: &[[u8; 4]; 16],
+    dedupe: bool,
+    mirror: bool,
+    policy: OverwritePolicy,
+) -> Result<AtlasOutput, std::io::Error> {
+    let duplicates = if dedupe {
+        analyze::duplicates(frames)
+    } else {
+        vec![None; frames.len()]
+    };
+
+    let unique = frames
+        .iter()
+        .zip(&duplicates)
+        .filter(|(_, d)| d.is_none())
+        .map(|(f, _)| f.clone())
+        .collect::<Vec<_>>();
+
+    let mut packed = unique.clone();
+    if mirror {
+        packed.extend(unique.iter().map(IndexedFrame::flipped));
+    }
+
+    let atlas = build_atlas(&packed, pal);
+    if atlas.width == 0 || atlas.height == 0 {
+        return Ok(AtlasOutput::Empty);
+    }
+
+    let image_name = atlas_filename(stem);
+
+    let image_path = dir.as_ref().join(&image_name);
+    let metadata_path = dir
+        .as_ref()
+        .join(metadata_filename(engine, stem, &image_name));
+
+    if policy == OverwritePolicy::Skip && (image_path.exists() || metadata_path.exists()) {
+        return Ok(AtlasOutput::Skipped);
+    }
+    policy.prepare(&image_path)?;
+    policy.prepare(&metadata_path)?;
+    write_rgba_to_png(image_path, &atlas.data, atlas.width, atlas.height)?;
+
+    let scale = |x: usize, y: usize, w: usize, h: usize| AtlasRect {
+        x: SCALE_FACTOR_WIDTH * x,
+        y: SCALE_FACTOR_HEIGHT * y,
+        w: SCALE_FACTOR_WIDTH * w,
+        h: SCALE_FACTOR_HEIGHT * h,
+    };
+
+    // Keyed by frame index and whether the frame is mirrored.
+    let rects = packed
+        .iter()
+        .zip(&atlas.rects)
+        .enumerate()
+        .map(|(n, (f, r))| ((f.index, n >= unique.len()), scale(r.x, r.y, r.w, r.h)))
+        .collect::<HashMap<_, _>>();
+
+    let name = |index: usize, mirrored: bool| {
+        let suffix = if mirrored { "-flip" } else { "" };
+        format!("{}-{:02}{suffix}", stem, index)
+    };
+
+    let mut entries = Vec::new();
+    for mirrored in [false, true].into_iter().filter(|&m| mirror || !m) {
+        for (f, d) in frames.iter().zip(&duplicates) {
+            let f = if mirrored { &f.flipped() } else { f };
+            entries.push(AtlasEntry {
+                name: name(f.index, mirrored),
+                rect: rects[&(d.unwrap_or(f.index), mirrored)],
+                hitboxes: f
+                    .hitboxes
+                    .iter()
+                    .map(|b| scale(b.x, b.y, b.w, b.h))
+                    .collect(),
+                duplicate_of: d.map(|m| name(m, mirrored)),
+                mirror_of: mirrored.then(|| name(f.index, false)),
+            });
+        }
+    }
+
+    write_metadata(
+        engine,
+        dir,
+        stem,
+        &image_name,
+        SCALE_FACTOR_WIDTH * atlas.width,
+        SCALE_FACTOR_HEIGHT * atlas.height,
+        &entries,
+    )?;
+
+    Ok(AtlasOutput::Written)
+}
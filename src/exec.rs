@@ -0,0 +1,171 @@
+//! A command run on every output file, for post-processing like
+//! optimizing the PNGs or copying them into a mod folder.
+//!
+//! The command is given as a single string and split into words like a
+//! shell would, honoring quotes and backslashes, but it is not run through
+//! a shell. `{}` stands for the path of the output, wherever it appears;
+//! without one, the path is added as the last word:
+//!
+//! ```text
+//! oxipng -o 4 {}
+//! cp {} "/games/kult mod/gfx/"
+//! ```
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+const PLACEHOLDER: &str = "{}";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecCommand {
+    words: Vec<String>,
+}
+
+impl ExecCommand {
+    /// Returns `None` for an empty command or one with an unterminated
+    /// quote.
+    pub fn parse(command: &str) -> Option<ExecCommand> {
+        let mut words = Vec::new();
+        let mut word: Option<String> = None;
+        let mut chars = command.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() => words.extend(word.take()),
+                '\'' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next()? {
+                            '\'' => break,
+                            c => word.push(c),
+                        }
+                    }
+                }
+                '"' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => match chars.next()? {
+                                c @ ('"' | '\\') => word.push(c),
+                                c => word.extend(['\\', c]),
+                            },
+                            c => word.push(c),
+                        }
+                    }
+                }
+                '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+                c => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+        words.extend(word);
+
+        if words.is_empty() {
+            return None;
+        }
+        if !words.iter().any(|w| w.contains(PLACEHOLDER)) {
+            words.push(PLACEHOLDER.to_string());
+        }
+        Some(ExecCommand { words })
+    }
+
+    /// The program and its arguments for an output.
+    pub fn args(&self, path: &Path) -> Vec<OsString> {
+        self.words
+            .iter()
+            .map(|word| {
+                let mut arg = OsString::new();
+                let mut parts = word.split(PLACEHOLDER);
+                arg.push(parts.next().unwrap_or_default());
+                for part in parts {
+                    arg.push(path);
+                    arg.push(part);
+                }
+                arg
+            })
+            .collect()
+    }
+}
+
+/// A command that did not succeed, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Runs a command on outputs in the background, up to `jobs` at once, so
+/// extraction carries on meanwhile. Commands only start once their output
+/// is written, and their own output is kept to report failures.
+pub struct Executor {
+    command: ExecCommand,
+    jobs: usize,
+    running: VecDeque<(PathBuf, Child)>,
+    failures: Vec<ExecFailure>,
+}
+
+impl Executor {
+    pub fn new(command: ExecCommand, jobs: usize) -> Executor {
+        Executor {
+            command,
+            jobs: jobs.max(1),
+            running: VecDeque::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Starts the command on `path`, after the oldest command finishes if
+    /// as many as allowed are running. A command still running on the same
+    /// path is waited for first, so the two don't work on the file at once.
+    pub fn run(&mut self, path: &Path) {
+        while let Some(n) = self.running.iter().position(|(p, _)| p == path) {
+            let (path, child) = self.running.remove(n).unwrap();
+            self.wait(path, child);
+        }
+        while self.running.len() >= self.jobs {
+            let (path, child) = self.running.pop_front().unwrap();
+            self.wait(path, child);
+        }
+
+        let args = self.command.args(path);
+        let spawned = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        match spawned {
+            Ok(child) => self.running.push_back((path.to_path_buf(), child)),
+            Err(e) => self.failures.push(ExecFailure {
+                path: path.to_path_buf(),
+                reason: format!("{} could not be run, {e}", args[0].to_string_lossy()),
+            }),
+        }
+    }
+
+    fn wait(&mut self, path: PathBuf, child: Child) {
+        let reason = match child.wait_with_output() {
+            Ok(output) if output.status.success() => return,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+                    Some(line) => format!("{}: {}", output.status, line.trim()),
+                    None => output.status.to_string(),
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+        self.failures.push(ExecFailure { path, reason });
+    }
+
+    /// Waits for the commands still running, and returns the ones that
+    /// failed, in the order they finished.
+    pub fn finish(mut self) -> Vec<ExecFailure> {
+        while let Some((path, child)) = self.running.pop_front() {
+            self.wait(path, child);
+        }
+        self.failures
+    }
+}
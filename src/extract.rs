@@ -0,0 +1,1665 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::analyze;
+#[cfg(feature = "archives")]
+use crate::archive;
+use crate::aseprite::write_aseprite_to_file;
+use crate::catalog::{self, Catalog};
+#[cfg(feature = "disk-images")]
+use crate::disk;
+use crate::ega::{self, Monitor, Transform};
+use crate::engine::{self, AtlasOutput, Engine};
+use crate::format::{
+    Detect, Extracted, Format, FormatRegistry, Geometry, IndexedFrame, Kind, PlanarScreen, Settings,
+};
+use crate::group;
+use crate::hercules::Phosphor;
+use crate::incremental::State;
+use crate::input::Input;
+use crate::output::{
+    downscale, output_stem, scale_pixels, write_frame_png, write_indexed_to_svg,
+    write_native_rgba_to_png, write_raw_to_png, write_rgba_to_png, write_unscaled_rgba_to_png, Crt,
+    Matte, OverwritePolicy, PngCompression, SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH,
+};
+use crate::palette::PALETTE_FORMATS;
+use crate::repair::{self, Damage};
+use crate::tiles;
+
+/// Text chunks recording where a frame image came from, so that stays
+/// with the image.
+fn frame_text(
+    source: &str,
+    source_hash: u64,
+    frame: &IndexedFrame,
+    pal: &[[u8; 4]; 16],
+) -> Vec<(String, String)> {
+    let palette = pal
+        .iter()
+        .map(|[r, g, b, a]| format!("#{r:02x}{g:02x}{b:02x}{a:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    [
+        ("Source file", source.to_string()),
+        ("Source file hash", format!("fnv:{source_hash:016x}")),
+        ("Frame", frame.index.to_string()),
+        ("Original size", format!("{}x{}", frame.width, frame.height)),
+        ("Palette", palette),
+        (
+            "Software",
+            format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        ),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect()
+}
+
+/// The settings frame images are encoded with, apart from the
+/// [`Extractor`] so they can be shared between threads.
+#[derive(Clone, Copy)]
+struct FrameEncoding {
+    matte: Option<Matte>,
+    native: bool,
+    crt: Option<Crt>,
+    compression: PngCompression,
+}
+
+impl FrameEncoding {
+    fn encode(
+        self,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+        text: &[(String, String)],
+    ) -> Result<Vec<u8>, Error> {
+        let mut frame_rgb = ega::indexed_to_rgba_with_palette(&frame.pixels, pal);
+        if let Some(matte) = self.matte {
+            matte.apply(&mut frame_rgb, frame.width);
+        }
+
+        let mut png = Vec::new();
+        write_frame_png(
+            &mut png,
+            &frame_rgb,
+            frame.width,
+            frame.height,
+            self.native,
+            self.crt,
+            self.compression,
+            text,
+        )?;
+        Ok(png)
+    }
+}
+
+/// A file extracted before, for linking the outputs of duplicates.
+#[derive(Clone)]
+struct Original {
+    path: PathBuf,
+    stem: String,
+    /// Relative to the output directory.
+    outputs: Vec<PathBuf>,
+}
+
+/// A frame image to encode ahead of writing it.
+struct PendingImage {
+    path: PathBuf,
+    frame: IndexedFrame,
+    text: Vec<(String, String)>,
+}
+
+/// Encodes the images on up to `jobs` threads, keyed by their path.
+fn encode_concurrently(
+    encoding: FrameEncoding,
+    images: Vec<PendingImage>,
+    pal: &[[u8; 4]; 16],
+    jobs: usize,
+) -> HashMap<PathBuf, Result<Vec<u8>, Error>> {
+    if images.is_empty() {
+        return HashMap::new();
+    }
+
+    let chunk_size = images.len().div_ceil(jobs.max(1));
+    let encoded = thread::scope(|scope| {
+        let handles = images
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|image| encoding.encode(&image.frame, pal, &image.text))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("encoding thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    images
+        .into_iter()
+        .map(|image| image.path)
+        .zip(encoded)
+        .collect()
+}
+
+/// The text chunks of a frame, for its mirrored copy.
+fn mirrored_text(text: &[(String, String)]) -> Vec<(String, String)> {
+    let mut text = text.to_vec();
+    text.push(("Mirrored".to_string(), "horizontally".to_string()));
+    text
+}
+
+/// Renames the outputs of a file after another stem, in folders named
+/// after it as well as in file names.
+fn renamed(output: &Path, from: &str, to: &str) -> PathBuf {
+    let count = output.components().count();
+    output
+        .components()
+        .enumerate()
+        .map(|(n, component)| {
+            let component = component.as_os_str();
+            match component.to_str() {
+                Some(name) if n + 1 < count && name == from => to.to_string().into(),
+                Some(name) if n + 1 == count => name.replacen(from, to, 1).into(),
+                _ => component.to_os_string(),
+            }
+        })
+        .collect()
+}
+
+/// Reads the palette register table accompanying a file, if there is one.
+fn read_remap(path: &Path) -> Option<[u8; 16]> {
+    ["PAL", "pal"]
+        .into_iter()
+        .find_map(|ext| std::fs::read(path.with_extension(ext)).ok())
+        .and_then(|src| ega::parse_palette_remap(&src))
+}
+
+/// Which frames of each file to write, by index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameSelection {
+    ranges: Vec<RangeInclusive<usize>>,
+    first: bool,
+    last: bool,
+}
+
+impl FrameSelection {
+    /// Adds a comma separated list of indices and inclusive ranges, like
+    /// `0,3,7-12`. Returns false if the list does not parse.
+    pub fn add_list(&mut self, list: &str) -> bool {
+        let Some(ranges) = list
+            .split(',')
+            .map(group::parse_range)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        self.ranges.extend(ranges);
+        true
+    }
+
+    /// Adds the first frame of each file.
+    pub fn add_first(&mut self) {
+        self.first = true;
+    }
+
+    /// Adds the last frame of each file.
+    pub fn add_last(&mut self) {
+        self.last = true;
+    }
+
+    /// Whether the frame at `index` is selected, in a file whose frames
+    /// range from `first` to `last`.
+    pub fn contains(&self, index: usize, first: usize, last: usize) -> bool {
+        (self.first && index == first)
+            || (self.last && index == last)
+            || self.ranges.iter().any(|r| r.contains(&index))
+    }
+}
+
+/// How frame images are named, with `{name}` standing for the file name
+/// without extension, followed by the catalog description if there is
+/// one, and `{frame}` for the two digit frame index. Templates can contain
+/// `/` to sort the images into subdirectories.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameTemplate(String);
+
+impl NameTemplate {
+    /// Returns `None` for templates with other placeholders, or that would
+    /// name files outside the output directory.
+    pub fn parse(template: &str) -> Option<NameTemplate> {
+        let rest = template.replace("{name}", "").replace("{frame}", "");
+        let escapes = template.starts_with('/')
+            || template
+                .split('/')
+                .any(|part| part.is_empty() || part == "..");
+        if rest.contains(['{', '}', '\\']) || escapes {
+            return None;
+        }
+        Some(NameTemplate(template.to_string()))
+    }
+
+    pub fn frame_stem(&self, name: &str, index: usize) -> String {
+        self.0
+            .replace("{name}", name)
+            .replace("{frame}", &format!("{index:02}"))
+    }
+}
+
+/// Progress reported by an [`Extractor`] while it works.
+pub enum Event<'a> {
+    FileStarted {
+        path: &'a Path,
+    },
+    FrameDecoded {
+        index: usize,
+        width: usize,
+        height: usize,
+    },
+    /// The image of a frame is at `path`, also when an existing file was
+    /// left alone.
+    FrameWritten {
+        index: usize,
+        path: &'a Path,
+    },
+    /// The thumbnail of a frame is at `path`, also when an existing file
+    /// was left alone.
+    ThumbnailWritten {
+        index: usize,
+        path: &'a Path,
+    },
+    /// The catalog knows the file by this description.
+    Identified {
+        description: &'a str,
+    },
+    /// An output file was written, images, SVGs, atlases and everything
+    /// else.
+    OutputWritten {
+        path: &'a Path,
+    },
+    /// An output file already exists and was left alone.
+    OutputSkipped {
+        path: &'a Path,
+    },
+    /// The file is identical to `of`, extracted before, and its outputs
+    /// link to those of `of`.
+    FileDuplicate {
+        path: &'a Path,
+        of: &'a Path,
+    },
+    /// The frame is identical to an earlier one and was not written.
+    FrameDuplicate {
+        index: usize,
+        of: usize,
+    },
+    /// Something was off, but extraction of the file carries on.
+    Warning {
+        message: String,
+    },
+    /// Something unusual but harmless, for verbose output.
+    Note {
+        message: String,
+    },
+    FileFinished {
+        path: &'a Path,
+    },
+    /// The file is the same as when it was last extracted incrementally
+    /// and its outputs are still there, so it was left alone.
+    FileUnchanged {
+        path: &'a Path,
+    },
+    FileFailed {
+        path: &'a Path,
+        error: &'a Error,
+    },
+}
+
+/// How outputs are sorted into folders of the output directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// A folder for each file, named like its outputs, as in
+    /// `SPR07/SPR07-00.png`.
+    File,
+    /// A folder for each kind of file: `fullscreen` and `sprites`, and
+    /// `other` for raw planes of files that don't decode.
+    Type,
+}
+
+impl GroupBy {
+    pub fn from_name(name: &str) -> Option<GroupBy> {
+        match name {
+            "file" => Some(GroupBy::File),
+            "type" => Some(GroupBy::Type),
+            _ => None,
+        }
+    }
+
+    fn folder(self, stem: &str, kind: Option<Kind>) -> &str {
+        match (self, kind) {
+            (GroupBy::File, _) => stem,
+            (GroupBy::Type, Some(Kind::Screen)) => "fullscreen",
+            (GroupBy::Type, Some(Kind::Sheet)) => "sprites",
+            (GroupBy::Type, None) => "other",
+        }
+    }
+}
+
+/// What to extract and how, apart from where to and the progress
+/// callback, so settings can be put together once and used for several
+/// [`Extractor`]s. Settings not given keep their defaults, and new ones
+/// only ever come with a default, so code built on this keeps compiling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtractOptions {
+    engine: Option<Engine>,
+    overwrite: OverwritePolicy,
+    size: Option<(usize, usize)>,
+    dimensions: Option<(usize, usize)>,
+    raw_planes: bool,
+    remap: bool,
+    matte: Option<Matte>,
+    crt: Option<Crt>,
+    compression: PngCompression,
+    jobs: usize,
+    monitor: Monitor,
+    phosphor: Phosphor,
+    gamma: f64,
+    transform: Option<Transform>,
+    dedupe: bool,
+    native: bool,
+    mirror: bool,
+    svg: bool,
+    thumbnails: Option<usize>,
+    frame_diffs: bool,
+    aseprite: bool,
+    tiles: Option<(usize, usize)>,
+    repair: bool,
+    strict: bool,
+    hitboxes: bool,
+    export_palette: bool,
+    frames: Option<FrameSelection>,
+    geometry: Geometry,
+    name_template: Option<NameTemplate>,
+    group_by: Option<GroupBy>,
+    dedupe_inputs: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            engine: None,
+            overwrite: OverwritePolicy::default(),
+            size: None,
+            dimensions: None,
+            raw_planes: false,
+            remap: true,
+            matte: None,
+            crt: None,
+            compression: PngCompression::default(),
+            jobs: 1,
+            monitor: Monitor::default(),
+            phosphor: Phosphor::default(),
+            gamma: 1.0,
+            transform: None,
+            dedupe: false,
+            native: false,
+            mirror: false,
+            svg: false,
+            thumbnails: None,
+            frame_diffs: false,
+            aseprite: false,
+            tiles: None,
+            repair: false,
+            strict: false,
+            hitboxes: false,
+            export_palette: false,
+            frames: None,
+            geometry: Geometry::default(),
+            name_template: None,
+            group_by: None,
+            dedupe_inputs: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    pub fn new() -> ExtractOptions {
+        ExtractOptions::default()
+    }
+
+    pub fn engine(mut self, engine: Option<Engine>) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Decodes every file as a planar fullscreen image of the given size,
+    /// instead of detecting the layout from the file size.
+    pub fn size(mut self, size: Option<(usize, usize)>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Decodes files detected as fullscreen images as screens of the given
+    /// size instead, taking the rest of the file as padding. For screens
+    /// padded to a standard layout, which otherwise decode doubled. Takes
+    /// precedence over sizes from the catalog.
+    pub fn dimensions(mut self, dimensions: Option<(usize, usize)>) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Also dumps every file as four unscaled 1-bit plane images and one
+    /// 4-bit image of the combined indices, whether it decodes or not. The
+    /// layout is taken from [`size`](Self::size) or the file size, falling
+    /// back to 320 pixels wide.
+    pub fn raw_planes(mut self, raw_planes: bool) -> Self {
+        self.raw_planes = raw_planes;
+        self
+    }
+
+    /// Whether to apply palette register tables, either following a screen
+    /// in the same file or in a 16 byte `<name>.PAL` file next to it.
+    /// Enabled by default.
+    pub fn remap(mut self, remap: bool) -> Self {
+        self.remap = remap;
+        self
+    }
+
+    /// Composites the frame images over a background. Atlases keep their
+    /// transparency.
+    pub fn matte(mut self, matte: Option<Matte>) -> Self {
+        self.matte = matte;
+        self
+    }
+
+    /// Gives the scaled frame images and the PNG of
+    /// [`extract_png`](Extractor::extract_png) a CRT look, see [`Crt`]. Frames
+    /// written natively, atlases and GIFs are left alone.
+    pub fn crt(mut self, crt: Option<Crt>) -> Self {
+        self.crt = crt;
+        self
+    }
+
+    /// How hard to compress the frame images and the PNG of
+    /// [`extract_png`](Extractor::extract_png).
+    pub fn png_compression(mut self, compression: PngCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Encodes up to `jobs` frame images of a file at once. The images are
+    /// still written, and reported, in order. 1 by default.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    pub fn monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// The color monochrome screens are shown in, instead of the EGA
+    /// palette.
+    pub fn phosphor(mut self, phosphor: Phosphor) -> Self {
+        self.phosphor = phosphor;
+        self
+    }
+
+    /// Gamma correction of the colors, 1 leaves them as they are.
+    pub fn gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Transforms the colors after the monitor and gamma are applied, for
+    /// previews. Atlases are transformed as well.
+    pub fn transform(mut self, transform: Option<Transform>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Writes identical frames of a sheet only once. In atlases the
+    /// duplicates share the rect of the first one.
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Writes the frame images 1:1 with their pixel aspect in the pHYs
+    /// chunk, instead of scaling them. Atlases and GIFs are still scaled.
+    pub fn native(mut self, native: bool) -> Self {
+        self.native = native;
+        self
+    }
+
+    /// Also writes horizontally mirrored copies of the frames, as
+    /// `<frame>-flip.png` and in atlases, for the facings the engine
+    /// mirrors at runtime.
+    pub fn mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Also writes each frame as an SVG of pixel rectangles, `<frame>.svg`,
+    /// sized for the pixel aspect unless frames are written natively.
+    pub fn svg(mut self, svg: bool) -> Self {
+        self.svg = svg;
+        self
+    }
+
+    /// Also writes a thumbnail of each frame as `<frame>-thumb.png`, the
+    /// scaled image shrunk to fit `size` pixels on either side.
+    pub fn thumbnails(mut self, size: Option<usize>) -> Self {
+        self.thumbnails = size;
+        self
+    }
+
+    /// Also writes, for each frame after the first, `<frame>-diff.png`
+    /// showing the pixels changed from the frame before it in their colors
+    /// and the others faded to gray.
+    pub fn frame_diffs(mut self, frame_diffs: bool) -> Self {
+        self.frame_diffs = frame_diffs;
+        self
+    }
+
+    /// Also writes the frames of sprite sheets as an Aseprite
+    /// document, `<name>.aseprite`, with the palette they were extracted
+    /// with.
+    pub fn aseprite(mut self, aseprite: bool) -> Self {
+        self.aseprite = aseprite;
+        self
+    }
+
+    /// Also splits screens into tiles of `size` pixels, writing the
+    /// distinct tiles unscaled as `<name>-tiles.png` and where they go as
+    /// `<name>-tiles.json` and `<name>-tiles.csv`.
+    pub fn tiles(mut self, size: Option<(usize, usize)>) -> Self {
+        self.tiles = size;
+        self
+    }
+
+    /// Repairs planar screens with bands of a plane filled in by a disk
+    /// copier for an unreadable sector, see [`crate::repair`], and warns
+    /// about each.
+    pub fn repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Fails files with inconsistent headers, like frames whose sizes do
+    /// not add up or a sheet size stored little-endian, instead of
+    /// warning and decoding what can be decoded.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Decodes the bytes after the pixels of sprite sheet frames as hit
+    /// boxes, see [`Frame::hitboxes`](crate::sprite::Frame::hitboxes),
+    /// and lists them in the engine metadata.
+    pub fn hitboxes(mut self, hitboxes: bool) -> Self {
+        self.hitboxes = hitboxes;
+        self
+    }
+
+    /// Also writes the palette each file is extracted with, after remapping
+    /// and monitor, gamma and phosphor settings, as `<name>.gpl`,
+    /// `<name>.pal` and `<name>.act` for pixel art editors.
+    pub fn export_palette(mut self, export_palette: bool) -> Self {
+        self.export_palette = export_palette;
+        self
+    }
+
+    /// Writes only the selected frames. As they need all frames, atlases
+    /// and GIFs are not written with a selection.
+    pub fn frames(mut self, frames: Option<FrameSelection>) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Crops, flips and rotates the frames before they are scaled and
+    /// written, atlases and GIFs included.
+    pub fn geometry(mut self, geometry: Geometry) -> Self {
+        self.geometry = geometry;
+        self
+    }
+
+    /// Names the frame images after a template instead of `<name>.png` for
+    /// screens and `<name>-NN.png` for the frames of sheets. Screens count
+    /// as frame 0.
+    pub fn name_template(mut self, template: Option<NameTemplate>) -> Self {
+        self.name_template = template;
+        self
+    }
+
+    /// Sorts the outputs of each file into a folder of the output
+    /// directory, instead of writing them all side by side.
+    pub fn group_by(mut self, group_by: Option<GroupBy>) -> Self {
+        self.group_by = group_by;
+        self
+    }
+
+    /// Extracts files with the same contents as one extracted before only
+    /// once. The outputs of the others are symlinks to the outputs of the
+    /// first, or copies where there are no symlinks, named after them as
+    /// usual.
+    pub fn dedupe_inputs(mut self, dedupe_inputs: bool) -> Self {
+        self.dedupe_inputs = dedupe_inputs;
+        self
+    }
+}
+
+/// Drives extraction of Kult files into PNGs in an output directory,
+/// reporting progress through a callback instead of printing. The
+/// settings are taken from [`ExtractOptions`], see
+/// [`options`](Self::options).
+pub struct Extractor<'f> {
+    output_dir: PathBuf,
+    /// Where the outputs of the file being extracted go, the output
+    /// directory or a folder of it.
+    file_dir: PathBuf,
+    options: ExtractOptions,
+    state: Option<State>,
+    /// Files extracted so far, by [`input_hash`](Self::input_hash), to
+    /// find duplicates.
+    inputs: HashMap<u64, Original>,
+    /// Outputs of the file being extracted, for the state and for
+    /// duplicates.
+    outputs: Vec<PathBuf>,
+    catalog: Catalog,
+    registry: FormatRegistry,
+    callback: Box<dyn FnMut(&Event) + 'f>,
+}
+
+impl<'f> Extractor<'f> {
+    pub fn new<P: Into<PathBuf>>(output_dir: P) -> Extractor<'f> {
+        let output_dir = output_dir.into();
+        Extractor {
+            output_dir: output_dir.clone(),
+            file_dir: output_dir,
+            options: ExtractOptions::default(),
+            state: None,
+            inputs: HashMap::new(),
+            outputs: Vec::new(),
+            catalog: Catalog::default(),
+            registry: FormatRegistry::default(),
+            callback: Box::new(|_| {}),
+        }
+    }
+
+    /// Sets the options of the extraction, the defaults of
+    /// [`ExtractOptions::new`] until then.
+    pub fn options(mut self, options: ExtractOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Extracts incrementally: files recorded in the state with the same
+    /// contents and options are skipped as long as their outputs exist,
+    /// the others are extracted, replacing their outputs unless the
+    /// overwrite policy is to back them up, and recorded. Get the updated
+    /// state back with [`take_state`](Self::take_state).
+    pub fn incremental(mut self, state: Option<State>) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn take_state(&mut self) -> Option<State> {
+        self.state.take()
+    }
+
+    /// Names the outputs of files the catalog knows after their
+    /// description as well, like `SPR07-raven_idle_animation-00.png`.
+    pub fn catalog(mut self, catalog: Catalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Replaces the built-in formats.
+    pub fn registry(mut self, registry: FormatRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn on_event<F: FnMut(&Event) + 'f>(mut self, callback: F) -> Self {
+        self.callback = Box::new(callback);
+        self
+    }
+
+    /// Extracts a file. With the `archives` feature, ZIP and LHA archives
+    /// are extracted member by member, and with the `disk-images` feature
+    /// the files of floppy images one by one.
+    pub fn extract_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        let src = match Input::read(path) {
+            Ok(src) => src,
+            Err(error) => return self.reporting(path, |_| Err(error)),
+        };
+
+        let remap = read_remap(path);
+        if self.state.is_none() && !self.options.dedupe_inputs {
+            return self.extract_src(path, &src, remap);
+        }
+        let hash = self.input_hash(&src, remap);
+
+        if self
+            .state
+            .as_ref()
+            .is_some_and(|state| state.is_current(path, hash, &self.output_dir))
+        {
+            if self.options.dedupe_inputs {
+                let outputs = self.state.as_ref().and_then(|s| s.outputs(path));
+                let outputs = outputs.unwrap_or_default().to_vec();
+                self.remember_input(path, &src, hash, outputs);
+            }
+            (self.callback)(&Event::FileUnchanged { path });
+            return Ok(());
+        }
+
+        let overwrite = self.options.overwrite;
+        if self.state.is_some() && overwrite == OverwritePolicy::Skip {
+            self.options.overwrite = OverwritePolicy::Force;
+        }
+        self.outputs.clear();
+        let original = self.inputs.get(&hash).cloned();
+        let result = match &original {
+            Some(original) => self.reporting(path, |this| this.link_outputs(path, &src, original)),
+            None => self.extract_src(path, &src, remap),
+        };
+        self.options.overwrite = overwrite;
+
+        let outputs = std::mem::take(&mut self.outputs);
+        if self.options.dedupe_inputs && original.is_none() && result.is_ok() {
+            self.remember_input(path, &src, hash, outputs.clone());
+        }
+        if let Some(state) = &mut self.state {
+            match result {
+                Ok(()) => state.record(path, hash, outputs),
+                Err(_) => state.forget(path),
+            }
+        }
+        result
+    }
+
+    /// Remembers an extracted file, for linking the outputs of files with
+    /// the same contents to its outputs.
+    fn remember_input(&mut self, path: &Path, src: &[u8], hash: u64, outputs: Vec<PathBuf>) {
+        if self.inputs.contains_key(&hash) {
+            return;
+        }
+        let filename = path.file_name().unwrap_or_default().to_string_lossy();
+        let description = self.catalog.lookup(&filename, src);
+        let original = Original {
+            path: path.to_path_buf(),
+            stem: self.stem(path, description),
+            outputs,
+        };
+        self.inputs.insert(hash, original);
+    }
+
+    /// Links the outputs of a file identical to one extracted before to
+    /// the outputs of that one, named after this file instead.
+    fn link_outputs(&mut self, path: &Path, src: &[u8], original: &Original) -> Result<(), Error> {
+        (self.callback)(&Event::FileDuplicate {
+            path,
+            of: &original.path,
+        });
+
+        let description = self.identify(path, src);
+        let stem = self.stem(path, description.as_deref());
+        for target in &original.outputs {
+            let relative = renamed(target, &original.stem, &stem);
+            if relative == *target {
+                continue;
+            }
+
+            let link = self.output_dir.join(&relative);
+            if let Some(dir) = link.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            if !self.claim_output(&link)? {
+                continue;
+            }
+            if link.symlink_metadata().is_ok() {
+                std::fs::remove_file(&link)?;
+            }
+
+            #[cfg(unix)]
+            {
+                // Relative, so the output directory can be moved.
+                let mut target_from_link = PathBuf::new();
+                for _ in 1..relative.components().count() {
+                    target_from_link.push("..");
+                }
+                target_from_link.push(target);
+                std::os::unix::fs::symlink(target_from_link, &link)?;
+            }
+            #[cfg(not(unix))]
+            std::fs::copy(self.output_dir.join(target), &link)?;
+
+            self.output_written(&link);
+        }
+
+        Ok(())
+    }
+
+    fn extract_src(
+        &mut self,
+        path: &Path,
+        src: &[u8],
+        remap: Option<[u8; 16]>,
+    ) -> Result<(), Error> {
+        #[cfg(feature = "archives")]
+        if archive::detect(src).is_some() {
+            return self.extract_archive(path, src);
+        }
+        #[cfg(feature = "disk-images")]
+        if disk::detect(src).is_some() {
+            return self.extract_disk(path, src);
+        }
+
+        self.reporting(path, |this| this.write_outputs(path, src, remap))
+    }
+
+    /// Identifies the contents of a file along with everything that
+    /// affects its outputs, for incremental extraction.
+    fn input_hash(&self, src: &[u8], remap: Option<[u8; 16]>) -> u64 {
+        let settings = format!(
+            "{:?}",
+            (
+                (
+                    self.options.engine,
+                    self.options.size,
+                    self.options.dimensions,
+                    self.options.raw_planes,
+                    self.options.remap,
+                    self.options.matte,
+                    self.options.crt,
+                    self.options.compression,
+                ),
+                (
+                    self.options.monitor,
+                    self.options.phosphor,
+                    self.options.gamma,
+                    self.options.transform
+                ),
+                (
+                    self.options.dedupe,
+                    self.options.native,
+                    self.options.mirror,
+                    self.options.svg,
+                    self.options.thumbnails,
+                    self.options.frame_diffs,
+                    self.options.aseprite,
+                    self.options.tiles,
+                    self.options.repair,
+                    self.options.strict,
+                    self.options.hitboxes,
+                    self.options.export_palette,
+                ),
+                (
+                    &self.options.frames,
+                    self.options.geometry,
+                    &self.options.name_template,
+                    self.options.group_by,
+                    remap,
+                    self.catalog.hash(),
+                ),
+            )
+        );
+        catalog::content_hash_parts(&[src, settings.as_bytes()])
+    }
+
+    /// Extracts the members of an archive as `<archive>/<member>`. Members
+    /// that fail to unpack or decode are reported like any other file,
+    /// without stopping the others.
+    #[cfg(feature = "archives")]
+    fn extract_archive(&mut self, path: &Path, src: &[u8]) -> Result<(), Error> {
+        let members = match archive::members(src) {
+            Ok(members) => members,
+            Err(error) => return self.reporting(path, |_| Err(error)),
+        };
+
+        let members = members.into_iter().map(|m| (m.name, m.data));
+        self.extract_members(path, members)
+    }
+
+    /// Extracts the files of a floppy image as `<image>/<file>`, like the
+    /// members of an archive.
+    #[cfg(feature = "disk-images")]
+    fn extract_disk(&mut self, path: &Path, src: &[u8]) -> Result<(), Error> {
+        let files = match disk::files(src) {
+            Ok(files) => files,
+            Err(error) => return self.reporting(path, |_| Err(error)),
+        };
+
+        let files = files.into_iter().map(|f| (f.name, f.data));
+        self.extract_members(path, files)
+    }
+
+    #[cfg(any(feature = "archives", feature = "disk-images"))]
+    fn extract_members<I>(&mut self, path: &Path, members: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (String, Result<Vec<u8>, Error>)>,
+    {
+        for (member, data) in members {
+            let name = path.join(member);
+            let result = self.reporting(&name, |this| {
+                let src = data?;
+                this.write_outputs(&name, &src, None)
+            });
+            match result {
+                Err(e) if e.kind() != ErrorKind::InvalidData => return Err(e),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`extract_file`](Self::extract_file) for data that does not
+    /// come from a file. `name` stands in for the filename, in events and
+    /// for naming the outputs.
+    pub fn extract_data<P: AsRef<Path>>(&mut self, name: P, src: &[u8]) -> Result<(), Error> {
+        let name = name.as_ref();
+        self.reporting(name, |this| this.write_outputs(name, src, None))
+    }
+
+    /// Decodes `src` into a single PNG written to `w` instead of the output
+    /// directory: a screen, or the frames of a sheet side by side.
+    pub fn extract_png<P: AsRef<Path>, W: Write>(
+        &mut self,
+        name: P,
+        src: &[u8],
+        w: W,
+    ) -> Result<(), Error> {
+        let name = name.as_ref();
+        self.reporting(name, |this| {
+            let atlas = this.decode_single(name, src)?;
+            write_frame_png(
+                w,
+                &atlas.data,
+                atlas.width,
+                atlas.height,
+                this.options.native,
+                this.options.crt,
+                this.options.compression,
+                &[],
+            )
+        })
+    }
+
+    /// Decodes `src` into the image [`extract_png`](Self::extract_png)
+    /// writes, unscaled and as RGBA, along with its width and height.
+    pub fn extract_rgba<P: AsRef<Path>>(
+        &mut self,
+        name: P,
+        src: &[u8],
+    ) -> Result<(Vec<u8>, usize, usize), Error> {
+        let name = name.as_ref();
+        let mut image = None;
+        self.reporting(name, |this| {
+            let atlas = this.decode_single(name, src)?;
+            image = Some((atlas.data, atlas.width, atlas.height));
+            Ok(())
+        })?;
+        Ok(image.unwrap())
+    }
+
+    fn decode_single(&mut self, name: &Path, src: &[u8]) -> Result<engine::Atlas, Error> {
+        self.identify(name, src);
+        let dimensions = self.dimensions_of(name, src);
+        let (extracted, pal) = self.decode(src, None, dimensions)?;
+
+        for frame in &extracted.frames {
+            (self.callback)(&Event::FrameDecoded {
+                index: frame.index,
+                width: frame.width,
+                height: frame.height,
+            });
+        }
+
+        let mut atlas = engine::build_atlas(&extracted.frames, &pal);
+        if let Some(matte) = self.options.matte {
+            matte.apply(&mut atlas.data, atlas.width);
+        }
+        Ok(atlas)
+    }
+
+    /// Runs `f` between the events reporting on a file.
+    fn reporting<F>(&mut self, path: &Path, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        (self.callback)(&Event::FileStarted { path });
+
+        match f(self) {
+            Ok(()) => {
+                (self.callback)(&Event::FileFinished { path });
+                Ok(())
+            }
+            Err(error) => {
+                (self.callback)(&Event::FileFailed {
+                    path,
+                    error: &error,
+                });
+                Err(error)
+            }
+        }
+    }
+
+    /// Applies the overwrite policy to an output file, returns false if it
+    /// should not be written.
+    fn claim_output(&mut self, path: &Path) -> Result<bool, Error> {
+        self.record_output(path);
+        let claimed = self.options.overwrite.prepare(path)?;
+        if !claimed {
+            (self.callback)(&Event::OutputSkipped { path });
+        }
+        Ok(claimed)
+    }
+
+    fn output_written(&mut self, path: &Path) {
+        (self.callback)(&Event::OutputWritten { path });
+    }
+
+    fn record_output(&mut self, path: &Path) {
+        if self.state.is_some() || self.options.dedupe_inputs {
+            let relative = path.strip_prefix(&self.output_dir).unwrap_or(path);
+            self.outputs.push(relative.to_path_buf());
+        }
+    }
+
+    fn write_raw_planes(&mut self, stem: &str, src: &[u8]) -> Result<(), Error> {
+        let (width, height) = self
+            .options
+            .size
+            .or_else(|| ega::fullscreen_layout(src.len()))
+            .unwrap_or((
+                ega::FULLSCREEN_WIDTH,
+                src.len() / ega::planar_size(ega::FULLSCREEN_WIDTH, 1),
+            ));
+
+        if width % 8 != 0 || height == 0 || src.len() < ega::planar_size(width, height) {
+            (self.callback)(&Event::Warning {
+                message: format!("Too small for {width}x{height} raw planes, skipping."),
+            });
+            return Ok(());
+        }
+
+        // Each plane already is a 1-bit image with the leftmost pixel in
+        // the most significant bit.
+        let plane_size = width * height / 8;
+        for (n, plane) in src.chunks(plane_size).take(4).enumerate() {
+            let filename = self.file_dir.join(format!("{stem}-plane{n}.png"));
+            if self.claim_output(&filename)? {
+                write_raw_to_png(&filename, plane, width, height, 1)?;
+                self.output_written(&filename);
+            }
+        }
+
+        let filename = self.file_dir.join(format!("{stem}-planes.png"));
+        if self.claim_output(&filename)? {
+            let indexed = ega::decode_planar_ega_to_indexed(src, width, height);
+            let packed = indexed
+                .chunks(2)
+                .map(|p| (p[0] << 4) | p[1])
+                .collect::<Vec<_>>();
+            write_raw_to_png(&filename, &packed, width, height, 4)?;
+            self.output_written(&filename);
+        }
+
+        Ok(())
+    }
+
+    /// Looks the file up in the catalog, returning the description.
+    fn identify(&mut self, name: &Path, src: &[u8]) -> Option<String> {
+        let filename = name.file_name().unwrap_or_default().to_string_lossy();
+        let description = self.catalog.lookup(&filename, src)?.to_string();
+        (self.callback)(&Event::Identified {
+            description: &description,
+        });
+        Some(description)
+    }
+
+    /// The size of a padded screen, from [`dimensions`](Self::dimensions)
+    /// or the catalog.
+    fn dimensions_of(&self, name: &Path, src: &[u8]) -> Option<(usize, usize)> {
+        self.options.dimensions.or_else(|| {
+            let filename = name.file_name().unwrap_or_default().to_string_lossy();
+            self.catalog.size(&filename, src)
+        })
+    }
+
+    /// Decodes data, a palette register table that came with it
+    /// applies unless the data has its own. Screens are decoded at
+    /// `dimensions` if given.
+    fn decode(
+        &mut self,
+        src: &[u8],
+        remap: Option<[u8; 16]>,
+        dimensions: Option<(usize, usize)>,
+    ) -> Result<(Extracted, [[u8; 4]; 16]), Error> {
+        let mut notes = Vec::new();
+        let mut padded = None;
+        let override_format;
+        let format: &dyn Format = match (self.options.size, dimensions) {
+            (Some((width, height)), _) => {
+                override_format = PlanarScreen::with_size(width, height);
+                if let Err(reason) = override_format.detect(src) {
+                    return Err(Error::new(ErrorKind::InvalidData, reason));
+                }
+                &override_format
+            }
+            (None, Some((width, height))) => match self.registry.detect(src) {
+                Ok(format) if format.name() != "screen" => {
+                    notes.push(format!(
+                        "Not a fullscreen image, ignoring the size {width}x{height}."
+                    ));
+                    format
+                }
+                _ => {
+                    override_format = PlanarScreen::with_size(width, height);
+                    if let Err(reason) = override_format.detect(src) {
+                        return Err(Error::new(ErrorKind::InvalidData, reason));
+                    }
+                    padded = Some((width, height));
+                    &override_format
+                }
+            },
+            (None, None) => self.registry.detect(src)?,
+        };
+
+        let settings = Settings {
+            hitboxes: self.options.hitboxes,
+        };
+        let mut extracted = format.extract_with(src, &settings)?;
+        if self.options.strict {
+            if let Some(problem) = extracted.problems.first() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{problem}, rejected in strict mode"),
+                ));
+            }
+        }
+        if self.options.repair {
+            if let (Some(offset), [frame]) = (format.planes_offset(src), &extracted.frames[..]) {
+                let mut repaired = src.to_vec();
+                let damages =
+                    repair::repair_planes(&mut repaired[offset..], frame.width, frame.height);
+                if !damages.is_empty() {
+                    extracted = format.extract_with(&repaired, &settings)?;
+                    extracted
+                        .warnings
+                        .extend(damages.iter().map(Damage::describe));
+                }
+            }
+        }
+        if let Some((width, height)) = padded {
+            let mut padding = src.len() - ega::planar_size(width, height);
+            if extracted.remap.is_some() {
+                padding -= ega::PALETTE_REMAP_SIZE;
+            }
+            if padding > 0 {
+                notes.push(format!(
+                    "{padding} bytes after the {width}x{height} image, ignored."
+                ));
+            }
+        }
+        extracted.notes.extend(notes);
+
+        if extracted.kind == Kind::Screen {
+            for frame in &extracted.frames {
+                if let Some(repetition) = analyze::repetition(frame) {
+                    extracted
+                        .warnings
+                        .push(repetition.describe(frame.width, frame.height));
+                }
+            }
+        }
+
+        if let Some(selection) = &self.options.frames {
+            let first = extracted.frames.first().map_or(0, |f| f.index);
+            let last = extracted.frames.last().map_or(0, |f| f.index);
+            extracted
+                .frames
+                .retain(|f| selection.contains(f.index, first, last));
+        }
+
+        if !self.options.geometry.is_identity() {
+            let mut frames = Vec::new();
+            for frame in &extracted.frames {
+                let frame = self.options.geometry.apply(frame);
+                if frame.width == 0 || frame.height == 0 {
+                    extracted.warnings.push(format!(
+                        "Frame {} lies outside the crop, skipping.",
+                        frame.index
+                    ));
+                    continue;
+                }
+                frames.push(frame);
+            }
+            extracted.frames = frames;
+        }
+
+        if !self.options.remap {
+            extracted.remap = None;
+        } else if extracted.kind == Kind::Screen && extracted.remap.is_none() {
+            extracted.remap = remap;
+        }
+        let mut pal = if extracted.monochrome {
+            self.options.phosphor.palette()
+        } else {
+            ega::palette(
+                self.options.monitor,
+                &extracted.registers(),
+                self.options.gamma,
+            )
+        };
+        if let Some(transform) = self.options.transform {
+            pal = transform.apply(&pal);
+        }
+
+        for message in std::mem::take(&mut extracted.warnings) {
+            (self.callback)(&Event::Warning { message });
+        }
+        for message in std::mem::take(&mut extracted.notes) {
+            (self.callback)(&Event::Note { message });
+        }
+
+        Ok((extracted, pal))
+    }
+
+    fn frame_encoding(&self) -> FrameEncoding {
+        FrameEncoding {
+            matte: self.options.matte,
+            native: self.options.native,
+            crt: self.options.crt,
+            compression: self.options.compression,
+        }
+    }
+
+    /// Writes a frame image, unless the overwrite policy says to leave it.
+    /// The image is taken from `encoded` if it was encoded ahead.
+    fn write_frame(
+        &mut self,
+        path: &Path,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+        text: &[(String, String)],
+        encoded: &mut HashMap<PathBuf, Result<Vec<u8>, Error>>,
+    ) -> Result<(), Error> {
+        let png = encoded.remove(path);
+        if !self.claim_output(path)? {
+            return Ok(());
+        }
+
+        let png = match png {
+            Some(png) => png?,
+            None => self.frame_encoding().encode(frame, pal, text)?,
+        };
+        std::fs::write(path, png)?;
+        self.output_written(path);
+        Ok(())
+    }
+
+    /// Encodes images on [`jobs`](Self::jobs) threads, ahead of writing
+    /// them in order. Images the overwrite policy leaves alone are not
+    /// encoded, and neither are images sharing a path, like the frames
+    /// named by a template without `{frame}`.
+    fn encode_ahead(
+        &self,
+        mut images: Vec<PendingImage>,
+        pal: &[[u8; 4]; 16],
+    ) -> HashMap<PathBuf, Result<Vec<u8>, Error>> {
+        let mut uses = HashMap::new();
+        for image in &images {
+            *uses.entry(image.path.clone()).or_insert(0) += 1;
+        }
+        images.retain(|image| {
+            uses[&image.path] == 1
+                && (self.options.overwrite != OverwritePolicy::Skip || !image.path.exists())
+        });
+        encode_concurrently(self.frame_encoding(), images, pal, self.options.jobs)
+    }
+
+    /// What the outputs of a file are named after, its name without
+    /// extension followed by the catalog description if there is one.
+    fn stem(&self, name: &Path, description: Option<&str>) -> String {
+        match description {
+            Some(description) => format!("{}-{}", output_stem(name), catalog::slug(description)),
+            None => output_stem(name),
+        }
+    }
+
+    fn frame_stem(&self, stem: &str, kind: Kind, index: usize) -> String {
+        match (&self.options.name_template, kind) {
+            (Some(template), _) => template.frame_stem(stem, index),
+            (None, Kind::Screen) => stem.to_string(),
+            (None, Kind::Sheet) => format!("{stem}-{index:02}"),
+        }
+    }
+
+    fn write_thumbnail(
+        &mut self,
+        frame_stem: &str,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+        size: usize,
+    ) -> Result<(), Error> {
+        let path = self.file_dir.join(format!("{frame_stem}-thumb.png"));
+        if self.claim_output(&path)? {
+            let mut frame_rgb = ega::indexed_to_rgba_with_palette(&frame.pixels, pal);
+            if let Some(matte) = self.options.matte {
+                matte.apply(&mut frame_rgb, frame.width);
+            }
+            let scaled = scale_pixels(&frame_rgb, 4, frame.width, frame.height);
+            let (thumbnail, width, height) = downscale(
+                &scaled,
+                SCALE_FACTOR_WIDTH * frame.width,
+                SCALE_FACTOR_HEIGHT * frame.height,
+                size,
+            );
+            write_unscaled_rgba_to_png(&path, &thumbnail, width, height)?;
+            self.output_written(&path);
+        }
+
+        (self.callback)(&Event::ThumbnailWritten {
+            index: frame.index,
+            path: &path,
+        });
+        Ok(())
+    }
+
+    fn write_frame_diff(
+        &mut self,
+        frame_stem: &str,
+        previous: &IndexedFrame,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+    ) -> Result<(), Error> {
+        let path = self.file_dir.join(format!("{frame_stem}-diff.png"));
+        if !self.claim_output(&path)? {
+            return Ok(());
+        }
+
+        let changed = analyze::changed_pixels(previous, frame);
+        let mut image = ega::indexed_to_rgba_with_palette(&frame.pixels, pal);
+        for (pixel, changed) in image.chunks_mut(4).zip(changed) {
+            if changed {
+                pixel[3] = 0xff;
+            } else {
+                let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(u32::from);
+                let gray = ((r * 299 + g * 587 + b * 114) / 4000) as u8;
+                pixel[..3].fill(gray);
+            }
+        }
+        if self.options.native {
+            write_native_rgba_to_png(&path, &image, frame.width, frame.height)?;
+        } else {
+            write_rgba_to_png(&path, &image, frame.width, frame.height)?;
+        }
+        self.output_written(&path);
+        Ok(())
+    }
+
+    fn write_tiles(
+        &mut self,
+        frame_stem: &str,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+        tile_width: usize,
+        tile_height: usize,
+    ) -> Result<(), Error> {
+        let tiling = tiles::split(frame, tile_width, tile_height);
+
+        let tileset_name = format!("{frame_stem}-tiles.png");
+        let path = self.file_dir.join(&tileset_name);
+        if self.claim_output(&path)? {
+            let tileset = tiling.tileset();
+            let rgba = ega::indexed_to_rgba_with_palette(&tileset.pixels, pal);
+            write_unscaled_rgba_to_png(&path, &rgba, tileset.width, tileset.height)?;
+            self.output_written(&path);
+        }
+
+        let path = self.file_dir.join(format!("{frame_stem}-tiles.json"));
+        if self.claim_output(&path)? {
+            let mut w = BufWriter::new(File::create(&path)?);
+            tiling.write_json(&mut w, &tileset_name)?;
+            w.flush()?;
+            self.output_written(&path);
+        }
+
+        let path = self.file_dir.join(format!("{frame_stem}-tiles.csv"));
+        if self.claim_output(&path)? {
+            let mut w = BufWriter::new(File::create(&path)?);
+            tiling.write_csv(&mut w)?;
+            w.flush()?;
+            self.output_written(&path);
+        }
+        Ok(())
+    }
+
+    fn write_svg(
+        &mut self,
+        frame_stem: &str,
+        frame: &IndexedFrame,
+        pal: &[[u8; 4]; 16],
+    ) -> Result<(), Error> {
+        let path = self.file_dir.join(format!("{frame_stem}.svg"));
+        if !self.claim_output(&path)? {
+            return Ok(());
+        }
+        write_indexed_to_svg(
+            &path,
+            &frame.pixels,
+            frame.width,
+            frame.height,
+            pal,
+            !self.options.native,
+        )?;
+        self.output_written(&path);
+        Ok(())
+    }
+
+    fn write_outputs(
+        &mut self,
+        name: &Path,
+        src: &[u8],
+        remap: Option<[u8; 16]>,
+    ) -> Result<(), Error> {
+        let description = self.identify(name, src);
+        let dimensions = self.dimensions_of(name, src);
+        let source = &name.file_name().unwrap_or_default().to_string_lossy();
+        let source_hash = catalog::content_hash(src);
+
+        let stem = self.stem(name, description.as_deref());
+        let stem = stem.as_str();
+
+        let decoded = self.decode(src, remap, dimensions);
+        self.file_dir = match self.options.group_by {
+            Some(group_by) => {
+                let kind = decoded.as_ref().ok().map(|(extracted, _)| extracted.kind);
+                self.output_dir.join(group_by.folder(stem, kind))
+            }
+            None => self.output_dir.clone(),
+        };
+        if self.options.group_by.is_some() && (decoded.is_ok() || self.options.raw_planes) {
+            std::fs::create_dir_all(&self.file_dir)?;
+        }
+
+        if self.options.raw_planes {
+            self.write_raw_planes(stem, src)?;
+        }
+
+        let (extracted, pal) = decoded?;
+
+        if self.options.export_palette {
+            for format in PALETTE_FORMATS {
+                let path = self.file_dir.join(format!("{stem}.{}", format.extension()));
+                if self.claim_output(&path)? {
+                    format.write_to_file(&path, &pal, stem)?;
+                    self.output_written(&path);
+                }
+            }
+        }
+
+        let duplicates = if self.options.dedupe && extracted.kind == Kind::Sheet {
+            analyze::duplicates(&extracted.frames)
+        } else {
+            vec![None; extracted.frames.len()]
+        };
+
+        let frame_stems = extracted
+            .frames
+            .iter()
+            .map(|frame| self.frame_stem(stem, extracted.kind, frame.index))
+            .collect::<Vec<_>>();
+
+        // With jobs to spare, the images are encoded up front, several at
+        // once, and written below in order.
+        let mut encoded = HashMap::new();
+        if self.options.jobs > 1 {
+            let mut images = Vec::new();
+            for ((frame, duplicate), frame_stem) in
+                extracted.frames.iter().zip(&duplicates).zip(&frame_stems)
+            {
+                if duplicate.is_some() {
+                    continue;
+                }
+                let text = frame_text(source, source_hash, frame, &pal);
+                if self.options.mirror {
+                    images.push(PendingImage {
+                        path: self.file_dir.join(format!("{frame_stem}-flip.png")),
+                        frame: frame.flipped(),
+                        text: mirrored_text(&text),
+                    });
+                }
+                images.push(PendingImage {
+                    path: self.file_dir.join(format!("{frame_stem}.png")),
+                    frame: frame.clone(),
+                    text,
+                });
+            }
+            encoded = self.encode_ahead(images, &pal);
+        }
+
+        let mut previous = None;
+        for ((frame, duplicate), frame_stem) in
+            extracted.frames.iter().zip(duplicates).zip(frame_stems)
+        {
+            let previous = previous.replace(frame);
+            (self.callback)(&Event::FrameDecoded {
+                index: frame.index,
+                width: frame.width,
+                height: frame.height,
+            });
+
+            if let Some(of) = duplicate {
+                (self.callback)(&Event::FrameDuplicate {
+                    index: frame.index,
+                    of,
+                });
+                continue;
+            }
+
+            let text = frame_text(source, source_hash, frame, &pal);
+            let output_filename = self.file_dir.join(format!("{frame_stem}.png"));
+            if let Some(dir) = output_filename.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            self.write_frame(&output_filename, frame, &pal, &text, &mut encoded)?;
+            if self.options.svg {
+                self.write_svg(&frame_stem, frame, &pal)?;
+            }
+
+            (self.callback)(&Event::FrameWritten {
+                index: frame.index,
+                path: &output_filename,
+            });
+            if let Some(size) = self.options.thumbnails {
+                self.write_thumbnail(&frame_stem, frame, &pal, size)?;
+            }
+            if let Some(previous) = previous.filter(|_| self.options.frame_diffs) {
+                self.write_frame_diff(&frame_stem, previous, frame, &pal)?;
+            }
+
+            if self.options.mirror {
+                let flip_filename = self.file_dir.join(format!("{frame_stem}-flip.png"));
+                let flipped = frame.flipped();
+                let text = mirrored_text(&text);
+                self.write_frame(&flip_filename, &flipped, &pal, &text, &mut encoded)?;
+                if self.options.svg {
+                    self.write_svg(&format!("{frame_stem}-flip"), &flipped, &pal)?;
+                }
+            }
+        }
+
+        if let Some((width, height)) = self.options.tiles {
+            if extracted.kind == Kind::Screen {
+                for frame in &extracted.frames {
+                    let frame_stem = self.frame_stem(stem, extracted.kind, frame.index);
+                    self.write_tiles(&frame_stem, frame, &pal, width, height)?;
+                }
+            }
+        }
+
+        if self.options.aseprite && extracted.kind != Kind::Screen {
+            let path = self.file_dir.join(format!("{stem}.aseprite"));
+            if self.claim_output(&path)? {
+                let duration = 10 * group::GIF_FRAME_DELAY;
+                write_aseprite_to_file(&path, &extracted.frames, &pal, duration)?;
+                self.output_written(&path);
+            }
+        }
+
+        let whole_sheet = extracted.kind == Kind::Sheet && self.options.frames.is_none();
+        if let Some(engine) = self.options.engine.filter(|_| whole_sheet) {
+            let output = engine::write_atlas(
+                engine,
+                &self.file_dir,
+                stem,
+                &extracted.frames,
+                &pal,
+                self.options.dedupe,
+                self.options.mirror,
+                self.options.overwrite,
+            )?;
+            let image_name = engine::atlas_filename(stem);
+            let path = self.file_dir.join(&image_name);
+            let metadata_path =
+                self.file_dir
+                    .join(engine::metadata_filename(engine, stem, &image_name));
+            match output {
+                AtlasOutput::Written => {
+                    self.record_output(&path);
+                    self.record_output(&metadata_path);
+                    self.output_written(&path);
+                    self.output_written(&metadata_path);
+                }
+                AtlasOutput::Skipped => {
+                    self.record_output(&path);
+                    self.record_output(&metadata_path);
+                    (self.callback)(&Event::OutputSkipped { path: &path });
+                }
+                AtlasOutput::Empty => {}
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! A C interface to the decoders, for linking the crate into engines
+//! written in other languages.
+//!
+//! Functions returning `int` return 0 on success and -1 on failure. Images
+//! are written as 8-bit RGBA with index 0 transparent, in the colors of
+//! the default palette unless the file carries its own palette registers.
+//! A panic inside a call is caught and reported as a failure, it never
+//! unwinds into the caller. `include/exxos_kult_extract.h` is
+//! generated from this module by `cbindgen`.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::ega::{self, Monitor};
+use crate::format::{Extract, Extracted, IndexedFrame, PlanarScreen, Sprites};
+
+/// The decoded frames of a sprite sheet.
+pub struct KultSheet {
+    frames: Vec<IndexedFrame>,
+    pal: [[u8; 4]; 16],
+}
+
+fn palette(extracted: &Extracted) -> [[u8; 4]; 16] {
+    ega::palette(Monitor::default(), &extracted.registers(), 1.0)
+}
+
+/// Runs `f`, returning `failed` if it panics.
+fn guard<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+/// Decodes `len` bytes at `src` with `format`.
+///
+/// # Safety
+///
+/// `src` must point to `len` readable bytes.
+unsafe fn extract(format: &dyn Extract, src: *const u8, len: usize) -> Option<Extracted> {
+    if src.is_null() {
+        return None;
+    }
+    let src = slice::from_raw_parts(src, len);
+    format.extract(src).ok()
+}
+
+/// Writes the RGBA pixels of `frame` to `out`, which must hold `out_len`
+/// bytes, failing if it is too small.
+///
+/// # Safety
+///
+/// `out` must point to `out_len` writable bytes.
+unsafe fn write_rgba(
+    frame: &IndexedFrame,
+    pal: &[[u8; 4]; 16],
+    out: *mut u8,
+    out_len: usize,
+) -> i32 {
+    let rgba = ega::indexed_to_rgba_with_palette(&frame.pixels, pal);
+    if out.is_null() || out_len < rgba.len() {
+        return -1;
+    }
+    ptr::copy_nonoverlapping(rgba.as_ptr(), out, rgba.len());
+    0
+}
+
+/// Decodes a fullscreen image into `out`, which must hold at least
+/// `width * height * 4` bytes. The size of the image is stored in `width`
+/// and `height` whether `out` is large enough or not, so passing a null
+/// `out` queries it.
+///
+/// # Safety
+///
+/// `src` must point to `len` readable bytes, `out` to `out_len` writable
+/// bytes or be null, and `width` and `height` must be valid or null.
+#[no_mangle]
+pub unsafe extern "C" fn kult_decode_fullscreen(
+    src: *const u8,
+    len: usize,
+    out: *mut u8,
+    out_len: usize,
+    width: *mut usize,
+    height: *mut usize,
+) -> i32 {
+    guard(-1, || {
+        let Some(extracted) = extract(&PlanarScreen::new(), src, len) else {
+            return -1;
+        };
+        let frame = &extracted.frames[0];
+
+        if !width.is_null() {
+            *width = frame.width;
+        }
+        if !height.is_null() {
+            *height = frame.height;
+        }
+
+        write_rgba(frame, &palette(&extracted), out, out_len)
+    })
+}
+
+/// Decodes a sprite sheet, returning null if it is not one. The data is
+/// not referenced after the call. Free the sheet with [`kult_sheet_free`].
+///
+/// # Safety
+///
+/// `src` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn kult_sheet_open(src: *const u8, len: usize) -> *mut KultSheet {
+    guard(ptr::null_mut(), || {
+        match extract(&Sprites::new(), src, len) {
+            Some(extracted) => Box::into_raw(Box::new(KultSheet {
+                pal: palette(&extracted),
+                frames: extracted.frames,
+            })),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// The number of frames that decoded. Frames are numbered from 0 in this
+/// interface, skipping frames that did not decode.
+///
+/// # Safety
+///
+/// `sheet` must come from [`kult_sheet_open`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn kult_sheet_frame_count(sheet: *const KultSheet) -> usize {
+    guard(0, || sheet.as_ref().map_or(0, |s| s.frames.len()))
+}
+
+/// Stores the size of frame `n` in `width` and `height`.
+///
+/// # Safety
+///
+/// `sheet` must come from [`kult_sheet_open`] and not have been freed, and
+/// `width` and `height` must be valid or null.
+#[no_mangle]
+pub unsafe extern "C" fn kult_sheet_frame_size(
+    sheet: *const KultSheet,
+    n: usize,
+    width: *mut usize,
+    height: *mut usize,
+) -> i32 {
+    guard(-1, || {
+        let Some(frame) = sheet.as_ref().and_then(|s| s.frames.get(n)) else {
+            return -1;
+        };
+
+        if !width.is_null() {
+            *width = frame.width;
+        }
+        if !height.is_null() {
+            *height = frame.height;
+        }
+        0
+    })
+}
+
+/// Writes frame `n` into `out`, which must hold at least
+/// `width * height * 4` bytes.
+///
+/// # Safety
+///
+/// `sheet` must come from [`kult_sheet_open`] and not have been freed, and
+/// `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn kult_sheet_frame_rgba(
+    sheet: *const KultSheet,
+    n: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> i32 {
+    guard(-1, || {
+        let Some(sheet) = sheet.as_ref() else {
+            return -1;
+        };
+        let Some(frame) = sheet.frames.get(n) else {
+            return -1;
+        };
+
+        write_rgba(frame, &sheet.pal, out, out_len)
+    })
+}
+
+/// Frees a sheet. Null is ignored.
+///
+/// # Safety
+///
+/// `sheet` must come from [`kult_sheet_open`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn kult_sheet_free(sheet: *mut KultSheet) {
+    guard((), || {
+        if !sheet.is_null() {
+            drop(Box::from_raw(sheet));
+        }
+    })
+}
@@ -0,0 +1,662 @@
+//! Format detection and decoding.
+//!
+//! Every asset format implements [`Detect`] and [`Extract`] and is
+//! registered with a [`FormatRegistry`]. All commands go through the
+//! registry, so a new format only needs to be registered to be picked up
+//! by `extract`, `info`, `list` and `analyze`.
+
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+use crate::ega::{self, decode_planar_ega_to_indexed};
+use crate::hercules::HerculesScreen;
+use crate::sprite::{Entry, HitBox, SizeOrder, SpriteSheet};
+
+#[derive(Clone)]
+pub struct IndexedFrame {
+    pub index: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub hitboxes: Vec<HitBox>,
+}
+
+impl IndexedFrame {
+    /// The frame mirrored horizontally, hit boxes included.
+    pub fn flipped(&self) -> IndexedFrame {
+        let pixels = self
+            .pixels
+            .chunks(self.width.max(1))
+            .flat_map(|row| row.iter().rev().copied())
+            .collect();
+        let hitboxes = self
+            .hitboxes
+            .iter()
+            .map(|b| HitBox {
+                x: self.width - b.x - b.w,
+                ..*b
+            })
+            .collect();
+
+        IndexedFrame {
+            pixels,
+            hitboxes,
+            ..*self
+        }
+    }
+
+    /// The frame mirrored vertically, hit boxes included.
+    pub fn flipped_vertically(&self) -> IndexedFrame {
+        let pixels = self
+            .pixels
+            .chunks(self.width.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        let hitboxes = self
+            .hitboxes
+            .iter()
+            .map(|b| HitBox {
+                y: self.height.saturating_sub(b.y + b.h),
+                ..*b
+            })
+            .collect();
+
+        IndexedFrame {
+            pixels,
+            hitboxes,
+            ..*self
+        }
+    }
+
+    /// The frame rotated clockwise by `turns` quarter turns.
+    pub fn rotated(&self, turns: usize) -> IndexedFrame {
+        (0..turns % 4).fold(self.clone(), |frame, _| frame.rotated_once())
+    }
+
+    fn rotated_once(&self) -> IndexedFrame {
+        let (width, height) = (self.height, self.width);
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(self.pixels[(self.height - 1 - x) * self.width + y]);
+            }
+        }
+        let hitboxes = self
+            .hitboxes
+            .iter()
+            .map(|b| HitBox {
+                x: self.height.saturating_sub(b.y + b.h),
+                y: b.x,
+                w: b.h,
+                h: b.w,
+            })
+            .collect();
+
+        IndexedFrame {
+            width,
+            height,
+            pixels,
+            hitboxes,
+            ..*self
+        }
+    }
+
+    /// The part of the frame inside the rectangle, which may be empty.
+    /// Hit boxes are clipped to it.
+    pub fn cropped(&self, x: usize, y: usize, w: usize, h: usize) -> IndexedFrame {
+        let (x0, x1) = (x.min(self.width), x.saturating_add(w).min(self.width));
+        let (y0, y1) = (y.min(self.height), y.saturating_add(h).min(self.height));
+
+        let pixels = (y0..y1)
+            .flat_map(|row| &self.pixels[row * self.width + x0..row * self.width + x1])
+            .copied()
+            .collect();
+        let hitboxes = self
+            .hitboxes
+            .iter()
+            .filter_map(|b| {
+                let (bx0, bx1) = (b.x.max(x0), (b.x + b.w).min(x1));
+                let (by0, by1) = (b.y.max(y0), (b.y + b.h).min(y1));
+                (bx0 < bx1 && by0 < by1).then(|| HitBox {
+                    x: bx0 - x0,
+                    y: by0 - y0,
+                    w: bx1 - bx0,
+                    h: by1 - by0,
+                })
+            })
+            .collect();
+
+        IndexedFrame {
+            width: x1 - x0,
+            height: y1 - y0,
+            pixels,
+            hitboxes,
+            ..*self
+        }
+    }
+}
+
+/// Geometric changes to the frames before they are written, in the
+/// order of the fields: the crop rectangle is in the coordinates of the
+/// decoded frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Geometry {
+    /// `x`, `y`, width and height of the part to keep.
+    pub crop: Option<(usize, usize, usize, usize)>,
+    pub flip_horizontally: bool,
+    pub flip_vertically: bool,
+    /// Clockwise quarter turns.
+    pub rotate: usize,
+}
+
+impl Geometry {
+    pub fn is_identity(&self) -> bool {
+        *self == Geometry::default()
+    }
+
+    pub fn apply(&self, frame: &IndexedFrame) -> IndexedFrame {
+        let mut frame = match self.crop {
+            Some((x, y, w, h)) => frame.cropped(x, y, w, h),
+            None => frame.clone(),
+        };
+        if self.flip_horizontally {
+            frame = frame.flipped();
+        }
+        if self.flip_vertically {
+            frame = frame.flipped_vertically();
+        }
+        frame.rotated(self.rotate)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A single image, written as `<name>.png`.
+    Screen,
+    /// A sequence of frames, written as `<name>-NN.png`.
+    Sheet,
+}
+
+/// The decoded contents of a file.
+pub struct Extracted {
+    pub kind: Kind,
+    pub frames: Vec<IndexedFrame>,
+    /// Problems that did not prevent decoding the rest of the file.
+    pub warnings: Vec<String>,
+    /// Details that are expected in some files and only worth mentioning
+    /// when asked for.
+    pub notes: Vec<String>,
+    /// Inconsistencies in the headers, also described by `warnings` or
+    /// `notes`, which strict mode rejects the file for.
+    pub problems: Vec<Problem>,
+    /// Palette registers to load before showing the frames.
+    pub remap: Option<[u8; 16]>,
+    /// The frames are for a monochrome monitor: index 1 is lit, in the
+    /// color of the phosphor rather than through the EGA palette.
+    pub monochrome: bool,
+}
+
+/// An inconsistency in the headers of a file that decoding works around.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A sprite sheet frame that could not be decoded. `stopped` if the
+    /// frames after it could not be reached either.
+    Frame {
+        index: usize,
+        reason: String,
+        stopped: bool,
+    },
+    /// The size in a sprite sheet header is little-endian.
+    LittleEndianSize,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Frame { index, reason, .. } => write!(f, "Frame {index}: {reason}"),
+            Problem::LittleEndianSize => write!(f, "Sheet size is little-endian"),
+        }
+    }
+}
+
+impl Extracted {
+    /// The palette registers to show the frames with, see [`ega::palette`].
+    pub fn registers(&self) -> [u8; 16] {
+        self.remap.unwrap_or(ega::DEFAULT_PALETTE_REGISTERS)
+    }
+}
+
+pub trait Detect {
+    /// Checks whether `src` looks like this format, returning the reason
+    /// if it does not.
+    fn detect(&self, src: &[u8]) -> Result<(), String>;
+}
+
+/// Settings of a run that formats may honor when extracting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// Decode what may be hit boxes after the pixels of sprite frames.
+    pub hitboxes: bool,
+}
+
+pub trait Extract {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, Error>;
+
+    /// Like [`Extract::extract`], with the settings of the run. Formats
+    /// without settings of their own ignore them.
+    fn extract_with(&self, src: &[u8], settings: &Settings) -> Result<Extracted, Error> {
+        let _ = settings;
+        self.extract(src)
+    }
+}
+
+pub trait Format: Detect + Extract + Send + Sync {
+    /// Short identifier, as used on the command line.
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// What detection looks for, for listing the supported formats.
+    fn signature(&self) -> String {
+        String::new()
+    }
+
+    /// Where the planes start in `src`, for formats holding a single
+    /// planar screen. Only such screens can be repaired.
+    fn planes_offset(&self, src: &[u8]) -> Option<usize> {
+        let _ = src;
+        None
+    }
+}
+
+/// Planar fullscreen images: four consecutive bitplanes.
+pub struct PlanarScreen {
+    layouts: Vec<(usize, usize)>,
+    exact_size: bool,
+}
+
+impl PlanarScreen {
+    /// Recognizes the standard layouts by file size.
+    pub fn new() -> PlanarScreen {
+        PlanarScreen {
+            layouts: ega::FULLSCREEN_LAYOUTS.to_vec(),
+            exact_size: true,
+        }
+    }
+
+    /// Accepts any file large enough for a single layout.
+    ///
+    /// Either way, a screen may be followed by a table of palette registers.
+    pub fn with_size(width: usize, height: usize) -> PlanarScreen {
+        PlanarScreen {
+            layouts: vec![(width, height)],
+            exact_size: false,
+        }
+    }
+
+    fn layout(&self, src: &[u8]) -> Option<(usize, usize)> {
+        self.layouts.iter().copied().find(|&(w, h)| {
+            let size = ega::planar_size(w, h);
+            if self.exact_size {
+                size == src.len() || remap_after(src, size).is_some()
+            } else {
+                size <= src.len()
+            }
+        })
+    }
+}
+
+/// A palette register table making up the rest of the file after `size`
+/// bytes of image data.
+fn remap_after(src: &[u8], size: usize) -> Option<[u8; 16]> {
+    if src.len() != size + ega::PALETTE_REMAP_SIZE {
+        return None;
+    }
+    ega::parse_palette_remap(&src[size..])
+}
+
+impl Default for PlanarScreen {
+    fn default() -> Self {
+        PlanarScreen::new()
+    }
+}
+
+impl Detect for PlanarScreen {
+    fn detect(&self, src: &[u8]) -> Result<(), String> {
+        match (self.layout(src), &self.layouts[..]) {
+            (Some(_), _) => Ok(()),
+            (None, [(w, h)]) if !self.exact_size => {
+                Err(format!("file too small for a {w}x{h} image"))
+            }
+            (None, _) => Err(format!("size {} matches no screen layout", src.len())),
+        }
+    }
+}
+
+impl Extract for PlanarScreen {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, Error> {
+        let Some((width, height)) = self.layout(src) else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Not a fullscreen image, incorrect size",
+            ));
+        };
+
+        Ok(Extracted {
+            kind: Kind::Screen,
+            frames: vec![IndexedFrame {
+                index: 0,
+                width,
+                height,
+                pixels: decode_planar_ega_to_indexed(src, width, height),
+                hitboxes: Vec::new(),
+            }],
+            warnings: Vec::new(),
+            notes: Vec::new(),
+            problems: Vec::new(),
+            remap: remap_after(src, ega::planar_size(width, height)),
+            monochrome: false,
+        })
+    }
+}
+
+impl Format for PlanarScreen {
+    fn name(&self) -> &'static str {
+        "screen"
+    }
+
+    fn description(&self) -> &'static str {
+        "planar EGA fullscreen image"
+    }
+
+    fn planes_offset(&self, _src: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+
+    fn signature(&self) -> String {
+        let sizes = self
+            .layouts
+            .iter()
+            .map(|&(w, h)| format!("{} bytes ({w}x{h})", ega::planar_size(w, h)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        if self.exact_size {
+            format!(
+                "{sizes}, optionally followed by {} palette registers",
+                ega::PALETTE_REMAP_SIZE
+            )
+        } else {
+            format!("at least {sizes}")
+        }
+    }
+}
+
+/// 320x200 planar screens preceded by their palette registers, as 16
+/// little-endian words. Some files put a 2 byte header word in front of
+/// those, which is skipped.
+pub struct PrefixedScreen;
+
+/// Size of the palette register words preceding a prefixed screen.
+pub const REGISTER_WORDS_SIZE: usize = 2 * 16;
+
+impl PrefixedScreen {
+    /// The palette registers and the image data.
+    fn split(src: &[u8]) -> Result<([u8; 16], &[u8]), String> {
+        let prefix_size = src
+            .len()
+            .checked_sub(ega::FULLSCREEN_SIZE)
+            .ok_or_else(|| format!("size {} too small for a screen", src.len()))?;
+
+        let words = match prefix_size {
+            REGISTER_WORDS_SIZE => &src[..REGISTER_WORDS_SIZE],
+            n if n == 2 + REGISTER_WORDS_SIZE => &src[2..n],
+            _ => return Err(format!("size {} matches no prefixed screen", src.len())),
+        };
+
+        let mut registers = [0; 16];
+        for (register, word) in registers.iter_mut().zip(words.chunks(2)) {
+            match u16::from_le_bytes([word[0], word[1]]) {
+                v @ 0..64 => *register = v as u8,
+                v => return Err(format!("palette register value {v} out of range")),
+            }
+        }
+
+        Ok((registers, &src[prefix_size..]))
+    }
+}
+
+impl Detect for PrefixedScreen {
+    fn detect(&self, src: &[u8]) -> Result<(), String> {
+        PrefixedScreen::split(src).map(|_| ())
+    }
+}
+
+impl Extract for PrefixedScreen {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, Error> {
+        let (registers, data) = PrefixedScreen::split(src).map_err(|reason| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Not a prefixed screen, {reason}"),
+            )
+        })?;
+
+        let (width, height) = (ega::FULLSCREEN_WIDTH, ega::FULLSCREEN_HEIGHT);
+        Ok(Extracted {
+            kind: Kind::Screen,
+            frames: vec![IndexedFrame {
+                index: 0,
+                width,
+                height,
+                pixels: decode_planar_ega_to_indexed(data, width, height),
+                hitboxes: Vec::new(),
+            }],
+            warnings: Vec::new(),
+            notes: Vec::new(),
+            problems: Vec::new(),
+            remap: Some(registers),
+            monochrome: false,
+        })
+    }
+}
+
+impl Format for PrefixedScreen {
+    fn name(&self) -> &'static str {
+        "prefixed-screen"
+    }
+
+    fn description(&self) -> &'static str {
+        "planar EGA fullscreen image with its palette registers in front"
+    }
+
+    fn signature(&self) -> String {
+        format!(
+            "{} or {} bytes, an optional 2 byte header and 16 little-endian palette register words, then a 320x200 screen",
+            REGISTER_WORDS_SIZE + ega::FULLSCREEN_SIZE,
+            2 + REGISTER_WORDS_SIZE + ega::FULLSCREEN_SIZE
+        )
+    }
+
+    fn planes_offset(&self, src: &[u8]) -> Option<usize> {
+        src.len().checked_sub(ega::FULLSCREEN_SIZE)
+    }
+}
+
+/// Sprite sheets of interleaved 4bpp frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sprites {
+    hitboxes: bool,
+}
+
+impl Sprites {
+    pub fn new() -> Sprites {
+        Sprites::default()
+    }
+
+    /// Also decodes the hit boxes that may follow the pixels of a frame,
+    /// see [`Frame::hitboxes`](crate::sprite::Frame::hitboxes).
+    pub fn hitboxes(mut self, hitboxes: bool) -> Self {
+        self.hitboxes = hitboxes;
+        self
+    }
+}
+
+impl Detect for Sprites {
+    fn detect(&self, src: &[u8]) -> Result<(), String> {
+        SpriteSheet::parse(src)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Extract for Sprites {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, Error> {
+        let sheet = SpriteSheet::parse(src).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Not a valid sprite sheet, {e}"),
+            )
+        })?;
+
+        let mut frames = Vec::new();
+        let mut warnings = Vec::new();
+        let mut notes = Vec::new();
+        let mut problems = Vec::new();
+
+        if sheet.size_order() == SizeOrder::LittleEndian {
+            notes.push(format!("{}.", Problem::LittleEndianSize));
+            problems.push(Problem::LittleEndianSize);
+        }
+
+        for entry in sheet.entries() {
+            match entry {
+                Entry::Frame(f) => frames.push(IndexedFrame {
+                    index: f.index,
+                    width: f.width,
+                    height: f.height,
+                    pixels: f.to_indexed(),
+                    hitboxes: if self.hitboxes {
+                        f.hitboxes()
+                    } else {
+                        Vec::new()
+                    },
+                }),
+                Entry::Skipped { index, reason } => {
+                    let problem = Problem::Frame {
+                        index,
+                        reason,
+                        stopped: false,
+                    };
+                    warnings.push(format!("{problem}, skipping."));
+                    problems.push(problem);
+                }
+                Entry::Stopped { index, reason } => {
+                    let problem = Problem::Frame {
+                        index,
+                        reason,
+                        stopped: true,
+                    };
+                    warnings.push(format!("{problem}, stopping."));
+                    problems.push(problem);
+                }
+                Entry::Padding { len } => {
+                    notes.push(format!("{len} bytes of padding after the last frame."))
+                }
+            }
+        }
+
+        Ok(Extracted {
+            kind: Kind::Sheet,
+            frames,
+            warnings,
+            notes,
+            problems,
+            remap: None,
+            monochrome: false,
+        })
+    }
+
+    fn extract_with(&self, src: &[u8], settings: &Settings) -> Result<Extracted, Error> {
+        self.hitboxes(self.hitboxes || settings.hitboxes)
+            .extract(src)
+    }
+}
+
+impl Format for Sprites {
+    fn name(&self) -> &'static str {
+        "sprites"
+    }
+
+    fn description(&self) -> &'static str {
+        "sprite sheet of interleaved EGA frames"
+    }
+
+    fn signature(&self) -> String {
+        "big-endian (or little-endian) u32 size of the rest of the file, then frames".to_string()
+    }
+}
+
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn Format>>,
+}
+
+impl FormatRegistry {
+    /// A registry without any formats.
+    pub fn empty() -> FormatRegistry {
+        FormatRegistry {
+            formats: Vec::new(),
+        }
+    }
+
+    /// Registers a format. Formats are tried in registration order.
+    pub fn register<F: Format + 'static>(&mut self, format: F) {
+        self.formats.push(Box::new(format));
+    }
+
+    pub fn formats(&self) -> impl Iterator<Item = &dyn Format> {
+        self.formats.iter().map(|f| f.as_ref())
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&dyn Format> {
+        self.formats().find(|f| f.name() == name)
+    }
+
+    /// Finds the first format that accepts `src`. If there is none, the
+    /// error lists why each format rejected it.
+    pub fn detect(&self, src: &[u8]) -> Result<&dyn Format, Error> {
+        let mut reasons = Vec::new();
+
+        for format in self.formats() {
+            match format.detect(src) {
+                Ok(()) => return Ok(format),
+                Err(reason) => reasons.push(format!("{}: {reason}", format.name())),
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Unknown file format ({})", reasons.join("; ")),
+        ))
+    }
+
+    pub fn extract(&self, src: &[u8]) -> Result<(&dyn Format, Extracted), Error> {
+        let format = self.detect(src)?;
+        Ok((format, format.extract(src)?))
+    }
+}
+
+impl Default for FormatRegistry {
+    /// A registry with all built-in formats.
+    fn default() -> Self {
+        let mut registry = FormatRegistry::empty();
+        registry.register(PlanarScreen::new());
+        registry.register(PrefixedScreen);
+        registry.register(Sprites::new());
+        // Hercules screens are recognized by their size alone, so only
+        // files no other format accepts are taken for one.
+        registry.register(HerculesScreen);
+        registry
+    }
+}
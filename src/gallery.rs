@@ -0,0 +1,149 @@
+//! An HTML index of extracted images, for browsing a dump of the game's
+//! art. Images are shown as thumbnails grouped by source file and link to
+//! the full size PNGs.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Largest thumbnail side, in CSS pixels.
+pub const THUMBNAIL_SIZE: usize = 160;
+
+pub struct GalleryImage {
+    pub index: usize,
+    /// Size of the decoded frame, before scaling.
+    pub width: usize,
+    pub height: usize,
+    /// Relative to the directory of the gallery.
+    pub href: String,
+    /// Shown instead of the full size image if there is one, relative to
+    /// the directory of the gallery as well.
+    pub thumbnail: Option<String>,
+}
+
+pub struct GallerySection {
+    pub source: String,
+    /// What the catalog knows the source file as.
+    pub description: Option<String>,
+    pub images: Vec<GalleryImage>,
+}
+
+#[derive(Default)]
+pub struct Gallery {
+    pub sections: Vec<GallerySection>,
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl Gallery {
+    pub fn new() -> Gallery {
+        Gallery::default()
+    }
+
+    /// Starts the section of a source file, which following images are
+    /// added to.
+    pub fn start_section(&mut self, source: &str) {
+        self.sections.push(GallerySection {
+            source: source.to_string(),
+            description: None,
+            images: Vec::new(),
+        });
+    }
+
+    /// Describes the current section.
+    pub fn set_description(&mut self, description: &str) {
+        if let Some(section) = self.sections.last_mut() {
+            section.description = Some(description.to_string());
+        }
+    }
+
+    pub fn add_image(&mut self, image: GalleryImage) {
+        if self.sections.is_empty() {
+            self.start_section("");
+        }
+        self.sections.last_mut().unwrap().images.push(image);
+    }
+
+    /// Sets the thumbnail of the last image added.
+    pub fn set_thumbnail(&mut self, href: &str) {
+        if let Some(image) = self.sections.last_mut().and_then(|s| s.images.last_mut()) {
+            image.thumbnail = Some(href.to_string());
+        }
+    }
+
+    pub fn write_html<W: Write>(&self, w: &mut W) -> Result<(), std::io::Error> {
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(w, "<html>")?;
+        writeln!(w, "<head>")?;
+        writeln!(w, "<meta charset=\"utf-8\">")?;
+        writeln!(w, "<title>Kult assets</title>")?;
+        writeln!(w, "<style>")?;
+        writeln!(
+            w,
+            "body {{ font-family: sans-serif; background: #333; color: #eee; }}"
+        )?;
+        writeln!(w, "a {{ color: inherit; text-decoration: none; }}")?;
+        writeln!(
+            w,
+            "figure {{ display: inline-block; margin: 8px; text-align: center; }}"
+        )?;
+        writeln!(
+            w,
+            "img {{ max-width: {THUMBNAIL_SIZE}px; max-height: {THUMBNAIL_SIZE}px; image-rendering: pixelated; }}"
+        )?;
+        writeln!(w, "</style>")?;
+        writeln!(w, "</head>")?;
+        writeln!(w, "<body>")?;
+
+        for section in self.sections.iter().filter(|s| !s.images.is_empty()) {
+            let source = html_escape(&section.source);
+            writeln!(w, "<section>")?;
+            match &section.description {
+                Some(description) => writeln!(
+                    w,
+                    "<h2>{} <small>{source}</small></h2>",
+                    html_escape(description)
+                )?,
+                None => writeln!(w, "<h2>{source}</h2>")?,
+            }
+            for image in &section.images {
+                let href = html_escape(&image.href);
+                let src = html_escape(image.thumbnail.as_ref().unwrap_or(&image.href));
+                writeln!(
+                    w,
+                    "<figure><a href=\"{href}\"><img src=\"{src}\" alt=\"{source} frame {}\" loading=\"lazy\"></a>",
+                    image.index
+                )?;
+                writeln!(
+                    w,
+                    "<figcaption>{:02} &middot; {}x{}</figcaption></figure>",
+                    image.index, image.width, image.height
+                )?;
+            }
+            writeln!(w, "</section>")?;
+        }
+
+        writeln!(w, "</body>")?;
+        writeln!(w, "</html>")?;
+        Ok(())
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, filename: P) -> Result<(), std::io::Error> {
+        let mut w = BufWriter::new(File::create(filename)?);
+        self.write_html(&mut w)?;
+        w.flush()
+    }
+}
@@ -0,0 +1,280 @@
+//! Logical sprite groups spanning several files.
+//!
+//! A group manifest lists named animations and the frames they are made
+//! of, one group per line:
+//!
+//! ```text
+//! # Raven
+//! walk_left = [SPR01.EGA:0-7, SPR02.EGA:0-3]
+//! idle = [SPR03.EGA]
+//! ```
+//!
+//! Frames are given as a single index or an inclusive range, without one
+//! every frame of the file is used. Entries may be quoted and an array may
+//! span several lines, so manifests can also be written as plain TOML.
+//! Filenames are relative to the directory of the manifest.
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+#[cfg(all(feature = "png", feature = "gif"))]
+use crate::ega::EGA_PAL;
+#[cfg(all(feature = "png", feature = "gif"))]
+use crate::engine::{self, AtlasOutput, Engine};
+use crate::format::IndexedFrame;
+#[cfg(all(feature = "png", feature = "gif"))]
+use crate::output::{write_indexed_to_gif, OverwritePolicy};
+use crate::sprite::{Frame, SpriteSheet};
+#[cfg(all(feature = "png", feature = "gif", feature = "video"))]
+use crate::video::write_video;
+#[cfg(all(feature = "png", feature = "gif"))]
+use crate::video::Video;
+
+/// Delay between GIF frames, in hundredths of a second.
+pub const GIF_FRAME_DELAY: u16 = 10;
+
+pub struct GroupItem {
+    pub file: PathBuf,
+    pub frames: Option<RangeInclusive<usize>>,
+}
+
+pub struct Group {
+    pub name: String,
+    pub items: Vec<GroupItem>,
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {line}: {msg}"))
+}
+
+pub(crate) fn parse_range(s: &str) -> Option<RangeInclusive<usize>> {
+    match s.split_once('-') {
+        Some((first, last)) => {
+            let first = first.trim().parse().ok()?;
+            let last = last.trim().parse().ok()?;
+            (first <= last).then_some(first..=last)
+        }
+        None => {
+            let n = s.trim().parse().ok()?;
+            Some(n..=n)
+        }
+    }
+}
+
+fn parse_item(s: &str) -> GroupItem {
+    let s = s.trim().trim_matches('"');
+
+    if let Some((file, range)) = s.rsplit_once(':') {
+        if let Some(frames) = parse_range(range) {
+            return GroupItem {
+                file: PathBuf::from(file),
+                frames: Some(frames),
+            };
+        }
+    }
+
+    GroupItem {
+        file: PathBuf::from(s),
+        frames: None,
+    }
+}
+
+pub fn parse_manifest(text: &str) -> Result<Vec<Group>, Error> {
+    let mut groups = Vec::new();
+
+    let mut pending = String::new();
+    let mut pending_line = 0;
+
+    for (n, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(ofs) => &line[..ofs],
+            None => line,
+        };
+        let line = line.trim();
+
+        if pending.is_empty() {
+            // TOML table headers carry no information for us.
+            if line.is_empty() || (line.starts_with('[') && !line.contains('=')) {
+                continue;
+            }
+            pending_line = n + 1;
+        }
+
+        pending.push_str(line);
+        pending.push(' ');
+
+        if !pending.contains('[') || pending.contains(']') {
+            groups.push(parse_group(&pending, pending_line)?);
+            pending.clear();
+        }
+    }
+
+    if !pending.is_empty() {
+        return Err(invalid(pending_line, "unterminated frame list"));
+    }
+
+    Ok(groups)
+}
+
+fn parse_group(s: &str, line: usize) -> Result<Group, Error> {
+    let Some((name, list)) = s.split_once('=') else {
+        return Err(invalid(line, "expected `name = [frames]`"));
+    };
+
+    let name = name.trim().trim_matches('"').to_string();
+    if name.is_empty() {
+        return Err(invalid(line, "group without a name"));
+    }
+    // The name is used as a file name in the output directory.
+    if name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(invalid(line, "group names can't be paths"));
+    }
+
+    let list = list.trim();
+    let Some(list) = list.strip_prefix('[').and_then(|l| l.strip_suffix(']')) else {
+        return Err(invalid(line, "expected a frame list in brackets"));
+    };
+
+    let items = list
+        .split(',')
+        .filter(|item| !item.trim().is_empty())
+        .map(parse_item)
+        .collect::<Vec<_>>();
+
+    if items.is_empty() {
+        return Err(invalid(line, "empty frame list"));
+    }
+
+    Ok(Group { name, items })
+}
+
+fn group_frame(index: usize, frame: &Frame) -> IndexedFrame {
+    IndexedFrame {
+        index,
+        width: frame.width,
+        height: frame.height,
+        pixels: frame.to_indexed(),
+        hitboxes: Vec::new(),
+    }
+}
+
+/// Collects the frames of a group, reading the files relative to
+/// `base_dir`. The frames are renumbered in group order.
+pub fn collect_frames(group: &Group, base_dir: &Path) -> Result<Vec<IndexedFrame>, Error> {
+    let mut frames = Vec::new();
+
+    for item in &group.items {
+        let path = base_dir.join(&item.file);
+
+        let mut src = Vec::new();
+        File::open(&path)?.read_to_end(&mut src)?;
+
+        let sheet = SpriteSheet::parse(&src).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: not a valid sprite sheet, {e}", item.file.display()),
+            )
+        })?;
+
+        let sheet_frames = sheet.frames().collect::<Vec<_>>();
+
+        match &item.frames {
+            Some(range) => {
+                for index in range.clone() {
+                    let Some(frame) = sheet_frames.iter().find(|f| f.index == index) else {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("{}: no frame {index}", item.file.display()),
+                        ));
+                    };
+                    frames.push(group_frame(frames.len(), frame));
+                }
+            }
+            None => {
+                for frame in &sheet_frames {
+                    frames.push(group_frame(frames.len(), frame));
+                }
+            }
+        }
+    }
+
+    Ok(frames)
+}
+
+#[cfg(all(feature = "png", feature = "gif"))]
+pub struct GroupOutput {
+    pub frames: usize,
+    /// Outputs left alone because they already existed.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Writes the atlas with metadata and the animated GIF of a group to
+/// `output_dir`, and a video if asked to. Asking for a video fails without
+/// the `video` feature.
+#[cfg(all(feature = "png", feature = "gif"))]
+pub fn write_group(
+    group: &Group,
+    base_dir: &Path,
+    output_dir: &Path,
+    engine: Engine,
+    video: Option<&Video>,
+    policy: OverwritePolicy,
+) -> Result<GroupOutput, Error> {
+    let frames = collect_frames(group, base_dir)?;
+    if frames.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("group {} has no frames", group.name),
+        ));
+    }
+
+    let mut skipped = Vec::new();
+
+    if engine::write_atlas(
+        engine,
+        output_dir,
+        &group.name,
+        &frames,
+        &EGA_PAL,
+        false,
+        false,
+        policy,
+    )? == AtlasOutput::Skipped
+    {
+        skipped.push(output_dir.join(engine::atlas_filename(&group.name)));
+    }
+
+    let gif_filename = output_dir.join(format!("{}.gif", group.name));
+    if policy.prepare(&gif_filename)? {
+        let gif_frames = frames
+            .iter()
+            .map(|f| (f.pixels.clone(), f.width, f.height))
+            .collect::<Vec<_>>();
+        write_indexed_to_gif(gif_filename, &gif_frames, GIF_FRAME_DELAY)?;
+    } else {
+        skipped.push(gif_filename);
+    }
+
+    if let Some(video) = video {
+        let video_filename =
+            output_dir.join(format!("{}.{}", group.name, video.container.extension()));
+        if !policy.prepare(&video_filename)? {
+            skipped.push(video_filename);
+        } else {
+            #[cfg(feature = "video")]
+            write_video(video_filename, &frames, &EGA_PAL, video)?;
+            #[cfg(not(feature = "video"))]
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "built without the video feature",
+            ));
+        }
+    }
+
+    Ok(GroupOutput {
+        frames: frames.len(),
+        skipped,
+    })
+}
@@ -0,0 +1,125 @@
+//! Hercules monochrome screens, for the DOS release's data files for
+//! monochrome systems.
+//!
+//! A screen is a dump of the 32K of Hercules video memory in its 720x348
+//! graphics mode: 1 bit per pixel, leftmost pixel in the most significant
+//! bit, with the rows interleaved over four 8K banks so that row `y`
+//! starts at `0x2000 * (y % 4) + 90 * (y / 4)`.
+
+use std::io::{Error, ErrorKind};
+
+use crate::format::{Detect, Extract, Extracted, Format, IndexedFrame, Kind};
+
+pub const HERCULES_WIDTH: usize = 720;
+pub const HERCULES_HEIGHT: usize = 348;
+pub const HERCULES_SIZE: usize = 4 * HERCULES_BANK_SIZE;
+
+const HERCULES_BANK_SIZE: usize = 0x2000;
+
+/// The color of a monochrome monitor, which lit pixels are shown in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Phosphor {
+    /// P39, as on most Hercules monitors.
+    #[default]
+    Green,
+    /// P3.
+    Amber,
+    /// P4, paper white.
+    White,
+}
+
+impl Phosphor {
+    pub fn from_name(name: &str) -> Option<Phosphor> {
+        match name {
+            "green" => Some(Phosphor::Green),
+            "amber" => Some(Phosphor::Amber),
+            "white" => Some(Phosphor::White),
+            _ => None,
+        }
+    }
+
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Phosphor::Green => [0x33, 0xff, 0x00],
+            Phosphor::Amber => [0xff, 0xb0, 0x00],
+            Phosphor::White => [0xff, 0xff, 0xff],
+        }
+    }
+
+    /// A palette for monochrome frames: black and the phosphor color. The
+    /// unused indices are black as well.
+    pub fn palette(self) -> [[u8; 4]; 16] {
+        let [r, g, b] = self.color();
+        let mut pal = [[0x00, 0x00, 0x00, 0xff]; 16];
+        pal[1] = [r, g, b, 0xff];
+        pal
+    }
+}
+
+/// Decodes interleaved 1bpp Hercules memory into indices 0 and 1.
+pub fn decode_hercules_to_indexed(src: &[u8]) -> Vec<u8> {
+    let span = HERCULES_WIDTH / 8;
+    let mut frame = vec![0u8; HERCULES_WIDTH * HERCULES_HEIGHT];
+
+    for y in 0..HERCULES_HEIGHT {
+        let row = HERCULES_BANK_SIZE * (y % 4) + span * (y / 4);
+        for x in 0..HERCULES_WIDTH {
+            let b = src.get(row + x / 8).copied().unwrap_or(0);
+            frame[y * HERCULES_WIDTH + x] = (b >> (7 - x % 8)) & 1;
+        }
+    }
+
+    frame
+}
+
+pub struct HerculesScreen;
+
+impl Detect for HerculesScreen {
+    fn detect(&self, src: &[u8]) -> Result<(), String> {
+        if src.len() != HERCULES_SIZE {
+            return Err(format!("size {} is not {HERCULES_SIZE}", src.len()));
+        }
+        Ok(())
+    }
+}
+
+impl Extract for HerculesScreen {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, Error> {
+        if let Err(reason) = self.detect(src) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Not a Hercules screen, {reason}"),
+            ));
+        }
+
+        Ok(Extracted {
+            kind: Kind::Screen,
+            frames: vec![IndexedFrame {
+                index: 0,
+                width: HERCULES_WIDTH,
+                height: HERCULES_HEIGHT,
+                pixels: decode_hercules_to_indexed(src),
+                hitboxes: Vec::new(),
+            }],
+            warnings: Vec::new(),
+            notes: Vec::new(),
+            problems: Vec::new(),
+            remap: None,
+            monochrome: true,
+        })
+    }
+}
+
+impl Format for HerculesScreen {
+    fn name(&self) -> &'static str {
+        "hercules"
+    }
+
+    fn description(&self) -> &'static str {
+        "Hercules monochrome 720x348 screen"
+    }
+
+    fn signature(&self) -> String {
+        format!("{HERCULES_SIZE} bytes, four interleaved banks of 1bpp rows")
+    }
+}
@@ -0,0 +1,124 @@
+//! Adapters between the Kult decoders and the `image` crate.
+//!
+//! Besides the conversion helpers, [`register`] installs a decoding hook for
+//! the `.ega` extension, after which `image::open("PIC.EGA")` just works.
+
+use std::io::{Error, ErrorKind, Read};
+
+use ::image::error::{DecodingError, ImageFormatHint};
+use ::image::{ColorType, DynamicImage, ImageDecoder, ImageError, ImageResult, RgbaImage};
+
+use crate::ega::{self, Monitor};
+use crate::format::{Extract, Extracted, FormatRegistry, IndexedFrame, PlanarScreen, Sprites};
+use crate::hercules::Phosphor;
+use crate::sprite::{Frame, SpriteSheet};
+
+fn to_image(frame: &IndexedFrame, pal: &[[u8; 4]; 16]) -> RgbaImage {
+    let rgba = ega::indexed_to_rgba_with_palette(&frame.pixels, pal);
+    RgbaImage::from_raw(frame.width as u32, frame.height as u32, rgba).unwrap()
+}
+
+fn palette(extracted: &Extracted) -> [[u8; 4]; 16] {
+    if extracted.monochrome {
+        return Phosphor::default().palette();
+    }
+    ega::palette(Monitor::default(), &extracted.registers(), 1.0)
+}
+
+pub fn fullscreen_to_image(src: &[u8]) -> Result<RgbaImage, Error> {
+    let extracted = PlanarScreen::new().extract(src)?;
+    Ok(to_image(&extracted.frames[0], &palette(&extracted)))
+}
+
+pub fn frame_to_image(frame: &Frame) -> RgbaImage {
+    RgbaImage::from_raw(frame.width as u32, frame.height as u32, frame.to_rgba()).unwrap()
+}
+
+pub fn sprite_sheet_to_images(src: &[u8]) -> Result<Vec<RgbaImage>, Error> {
+    let extracted = Sprites::new().extract(src)?;
+    let pal = palette(&extracted);
+    Ok(extracted.frames.iter().map(|f| to_image(f, &pal)).collect())
+}
+
+/// Decodes the first frame of any registered format, which for
+/// fullscreen images is the whole image.
+pub fn decode(src: &[u8]) -> Result<DynamicImage, Error> {
+    let (_, extracted) = FormatRegistry::default().extract(src)?;
+    let Some(frame) = extracted.frames.first() else {
+        return Err(Error::new(ErrorKind::InvalidData, "no frames in file"));
+    };
+
+    Ok(DynamicImage::ImageRgba8(to_image(
+        frame,
+        &palette(&extracted),
+    )))
+}
+
+/// An `image::ImageDecoder` for the Kult formats. Fullscreen images decode
+/// as a whole; for sprite sheets a single frame is selected.
+pub struct KultDecoder {
+    image: RgbaImage,
+}
+
+fn decoding_error(e: Error) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Name("Kult EGA".to_string()),
+        e,
+    ))
+}
+
+impl KultDecoder {
+    pub fn new<R: Read>(r: R) -> ImageResult<KultDecoder> {
+        let src = read_all(r)?;
+        let image = decode(&src).map_err(decoding_error)?.into_rgba8();
+        Ok(KultDecoder { image })
+    }
+
+    pub fn with_frame<R: Read>(r: R, index: usize) -> ImageResult<KultDecoder> {
+        let src = read_all(r)?;
+        let sheet = SpriteSheet::parse(&src).map_err(decoding_error)?;
+        let Some(frame) = sheet.frames().find(|f| f.index == index) else {
+            return Err(decoding_error(Error::new(
+                ErrorKind::InvalidInput,
+                format!("no frame {index} in sprite sheet"),
+            )));
+        };
+        Ok(KultDecoder {
+            image: frame_to_image(&frame),
+        })
+    }
+}
+
+fn read_all<R: Read>(mut r: R) -> ImageResult<Vec<u8>> {
+    let mut src = Vec::new();
+    r.read_to_end(&mut src).map_err(ImageError::IoError)?;
+    Ok(src)
+}
+
+impl ImageDecoder for KultDecoder {
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn color_type(&self) -> ColorType {
+        ColorType::Rgba8
+    }
+
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        buf.copy_from_slice(self.image.as_raw());
+        Ok(())
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
+
+/// Registers a decoding hook for `.ega` files with the `image` crate.
+/// Returns false if a hook for the extension was already registered.
+pub fn register() -> bool {
+    ::image::hooks::register_decoding_hook(
+        "ega".into(),
+        Box::new(|r| Ok(Box::new(KultDecoder::new(r)?))),
+    )
+}
@@ -0,0 +1,170 @@
+//! State of incremental extraction: which files were extracted to which
+//! outputs, so files that did not change can be skipped the next time.
+//!
+//! The state is kept in the output directory, one extracted file per
+//! line: the hash of the file along with the options it was extracted
+//! with, its path, and the paths of its outputs relative to the output
+//! directory, separated by tabs. Bytes of paths that would break the
+//! format, `%`, control characters like tabs and line breaks and bytes
+//! that are not UTF-8, are written percent-encoded as `%XX`.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+pub const STATE_FILENAME: &str = ".kult-extract-state";
+
+const HASH_PREFIX: &str = "fnv:";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Record {
+    hash: u64,
+    outputs: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    files: BTreeMap<PathBuf, Record>,
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {line}: {msg}"))
+}
+
+/// Writes `path` for a field of the state file.
+fn escape(path: &Path) -> String {
+    let mut out = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '%' || c.is_control() {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{b:02X}"));
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+/// Reads a path written by [`escape`].
+fn unescape(field: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::new();
+    let mut rest = field.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    path_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Other systems only get paths back that are valid UTF-8.
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes)
+        .ok()
+        .map(|s| PathBuf::from(OsString::from(s)))
+}
+
+impl State {
+    pub fn parse(text: &str) -> Result<State, Error> {
+        let mut state = State::default();
+
+        for (n, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split('\t');
+            let hash = fields
+                .next()
+                .and_then(|h| h.strip_prefix(HASH_PREFIX))
+                .and_then(|h| u64::from_str_radix(h, 16).ok())
+                .ok_or_else(|| invalid(n + 1, "invalid hash"))?;
+            let Some(path) = fields.next().filter(|p| !p.is_empty()) else {
+                return Err(invalid(n + 1, "entry without a file"));
+            };
+            let path = unescape(path).ok_or_else(|| invalid(n + 1, "invalid path"))?;
+            let outputs = fields
+                .map(unescape)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid(n + 1, "invalid path"))?;
+            state.files.insert(path, Record { hash, outputs });
+        }
+
+        Ok(state)
+    }
+
+    /// Reads the state of the output directory `dir`, which is empty if
+    /// nothing was extracted there incrementally yet.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<State, Error> {
+        match fs::read_to_string(dir.as_ref().join(STATE_FILENAME)) {
+            Ok(text) => State::parse(&text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let mut text = String::new();
+        for (path, record) in &self.files {
+            text.push_str(&format!(
+                "{HASH_PREFIX}{:016x}\t{}",
+                record.hash,
+                escape(path)
+            ));
+            for output in &record.outputs {
+                text.push_str(&format!("\t{}", escape(output)));
+            }
+            text.push('\n');
+        }
+        fs::write(dir.as_ref().join(STATE_FILENAME), text)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Whether `path` was extracted with the same `hash` before and all
+    /// its outputs are still in `dir`.
+    pub fn is_current(&self, path: &Path, hash: u64, dir: &Path) -> bool {
+        self.files.get(path).is_some_and(|record| {
+            record.hash == hash && record.outputs.iter().all(|o| dir.join(o).exists())
+        })
+    }
+
+    /// The outputs `path` was recorded with, relative to the output
+    /// directory.
+    pub fn outputs(&self, path: &Path) -> Option<&[PathBuf]> {
+        self.files.get(path).map(|record| &record.outputs[..])
+    }
+
+    pub fn record(&mut self, path: &Path, hash: u64, outputs: Vec<PathBuf>) {
+        self.files
+            .insert(path.to_path_buf(), Record { hash, outputs });
+    }
+
+    /// Drops `path`, so it is extracted again the next time.
+    pub fn forget(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+}
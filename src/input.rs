@@ -0,0 +1,152 @@
+//! Input files for the scanner and the decoders.
+//!
+//! Files are normally read into memory. With the `mmap` feature, on Unix,
+//! large files can be mapped instead, so scanning a disk or memory dump of
+//! hundreds of megabytes only keeps the pages being looked at resident.
+//! Mapping is only sound while nothing else changes the file, which the
+//! caller has to promise, so [`Input::map`] is `unsafe`.
+
+use std::fs;
+use std::io::Error;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Files at least this large are mapped rather than read.
+pub const MAP_THRESHOLD: u64 = 1 << 20;
+
+/// The contents of an input file.
+pub struct Input {
+    data: Data,
+}
+
+enum Data {
+    Owned(Vec<u8>),
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(map::Map),
+}
+
+impl Input {
+    /// Reads `path` into memory.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Input, Error> {
+        Ok(Input::from(fs::read(path)?))
+    }
+
+    /// Opens `path`, mapping it if it is a regular file of at least
+    /// [`MAP_THRESHOLD`] bytes and the `mmap` feature is enabled, reading
+    /// it otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written to, by this process or
+    /// any other, until the returned `Input` is dropped. Reading a
+    /// truncated mapping raises `SIGBUS`, and a rewritten one changes
+    /// the bytes under the slices borrowed from it. Files that may be
+    /// edited meanwhile, like those being watched, must be read with
+    /// [`Input::read`].
+    pub unsafe fn map<P: AsRef<Path>>(path: P) -> Result<Input, Error> {
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            let file = fs::File::open(path.as_ref())?;
+            let metadata = file.metadata()?;
+            if metadata.is_file() && metadata.len() >= MAP_THRESHOLD {
+                let Ok(len) = usize::try_from(metadata.len()) else {
+                    return Input::read(path);
+                };
+                // SAFETY: passed on to the caller.
+                let map = unsafe { map::Map::new(&file, len)? };
+                return Ok(Input {
+                    data: Data::Mapped(map),
+                });
+            }
+        }
+
+        Input::read(path)
+    }
+
+    /// Whether the file is mapped rather than read.
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.data, Data::Owned(_))
+    }
+}
+
+impl From<Vec<u8>> for Input {
+    fn from(data: Vec<u8>) -> Input {
+        Input {
+            data: Data::Owned(data),
+        }
+    }
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.data {
+            Data::Owned(data) => data,
+            #[cfg(all(feature = "mmap", unix))]
+            Data::Mapped(map) => map.as_slice(),
+        }
+    }
+}
+
+impl AsRef<[u8]> for Input {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+mod map {
+    use std::fs::File;
+    use std::io::Error;
+    use std::os::fd::AsRawFd;
+    use std::ptr;
+
+    /// A read-only private mapping of the start of a file.
+    pub struct Map {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    impl Map {
+        /// Maps the first `len` bytes of `file`, which must not be 0.
+        ///
+        /// # Safety
+        ///
+        /// The file must not change while the mapping exists, see
+        /// [`Input::map`](super::Input::map).
+        pub unsafe fn new(file: &File, len: usize) -> Result<Map, Error> {
+            // SAFETY: a new mapping at an address of the system's choosing
+            // does not affect any existing memory.
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(Error::last_os_error());
+            }
+            Ok(Map { ptr, len })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: the mapping is `len` readable bytes until dropped,
+            // and the file does not change as promised to `new`.
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            // SAFETY: the mapping was made by `new` and is not used again.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! IPS patches between original and modified files, so modified assets can
+//! be shared without the game data they are based on.
+//!
+//! A patch is `PATCH`, a list of records and `EOF`, optionally followed by
+//! the size to truncate the output to. A record is a 24-bit big-endian
+//! offset and a 16-bit size followed by that many bytes, or a size of zero
+//! followed by a 16-bit count and the byte to repeat.
+
+use std::io::{Error, ErrorKind, Read};
+
+use crate::ReadBytesExt;
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+
+/// Offsets and sizes have to fit in 24 bits.
+pub const MAX_FILE_SIZE: usize = 1 << 24;
+
+const MAX_RECORD_SIZE: usize = 0xffff;
+
+/// A record can't start here, its offset would read as `EOF`.
+const EOF_OFFSET: usize = 0x454f46;
+
+/// Runs of equal bytes separating changes that are shorter than this are
+/// included in the record, which is cheaper than starting a new one.
+const MIN_GAP: usize = 6;
+
+/// Runs of a repeated byte at least this long become RLE records.
+const MIN_RLE: usize = 9;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn push_u24(out: &mut Vec<u8>, v: usize) {
+    out.extend_from_slice(&(v as u32).to_be_bytes()[1..]);
+}
+
+fn push_record(out: &mut Vec<u8>, offset: usize, data: &[u8]) {
+    let mut start = 0;
+    while start < data.len() {
+        let byte = data[start];
+        let run = data[start..].iter().take_while(|&&b| b == byte).count();
+
+        if run >= MIN_RLE {
+            let mut run = run.min(MAX_RECORD_SIZE);
+            if offset + start + run == EOF_OFFSET {
+                run -= 1;
+            }
+            push_u24(out, offset + start);
+            out.extend_from_slice(&0u16.to_be_bytes());
+            out.extend_from_slice(&(run as u16).to_be_bytes());
+            out.push(byte);
+            start += run;
+            continue;
+        }
+
+        // Plain bytes up to the next long run.
+        let mut end = start;
+        while end < data.len()
+            && end - start < MAX_RECORD_SIZE
+            && data[end..].iter().take_while(|&&b| b == data[end]).count() < MIN_RLE
+        {
+            end += 1;
+        }
+        if offset + end == EOF_OFFSET && end < data.len() {
+            if end - start < MAX_RECORD_SIZE {
+                end += 1;
+            } else {
+                end -= 1;
+            }
+        }
+        push_u24(out, offset + start);
+        out.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        out.extend_from_slice(&data[start..end]);
+        start = end;
+    }
+}
+
+/// Creates a patch turning `original` into `modified`.
+pub fn diff(original: &[u8], modified: &[u8]) -> Result<Vec<u8>, Error> {
+    if original.len() > MAX_FILE_SIZE || modified.len() > MAX_FILE_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "file too large for an IPS patch",
+        ));
+    }
+
+    let differs = |i: usize| original.get(i) != Some(&modified[i]);
+
+    let mut out = HEADER.to_vec();
+
+    let mut i = 0;
+    while i < modified.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+
+        let mut start = i;
+        let mut end = i + 1;
+        loop {
+            while end < modified.len() && differs(end) {
+                end += 1;
+            }
+            let gap = (end..modified.len())
+                .take(MIN_GAP)
+                .take_while(|&j| !differs(j))
+                .count();
+            if gap < MIN_GAP && end + gap < modified.len() {
+                end += gap;
+            } else {
+                break;
+            }
+        }
+
+        if start == EOF_OFFSET {
+            start -= 1;
+        }
+
+        push_record(&mut out, start, &modified[start..end]);
+        i = end;
+    }
+
+    out.extend_from_slice(FOOTER);
+    if modified.len() < original.len() {
+        push_u24(&mut out, modified.len());
+    }
+
+    Ok(out)
+}
+
+/// Applies a patch to `original`.
+pub fn apply(original: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut r = patch
+        .strip_prefix(HEADER)
+        .ok_or_else(|| invalid("not an IPS patch"))?;
+
+    let mut out = original.to_vec();
+
+    loop {
+        if let Some(rest) = r.strip_prefix(FOOTER) {
+            match rest.len() {
+                0 => {}
+                3 => out.truncate(u32::from_be_bytes([0, rest[0], rest[1], rest[2]]) as usize),
+                _ => return Err(invalid("trailing data after the end of the patch")),
+            }
+            return Ok(out);
+        }
+
+        let truncated = || invalid("patch is truncated");
+
+        let mut offset = [0; 3];
+        r.read_exact(&mut offset).map_err(|_| truncated())?;
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let size = r.read_be_u16().map_err(|_| truncated())? as usize;
+
+        let data = if size == 0 {
+            let count = r.read_be_u16().map_err(|_| truncated())? as usize;
+            let byte = r.read_u8().map_err(|_| truncated())?;
+            vec![byte; count]
+        } else {
+            let mut data = vec![0; size];
+            r.read_exact(&mut data).map_err(|_| truncated())?;
+            data
+        };
+
+        if out.len() < offset + data.len() {
+            out.resize(offset + data.len(), 0);
+        }
+        out[offset..offset + data.len()].copy_from_slice(&data);
+    }
+}
@@ -0,0 +1,66 @@
+pub mod analyze;
+#[cfg(feature = "archives")]
+pub mod archive;
+pub mod aseprite;
+pub mod cache;
+pub mod catalog;
+pub mod compare;
+pub mod config;
+pub mod debug;
+#[cfg(feature = "disk-images")]
+pub mod disk;
+pub mod ega;
+pub mod engine;
+pub mod exec;
+#[cfg(feature = "png")]
+pub mod extract;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+pub mod gallery;
+pub mod group;
+pub mod hercules;
+#[cfg(feature = "image")]
+pub mod image;
+pub mod incremental;
+pub mod input;
+pub mod ips;
+pub mod output;
+#[cfg(feature = "png")]
+pub mod pack;
+pub mod palette;
+pub mod progress;
+pub mod repair;
+pub mod scan;
+pub mod sprite;
+pub mod tiles;
+pub mod video;
+pub mod watch;
+
+pub trait ReadBytesExt: std::io::Read {
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8, std::io::Error> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[inline]
+    fn read_be_u16(&mut self) -> Result<u16, std::io::Error> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+impl<R: std::io::Read> ReadBytesExt for R {}
+
+pub trait WriteBytesExt: std::io::Write {
+    #[inline]
+    fn write_u8(&mut self, v: u8) -> Result<(), std::io::Error> {
+        let buf = v.to_le_bytes();
+        self.write_all(&buf)
+    }
+}
+
+impl<W: std::io::Write> WriteBytesExt for W {}
@@ -1,230 +1,1563 @@
+use std::cell::{Cell, RefCell};
 use std::fs::{self, File};
-use std::io::{BufWriter, Read};
-use std::path::Path;
-
-pub trait ReadBytesExt: std::io::Read {
-    #[inline]
-    fn read_u8(&mut self) -> Result<u8, std::io::Error> {
-        let mut buf = [0; 1];
-        self.read_exact(&mut buf)?;
-        Ok(buf[0])
-    }
-}
-
-impl<R: std::io::Read> ReadBytesExt for R {}
-
-pub trait WriteBytesExt: std::io::Write {
-    #[inline]
-    fn write_u8(&mut self, v: u8) -> Result<(), std::io::Error> {
-        let buf = v.to_le_bytes();
-        self.write_all(&buf)
-    }
-}
-
-impl<W: std::io::Write> WriteBytesExt for W {}
-
-// Based on https://int10h.org/blog/2022/06/ibm-5153-color-true-cga-palette/
-// Index 0 has been changed to transparent
-// Index 8 has been changed to black.
-const EGA_PAL: [[u8; 4]; 16] = [
-    [0x00, 0x00, 0x00, 0x00], //  0
-    [0x00, 0x00, 0xc4, 0xff], //  1
-    [0x00, 0xc4, 0x00, 0xff], //  2
-    [0x00, 0xc4, 0xc4, 0xff], //  3
-    [0xc4, 0x00, 0x00, 0xff], //  4
-    [0xc4, 0x00, 0xc4, 0xff], //  5
-    [0xc4, 0x7e, 0x00, 0xff], //  6
-    [0xc4, 0xc4, 0xc4, 0xff], //  7
-    [0x00, 0x00, 0x00, 0xff], //  8
-    [0x4e, 0x4e, 0xdc, 0xff], //  9
-    [0x4e, 0xdc, 0x4e, 0xff], // 10
-    [0x4e, 0xf3, 0xf3, 0xff], // 11
-    [0xdc, 0x4e, 0x4e, 0xff], // 12
-    [0xf3, 0x4e, 0xf3, 0xff], // 13
-    [0xf3, 0xf3, 0x4e, 0xff], // 14
-    [0xff, 0xff, 0xff, 0xff], // 15
-];
+use std::io::{self, ErrorKind, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 
-#[allow(clippy::erasing_op, clippy::identity_op)]
-fn decode_planar_ega_to_rgba(src: &[u8], width: usize, height: usize) -> Vec<u8> {
-    const PLANE_SIZE: usize = 8000;
+use exxos_kult_extract::analyze::{self, DuplicateFinder};
+#[cfg(feature = "archives")]
+use exxos_kult_extract::archive;
+use exxos_kult_extract::catalog::{self, Catalog};
+use exxos_kult_extract::compare;
+use exxos_kult_extract::config::{self, Config};
+use exxos_kult_extract::debug;
+#[cfg(feature = "disk-images")]
+use exxos_kult_extract::disk;
+use exxos_kult_extract::ega::{self, Monitor, Transform};
+use exxos_kult_extract::engine::{self, Engine};
+use exxos_kult_extract::exec::{ExecCommand, Executor};
+use exxos_kult_extract::extract::{
+    Event, ExtractOptions, Extractor, FrameSelection, GroupBy, NameTemplate,
+};
+use exxos_kult_extract::format::{FormatRegistry, Geometry};
+use exxos_kult_extract::gallery::{Gallery, GalleryImage};
+use exxos_kult_extract::group;
+use exxos_kult_extract::hercules::Phosphor;
+use exxos_kult_extract::incremental::{State, STATE_FILENAME};
+use exxos_kult_extract::input::Input;
+use exxos_kult_extract::ips;
+use exxos_kult_extract::output::{
+    downscale, output_stem, read_png_rgba, write_unscaled_rgba_to_png, Crt, Matte, OverwritePolicy,
+    PngCompression,
+};
+use exxos_kult_extract::pack;
+use exxos_kult_extract::progress::Progress;
+use exxos_kult_extract::scan::{self, CandidateKind};
+use exxos_kult_extract::tiles;
+use exxos_kult_extract::video::{Container, Video};
+use exxos_kult_extract::watch;
 
-    let mut frame = vec![0u8; width * height * 4];
+/// The exit status of a run with missing or invalid arguments.
+fn usage_error() -> ExitCode {
+    ExitCode::from(2)
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let ofs = width * y + x;
-            let bitofs = 7 - x % 8;
+fn print_usage() {
+    let name = std::env::args().next().unwrap_or_default();
+    println!("\nUsage: {name} [extract] [options] path/to/kult/*.ega");
+    println!("       {name} [options] --group <manifest.toml>");
+    println!("       {name} pack [--native] <manifest> <output file>");
+    println!("       {name} diff <original> <modified> <patch.ips>");
+    println!("       {name} patch <original> <patch.ips> <output file>");
+    println!("       {name} info [--catalog <catalog.toml>] path/to/kult/*.ega");
+    println!("       {name} list|analyze path/to/kult/*.ega");
+    println!("       {name} debug [--full] <file>");
+    println!("       {name} formats | --version");
+    println!("       {name} scan [--step N] <file>...");
+    println!(
+        "       {name} compare-screenshot [--monitor NAME] [--no-remap] <screenshot.png> <file> [<heatmap.png>]"
+    );
+    println!("       {name} watch [--interval MS] <path>... -- <command> [args]\n");
+    println!("Will create a folder called `png` in which the output images is placed.\n");
+    println!("The extracted PNGs will be scaled 5x in width and 6x in height.\n");
+    println!("Options:");
+    println!("  --output DIR                  Write the outputs to DIR instead of `png`");
+    println!("  --name-template TEMPLATE      Name frames like `{{name}}-{{frame}}`");
+    println!("  --group-by file|type|none     Put the outputs in a folder per file or per kind");
+    println!("  --engine godot|unity|generic  Also write an atlas with import metadata");
+    println!("  --size WxH                    Decode all files as WxH planar screens");
+    println!("  --dimensions WxH              Decode screens as WxH, ignoring their padding");
+    println!("  --tiles WxH                   Also split screens into a tileset and tilemap");
+    println!("  --repair                      Repair screens with sectors lost to disk errors");
+    println!("  --strict                      Fail files with inconsistent headers");
+    println!("  --hitboxes                    Decode frame trailers as hit boxes, unconfirmed");
+    println!(
+        "  --raw-planes                  Also dump the bitplanes, unscaled and without palette"
+    );
+    println!("  --matte rrggbb|checker        Show transparent pixels over a background");
+    println!(
+        "  --crt                         Give the scaled frames scanlines and a phosphor mask"
+    );
+    println!("  --crt-intensity N             Strength of the CRT look from 0 to 1, 1 by default");
+    println!("  --png-compression LEVEL       Compress PNGs fast, default or best");
+    println!("  --jobs N                      Encode N frames at once, one per core by default");
+    println!("  --monitor NAME                Show colors as on ibm5153, generic-ega or vga-dac");
+    println!("  --phosphor NAME               Show monochrome screens in green, amber or white");
+    println!("  --gamma G                     Brighten (G > 1) or darken (G < 1) the colors");
+    println!("  --transform NAME              Preview as grayscale, protanopia or deuteranopia");
+    println!("  --no-remap                    Ignore palette register tables of screens");
+    println!("  --dedupe                      Write identical frames of a sheet only once");
+    println!("  --dedupe-inputs               Extract identical files once, linking the others");
+    println!("  --mirror                      Also write horizontally mirrored frames, as -flip");
+    println!("  --svg                         Also write frames as SVGs of pixel rectangles");
+    println!("  --frame-diffs                 Also write the pixels changed from the frame before");
+    println!("  --aseprite                    Also write sheets as Aseprite documents");
+    println!("  --export-palette              Also write the palette as .gpl, .pal and .act");
+    println!("  --native                      Write frames unscaled, with the aspect in pHYs");
+    println!("  --crop X,Y,W,H                Only keep the given part of each frame");
+    println!("  --flip-h, --flip-v            Mirror the frames horizontally or vertically");
+    println!("  --rotate 90|180|270           Rotate the frames clockwise");
+    println!("  --frames LIST                 Only write the frames listed, e.g. 0,3,7-12");
+    println!("  --first, --last               Only write the first or last frame");
+    println!("  --catalog FILE                Add the file names and descriptions listed in FILE");
+    println!("  --html-gallery                Also write png/index.html showing all frames");
+    println!("  --thumbnails N                Also write thumbnails fitting NxN, as -thumb");
+    println!("  --exec CMD                    Run CMD on every file written, {{}} is its path");
+    println!("  --stdout                      Write a single PNG to stdout instead of files");
+    println!("  --video mp4|webm              With --group, also write videos using ffmpeg");
+    println!("  --fps N                       Frame rate of the videos, 10 by default");
+    println!("  --video-scale N               Scale the videos N times more than the PNGs");
+    println!("  --quiet                       Only print warnings and errors, no progress bar");
+    println!("  --verbose                     Also mention harmless oddities, like padding");
+    println!("  --force                       Overwrite existing output files");
+    println!("  --backup                      Rename existing output files to .bak first");
+    println!("  --incremental                 Only extract files changed since the last run");
+    println!(
+        "  --fail-fast                   Stop at the first file that can't be read or written"
+    );
+    println!("  --config FILE                 Read default options from FILE");
+    println!("  --no-FLAG                     Turn off a flag set in kult-extract.toml");
+    println!("  --no-config                   Ignore kult-extract.toml\n");
+    println!("A file name of `-` reads from stdin. With --stdout, a screen or the frames");
+    println!("of a sheet side by side are written to stdout as one PNG.\n");
+    println!("Existing output files are skipped unless --force or --backup is given.\n");
+    println!("With --incremental, the files extracted are recorded in the output directory");
+    println!("and skipped on later runs while they, the options and their outputs stay the");
+    println!("same. Files that changed replace their outputs.\n");
+    println!("Default options are read from kult-extract.toml in the current directory or");
+    println!("the XDG config directory, one `option = value` per line, e.g. `native = true`.");
+    println!("Options given on the command line take precedence.\n");
+    println!("A progress bar with the estimated time left is shown when stdout is a terminal.\n");
+    println!("With --engine, each sprite sheet is also packed into an atlas image with");
+    println!("import metadata for the chosen engine (Godot 4 .tres, Unity .meta or JSON).\n");
+    println!("With --group, the frames of the logical animations listed in the manifest,");
+    println!("e.g. `walk_left = [SPR01.EGA:0-7, SPR02.EGA:0-3]`, are merged into one atlas");
+    println!("and one animated GIF per animation instead.\n");
+    println!("`pack` builds a new sprite sheet from the PNGs listed in a manifest, one per");
+    println!("line in order, like `SPR07-01.png size=32x40 offset=4,0 hitbox=0,0,8,8`.");
+    println!("The PNGs are expected scaled 5x6 as extracted, or 1:1 with --native.\n");
+    println!("`diff` writes the changes between two files as an IPS patch, which `patch`");
+    println!("applies to a copy of the original.\n");
+    println!("`formats` lists the formats this build detects and how it tells them apart.\n");
+    println!("`info` shows the detected format of each file, `list` lists its frames.\n");
+    println!("Files found in the catalog are described by `info` and in the gallery, and");
+    println!("their outputs are named after the description as well.\n");
+    println!("`debug` prints an annotated hexdump of a file: its headers, frames and");
+    println!("sizes as the detected format lays them out, and why each format accepts or");
+    println!("rejects it. Long spans are shortened unless --full is given.\n");
+    println!("`analyze` reports the palette indices used by each frame, index 0 pixels");
+    println!("enclosed by other colors and duplicate frames.\n");
+    println!("`scan` looks for screens and sprite sheets at any offset of unknown files,");
+    println!("lists the most likely ones and writes previews to `png/scan`.\n");
+}
 
-            let p0 = (src[0 * PLANE_SIZE + ofs / 8] >> bitofs) & 1;
-            let p1 = (src[1 * PLANE_SIZE + ofs / 8] >> bitofs) & 1;
-            let p2 = (src[2 * PLANE_SIZE + ofs / 8] >> bitofs) & 1;
-            let p3 = (src[3 * PLANE_SIZE + ofs / 8] >> bitofs) & 1;
+/// Stands in for the filename of data read from stdin.
+const STDIN_NAME: &str = "stdin";
 
-            let v = (p3 << 3) | (p2 << 2) | (p1 << 1) | p0;
+fn read_stdin() -> Result<Vec<u8>, std::io::Error> {
+    let mut src = Vec::new();
+    io::stdin().lock().read_to_end(&mut src)?;
+    Ok(src)
+}
 
-            for c in 0..4 {
-                frame[4 * (y * width + x) + c] = EGA_PAL[v as usize][c];
-            }
+/// Reads a file, or stdin for `-`.
+fn read_file(filename: &str) -> Result<Vec<u8>, std::io::Error> {
+    if filename == "-" {
+        return read_stdin();
+    }
+
+    let mut src = Vec::new();
+    File::open(filename)?.read_to_end(&mut src)?;
+    Ok(src)
+}
+
+/// Whether large inputs may be mapped, which `watch` turns off as the
+/// files it watches are likely written while they are being read.
+static MAP_INPUTS: AtomicBool = AtomicBool::new(true);
+
+/// Like [`read_file`], but mapping large files with the `mmap` feature.
+fn open_input(filename: &str) -> Result<Input, std::io::Error> {
+    Ok(match filename {
+        "-" => Input::from(read_stdin()?),
+        // SAFETY: the tool never writes its inputs, and files changed by
+        // others while being scanned are documented as unsupported.
+        _ if MAP_INPUTS.load(Ordering::Relaxed) => unsafe { Input::map(filename)? },
+        _ => Input::read(filename)?,
+    })
+}
+
+fn pack_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let (native, args) = match args {
+        [flag, rest @ ..] if flag == "--native" => (true, rest),
+        _ => (false, args),
+    };
+    let [manifest_filename, output_filename] = args else {
+        print_usage();
+        return Ok(usage_error());
+    };
+
+    let manifest_path = Path::new(manifest_filename);
+    let frames = match pack::parse_manifest(&fs::read_to_string(manifest_path)?) {
+        Ok(frames) => frames,
+        Err(e) => {
+            println!("Invalid pack manifest, {e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+    let packed = match pack::pack(&frames, base_dir, native) {
+        Ok(packed) => packed,
+        Err(e) if matches!(e.kind(), ErrorKind::InvalidData | ErrorKind::NotFound) => {
+            println!("{e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+        Err(e) => return Err(e),
+    };
+
+    for warning in &packed.warnings {
+        println!("{warning}.");
+    }
+    println!(
+        "Packed {} frames into {} bytes",
+        frames.len(),
+        packed.data.len()
+    );
+
+    fs::write(output_filename, packed.data)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn version_command() -> Result<(), std::io::Error> {
+    println!(
+        "{} {} ({})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        env!("KULT_GIT_COMMIT")
+    );
+    println!(
+        "{} build for {}",
+        env!("KULT_BUILD_PROFILE"),
+        env!("KULT_BUILD_TARGET")
+    );
+
+    let features = [
+        cfg!(feature = "image").then_some("image"),
+        cfg!(feature = "archives").then_some("archives"),
+        cfg!(feature = "disk-images").then_some("disk-images"),
+        cfg!(feature = "adf").then_some("adf"),
+        cfg!(feature = "mmap").then_some("mmap"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    if !features.is_empty() {
+        println!("features: {}", features.join(", "));
+    }
+
+    Ok(())
+}
+
+fn formats_command() -> Result<(), std::io::Error> {
+    for format in FormatRegistry::default().formats() {
+        println!("{}: {}", format.name(), format.description());
+        let signature = format.signature();
+        if !signature.is_empty() {
+            println!("  {signature}");
         }
     }
 
-    frame
+    Ok(())
+}
+
+fn diff_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let [original_filename, modified_filename, patch_filename] = args else {
+        print_usage();
+        return Ok(usage_error());
+    };
+
+    let original = fs::read(original_filename)?;
+    let modified = fs::read(modified_filename)?;
+
+    let patch = match ips::diff(&original, &modified) {
+        Ok(patch) => patch,
+        Err(e) => {
+            println!("Cannot create a patch, {e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    println!("Wrote a {} byte patch", patch.len());
+
+    fs::write(patch_filename, patch)?;
+    Ok(ExitCode::SUCCESS)
 }
 
-fn decode_interleaved_ega_to_rgba(src: &[u8], span: usize, height: usize) -> Vec<u8> {
-    let width = 2 * span;
-    let mut frame = vec![0u8; width * height * 4];
+fn patch_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let [original_filename, patch_filename, output_filename] = args else {
+        print_usage();
+        return Ok(usage_error());
+    };
 
-    for y in 0..height {
-        for x in 0..width {
-            let ofs = y * span + x / 2;
-            let b = src[ofs];
-            let v = if x % 2 == 0 { b >> 4 } else { b & 0x0f };
+    let original = fs::read(original_filename)?;
+    let patch = fs::read(patch_filename)?;
 
-            for c in 0..4 {
-                frame[4 * (y * width + x) + c] = EGA_PAL[v as usize][c];
+    let patched = match ips::apply(&original, &patch) {
+        Ok(patched) => patched,
+        Err(e) => {
+            println!("Cannot apply patch, {e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    fs::write(output_filename, patched)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn compare_command(mut args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let mut options = ExtractOptions::new();
+    loop {
+        match args {
+            [flag, name, rest @ ..] if flag == "--monitor" => {
+                let Some(monitor) = Monitor::from_name(name) else {
+                    println!("--monitor requires ibm5153, generic-ega or vga-dac.");
+                    return Ok(usage_error());
+                };
+                options = options.monitor(monitor);
+                args = rest;
             }
+            [flag, rest @ ..] if flag == "--no-remap" => {
+                options = options.remap(false);
+                args = rest;
+            }
+            _ => break,
         }
     }
+    let (screenshot_filename, filename, heatmap_filename) = match args {
+        [screenshot, file] => (screenshot, file, None),
+        [screenshot, file, heatmap] => (screenshot, file, Some(heatmap)),
+        _ => {
+            print_usage();
+            return Ok(usage_error());
+        }
+    };
+
+    let (screenshot, screenshot_width, screenshot_height) = read_png_rgba(screenshot_filename)?;
+    let src = read_file(filename)?;
+    let mut extractor = Extractor::new(".").options(options);
+    let (image, width, height) = match extractor.extract_rgba(filename, &src) {
+        Ok(image) => image,
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            println!("{e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+        Err(e) => return Err(e),
+    };
 
-    frame
+    let comparison = match compare::compare(
+        &image,
+        width,
+        height,
+        &screenshot,
+        screenshot_width,
+        screenshot_height,
+    ) {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            println!("Cannot compare, {e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let (sx, sy) = comparison.scale;
+    println!("Comparing {width}x{height} pixels, scaled {sx}x{sy} in the screenshot.");
+    if let Some(heatmap_filename) = heatmap_filename {
+        write_unscaled_rgba_to_png(heatmap_filename, &comparison.heatmap, width, height)?;
+        println!("Wrote {heatmap_filename}");
+    }
+
+    let differences = &comparison.differences;
+    if differences.is_empty() {
+        println!("All pixels match.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    const SHOWN: usize = 20;
+    let largest = differences.iter().map(|d| d.amount()).max().unwrap_or(0);
+    println!(
+        "{} of {} pixels differ, by up to {largest}.",
+        differences.len(),
+        width * height
+    );
+    for d in differences.iter().take(SHOWN) {
+        let [r, g, b] = d.expected;
+        let [ar, ag, ab] = d.actual;
+        println!(
+            "  {},{}: expected {r:02x}{g:02x}{b:02x}, got {ar:02x}{ag:02x}{ab:02x}",
+            d.x, d.y
+        );
+    }
+    if differences.len() > SHOWN {
+        println!("  and {} more", differences.len() - SHOWN);
+    }
+    Ok(ExitCode::FAILURE)
 }
 
-fn write_rgba_to_png<P: AsRef<Path>>(
-    filename: P,
-    data: &[u8],
-    width: usize,
-    height: usize,
-) -> Result<(), std::io::Error> {
-    const SCALE_FACTOR_WIDTH: usize = 5;
-    const SCALE_FACTOR_HEIGHT: usize = 6;
+/// The built-in catalog with the entries of `filename` added, or None if
+/// they are invalid.
+fn load_catalog(filename: Option<&str>) -> Result<Option<Catalog>, std::io::Error> {
+    let mut catalog = Catalog::builtin();
+    if let Some(filename) = filename {
+        match Catalog::parse(&fs::read_to_string(filename)?) {
+            Ok(entries) => catalog.merge(entries),
+            Err(e) => {
+                println!("Invalid catalog, {e}.");
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(catalog))
+}
 
-    let file = File::create(filename)?;
-    let w = BufWriter::new(file);
+fn info_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let (catalog_filename, filenames) = match args {
+        [flag, filename, rest @ ..] if flag == "--catalog" => (Some(filename.as_str()), rest),
+        _ => (None, args),
+    };
 
-    let scaled_width = SCALE_FACTOR_WIDTH * width;
-    let scaled_height = SCALE_FACTOR_HEIGHT * height;
+    if filenames.is_empty() {
+        print_usage();
+        return Ok(usage_error());
+    }
 
-    let mut encoder = png::Encoder::new(w, scaled_width as u32, scaled_height as u32);
-    encoder.set_color(png::ColorType::Rgba);
-    encoder.set_depth(png::BitDepth::Eight);
+    let Some(catalog) = load_catalog(catalog_filename)? else {
+        return Ok(usage_error());
+    };
+    let registry = FormatRegistry::default();
 
-    let mut writer = encoder.write_header()?;
+    for filename in filenames {
+        let mut src = Vec::new();
+        File::open(filename)?.read_to_end(&mut src)?;
 
-    let scaled_size = 5 * width * 6 * height;
-    let mut scaled_data = vec![0; 4 * scaled_size];
+        println!("{filename}: {} bytes", src.len());
 
-    for y in 0..height {
-        for dy in 0..SCALE_FACTOR_HEIGHT {
-            for x in 0..width {
-                let ofs = y * width + x;
-                for dx in 0..SCALE_FACTOR_WIDTH {
-                    let sy = SCALE_FACTOR_HEIGHT * y + dy;
-                    let sx = SCALE_FACTOR_WIDTH * x + dx;
+        let basename = Path::new(filename).file_name().unwrap_or_default();
+        if let Some(description) = catalog.lookup(&basename.to_string_lossy(), &src) {
+            println!("  {description}");
+        }
+        if let Some((width, height)) = catalog.size(&basename.to_string_lossy(), &src) {
+            println!("  size: {width}x{height}, padded");
+        }
+        println!("  hash: fnv:{:016x}", catalog::content_hash(&src));
 
-                    let scaled_ofs = sy * scaled_width + sx;
+        match registry.extract(&src) {
+            Ok((format, extracted)) => {
+                println!("  format: {} ({})", format.name(), format.description());
+                println!("  frames: {}", extracted.frames.len());
+                if let Some(remap) = extracted.remap {
+                    let registers = remap.map(|v| v.to_string());
+                    println!("  palette registers: {}", registers.join(" "));
+                }
+                if !extracted.warnings.is_empty() {
+                    println!("  warnings: {}", extracted.warnings.len());
+                }
+            }
+            Err(e) => println!("  {e}."),
+        }
+    }
 
-                    for c in 0..4 {
-                        scaled_data[4 * scaled_ofs + c] = data[4 * ofs + c];
-                    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn debug_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let (full, filename) = match args {
+        [flag, filename] if flag == "--full" => (true, filename),
+        [filename] => (false, filename),
+        _ => {
+            print_usage();
+            return Ok(usage_error());
+        }
+    };
+
+    let src = read_file(filename)?;
+    println!("{filename}: {} bytes", src.len());
+
+    let annotated = debug::annotate(&src, &FormatRegistry::default());
+    let color = io::stdout().is_terminal();
+    annotated.write_hexdump(&mut io::stdout().lock(), &src, color, full)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn list_command(filenames: &[String]) -> Result<ExitCode, std::io::Error> {
+    if filenames.is_empty() {
+        print_usage();
+        return Ok(usage_error());
+    }
+
+    let registry = FormatRegistry::default();
+
+    for filename in filenames {
+        let src = read_file(filename)?;
+
+        #[cfg(feature = "archives")]
+        if archive::detect(&src).is_some() {
+            let members = match archive::members(&src) {
+                Ok(members) => members,
+                Err(e) => {
+                    println!("{filename}\n  {e}.");
+                    continue;
+                }
+            };
+            for member in members {
+                println!("{filename}/{}", member.name);
+                match member.data {
+                    Ok(src) => list_frames(&registry, &src),
+                    Err(e) => println!("  {e}."),
+                }
+            }
+            continue;
+        }
+
+        #[cfg(feature = "disk-images")]
+        if disk::detect(&src).is_some() {
+            let files = match disk::files(&src) {
+                Ok(files) => files,
+                Err(e) => {
+                    println!("{filename}\n  {e}.");
+                    continue;
+                }
+            };
+            for file in files {
+                println!("{filename}/{}", file.name);
+                match file.data {
+                    Ok(src) => list_frames(&registry, &src),
+                    Err(e) => println!("  {e}."),
                 }
             }
+            continue;
         }
+
+        println!("{filename}");
+        list_frames(&registry, &src);
     }
 
-    writer.write_image_data(&scaled_data)?;
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+fn list_frames(registry: &FormatRegistry, src: &[u8]) {
+    match registry.extract(src) {
+        Ok((_, extracted)) => {
+            for message in extracted.warnings.iter().chain(&extracted.notes) {
+                println!("  {message}");
+            }
+            for frame in &extracted.frames {
+                print!("  {:02}  {}x{}", frame.index, frame.width, frame.height);
+                match frame.hitboxes.len() {
+                    0 => println!(),
+                    n => println!("  {n} hit boxes"),
+                }
+            }
+        }
+        Err(e) => println!("  {e}."),
+    }
 }
 
-fn extract_fullscreen_ega<P: AsRef<Path>>(
-    src: Vec<u8>,
-    input_filename: P,
-) -> Result<(), std::io::Error> {
-    let width = 320;
-    let height = 200;
+fn analyze_command(filenames: &[String]) -> Result<ExitCode, std::io::Error> {
+    if filenames.is_empty() {
+        print_usage();
+        return Ok(usage_error());
+    }
 
-    let frame_rgb = decode_planar_ega_to_rgba(&src, width, height);
+    let registry = FormatRegistry::default();
+    let mut duplicates = DuplicateFinder::new();
 
-    let filename = input_filename.as_ref();
-    let output_filename = format!(
-        "png/{}.png",
-        filename.file_stem().unwrap().to_str().unwrap(),
-    );
+    for filename in filenames {
+        println!("{filename}");
 
-    write_rgba_to_png(output_filename, &frame_rgb, width, height)?;
+        let src = read_file(filename)?;
+        let frames = match registry.extract(&src) {
+            Ok((_, extracted)) => extracted.frames,
+            Err(e) => {
+                println!("  {e}.");
+                continue;
+            }
+        };
 
-    Ok(())
+        for frame in &frames {
+            print!(
+                "  frame {:02}  {}x{}",
+                frame.index, frame.width, frame.height
+            );
+
+            if let Some((file, index)) = duplicates.check(frame, (filename.clone(), frame.index)) {
+                println!("  duplicate of {file} frame {index:02}");
+                continue;
+            }
+
+            let stats = analyze::frame_stats(frame);
+
+            let used = stats
+                .histogram
+                .iter()
+                .enumerate()
+                .filter(|(_, &n)| n > 0)
+                .map(|(v, n)| format!("{v}:{n}"))
+                .collect::<Vec<_>>();
+            println!("  indices {}", used.join(" "));
+
+            if stats.interior_zero > 0 {
+                println!(
+                    "    index 0 in {} interior pixels, may not be transparent",
+                    stats.interior_zero
+                );
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn extract_sprites_ega<P: AsRef<Path>>(
-    src: Vec<u8>,
-    input_filename: P,
-) -> Result<(), std::io::Error> {
-    if src.len() < 4 {
-        println!("Not a valid sprite sheet, file too small.");
-        return Ok(());
+/// Largest side of the previews `scan` writes of its candidates.
+const SCAN_PREVIEW_SIZE: usize = 160;
+
+fn scan_command(args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let (step, filenames) = match args {
+        [flag, step, rest @ ..] if flag == "--step" => match step.parse::<usize>() {
+            Ok(step) if step > 0 => (step, rest),
+            _ => {
+                println!("--step requires a positive number.");
+                return Ok(usage_error());
+            }
+        },
+        _ => (16, args),
+    };
+
+    if filenames.is_empty() {
+        print_usage();
+        return Ok(usage_error());
     }
 
-    let size = u32::from_be_bytes(src[0..4].try_into().unwrap()) as usize;
+    let preview_dir = Path::new("png").join("scan");
+    fs::create_dir_all(&preview_dir)?;
 
-    if size + 4 != src.len() {
-        println!("Not a valid sprite sheet, size in header incorrect.");
-        return Ok(());
+    for filename in filenames {
+        println!("{filename}");
+
+        let src = open_input(filename)?;
+        let stem = output_stem(Path::new(filename));
+
+        let candidates = scan::scan(&src, step);
+        if candidates.is_empty() {
+            println!("  nothing found");
+        }
+
+        for c in &candidates {
+            let what = match c.kind {
+                CandidateKind::Screen { width, height } => format!("screen {width}x{height}"),
+                CandidateKind::Sheet { frames } => format!("sprites, {frames} frames"),
+            };
+
+            let atlas = engine::build_atlas(&scan::decode(&src, c), &ega::EGA_PAL);
+            let (thumbnail, width, height) =
+                downscale(&atlas.data, atlas.width, atlas.height, SCAN_PREVIEW_SIZE);
+            let preview = preview_dir.join(format!("{stem}-{:08x}.png", c.offset));
+            write_unscaled_rgba_to_png(&preview, &thumbnail, width, height)?;
+
+            println!(
+                "  0x{:08x}  {what}  score {:.2}  {}",
+                c.offset,
+                c.score,
+                preview.display()
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+struct Options {
+    output: String,
+    name_template: Option<NameTemplate>,
+    group_by: Option<GroupBy>,
+    engine: Option<Engine>,
+    overwrite: OverwritePolicy,
+    size: Option<(usize, usize)>,
+    dimensions: Option<(usize, usize)>,
+    tiles: Option<(usize, usize)>,
+    repair: bool,
+    strict: bool,
+    hitboxes: bool,
+    raw_planes: bool,
+    remap: bool,
+    matte: Option<Matte>,
+    crt: Option<Crt>,
+    compression: PngCompression,
+    jobs: usize,
+    monitor: Monitor,
+    phosphor: Phosphor,
+    gamma: f64,
+    transform: Option<Transform>,
+    dedupe: bool,
+    dedupe_inputs: bool,
+    export_palette: bool,
+    native: bool,
+    mirror: bool,
+    svg: bool,
+    thumbnails: Option<usize>,
+    frame_diffs: bool,
+    aseprite: bool,
+    exec: Option<ExecCommand>,
+    html_gallery: bool,
+    frames: Option<FrameSelection>,
+    geometry: Geometry,
+    catalog: Option<String>,
+    stdout: bool,
+    incremental: bool,
+    fail_fast: bool,
+    quiet: bool,
+    verbose: bool,
+    video: Option<Container>,
+    fps: u32,
+    video_scale: usize,
+}
+
+fn parse_crop(s: &str) -> Option<(usize, usize, usize, usize)> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    match values[..] {
+        [x, y, w, h] if w > 0 && h > 0 => Some((x, y, w, h)),
+        _ => None,
     }
+}
 
-    let mut src = &src[4..];
+fn group_command(manifest_filename: &str, options: &Options) -> Result<ExitCode, std::io::Error> {
+    let manifest_path = Path::new(manifest_filename);
 
-    let mut n = 0;
-    while !src.is_empty() {
-        let input_size = u16::from_le_bytes(src[0..2].try_into().unwrap()) as usize;
-        let byte_width = 2 * src[2] as usize;
-        let height = src[3] as usize;
+    let groups = match group::parse_manifest(&fs::read_to_string(manifest_path)?) {
+        Ok(groups) => groups,
+        Err(e) => {
+            println!("Invalid group manifest, {e}.");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
 
-        let width = 2 * byte_width;
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
 
-        let frame_rgb = decode_interleaved_ega_to_rgba(&src[4..], byte_width, height);
+    let output_dir = Path::new(&options.output);
+    fs::create_dir_all(output_dir)?;
 
-        let filename = input_filename.as_ref();
-        let output_filename = format!(
-            "png/{}-{:02}.png",
-            filename.file_stem().unwrap().to_str().unwrap(),
-            n
-        );
-        n += 1;
+    let video = options.video.map(|container| Video {
+        container,
+        fps: options.fps,
+        scale: options.video_scale,
+    });
 
-        write_rgba_to_png(&output_filename, &frame_rgb, width, height)?;
+    let mut failed = 0;
+    for g in &groups {
+        println!("Merging {}", g.name);
 
-        src = &src[input_size..];
+        let engine = options.engine.unwrap_or(Engine::Generic);
+        match group::write_group(
+            g,
+            base_dir,
+            output_dir,
+            engine,
+            video.as_ref(),
+            options.overwrite,
+        ) {
+            Ok(output) => {
+                for path in &output.skipped {
+                    println!("Skipping existing {}", path.display());
+                }
+                println!("Wrote {} frames", output.frames);
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::InvalidData | ErrorKind::NotFound) => {
+                println!("{e}.");
+                failed += 1;
+            }
+            Err(e) => return Err(e),
+        }
     }
 
-    Ok(())
+    if failed > 0 {
+        println!("{failed} of {} groups failed.", groups.len());
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() -> Result<(), std::io::Error> {
-    if std::env::args().len() <= 1 {
-        let name = std::env::args().next().unwrap_or_default();
-        println!("\nUsage: {name} path/to/kult/*.ega\n");
-        println!("Will create a folder called `png` in which the output images is placed.\n");
-        println!("The extracted PNGs will be scaled 5x in width and 6x in height.\n");
-        return Ok(());
+/// Options of the extract command a config file can set. The others only
+/// make sense for a single run.
+const CONFIG_OPTIONS: [&str; 23] = [
+    "output",
+    "name-template",
+    "group-by",
+    "rotate",
+    "crop",
+    "engine",
+    "size",
+    "dimensions",
+    "tiles",
+    "matte",
+    "crt-intensity",
+    "png-compression",
+    "jobs",
+    "monitor",
+    "phosphor",
+    "gamma",
+    "transform",
+    "catalog",
+    "thumbnails",
+    "exec",
+    "video",
+    "fps",
+    "video-scale",
+];
+
+const CONFIG_FLAGS: [&str; 23] = [
+    "raw-planes",
+    "flip-h",
+    "flip-v",
+    "no-remap",
+    "repair",
+    "strict",
+    "hitboxes",
+    "dedupe",
+    "dedupe-inputs",
+    "export-palette",
+    "native",
+    "mirror",
+    "svg",
+    "frame-diffs",
+    "aseprite",
+    "crt",
+    "html-gallery",
+    "quiet",
+    "verbose",
+    "force",
+    "backup",
+    "incremental",
+    "fail-fast",
+];
+
+/// Puts the options of the config file, `--config FILE` or the one
+/// [`config::find`] finds, ahead of `args`. Returns `None` if the config
+/// file is invalid.
+fn with_config(args: Vec<String>) -> Result<Option<Vec<String>>, std::io::Error> {
+    let mut filename = None;
+    let mut use_config = true;
+    let mut rest = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let Some(f) = args.next() else {
+                    println!("--config requires an argument.");
+                    return Ok(None);
+                };
+                filename = Some(PathBuf::from(f));
+            }
+            "--no-config" => use_config = false,
+            _ => rest.push(arg),
+        }
     }
 
-    fs::create_dir_all("png")?;
+    // `--no-<flag>` turns off a flag the config file sets.
+    let mut turned_off = Vec::new();
+    rest.retain(|arg| {
+        let flag = arg
+            .strip_prefix("--")
+            .and_then(|name| CONFIG_FLAGS.iter().find(|f| config::negation(f) == name));
+        if let Some(flag) = flag {
+            turned_off.push(format!("--{flag}"));
+        }
+        flag.is_none()
+    });
 
-    for filename in std::env::args().skip(1) {
-        println!("Extracting {}", filename);
+    let Some(path) = filename.or_else(config::find).filter(|_| use_config) else {
+        return Ok(Some(rest));
+    };
 
-        let mut src = Vec::new();
-        File::open(&filename)?.read_to_end(&mut src)?;
+    let config_args = match Config::parse(&fs::read_to_string(&path)?)
+        .and_then(|config| config.to_args(&CONFIG_OPTIONS, &CONFIG_FLAGS))
+    {
+        Ok(config_args) => config_args,
+        Err(e) => {
+            println!("Invalid config file {}, {e}.", path.display());
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(
+        config_args
+            .into_iter()
+            .filter(|arg| !turned_off.contains(arg))
+            .chain(rest)
+            .collect(),
+    ))
+}
+
+fn extract_command(args: Vec<String>) -> Result<ExitCode, std::io::Error> {
+    let Some(args) = with_config(args)? else {
+        return Ok(usage_error());
+    };
+
+    let mut options = Options {
+        output: "png".to_string(),
+        name_template: None,
+        group_by: None,
+        engine: None,
+        overwrite: OverwritePolicy::Skip,
+        size: None,
+        dimensions: None,
+        tiles: None,
+        repair: false,
+        strict: false,
+        hitboxes: false,
+        raw_planes: false,
+        remap: true,
+        matte: None,
+        crt: None,
+        compression: PngCompression::default(),
+        jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        monitor: Monitor::default(),
+        phosphor: Phosphor::default(),
+        gamma: 1.0,
+        transform: None,
+        dedupe: false,
+        dedupe_inputs: false,
+        export_palette: false,
+        native: false,
+        mirror: false,
+        svg: false,
+        thumbnails: None,
+        frame_diffs: false,
+        aseprite: false,
+        exec: None,
+        html_gallery: false,
+        frames: None,
+        geometry: Geometry::default(),
+        catalog: None,
+        stdout: false,
+        incremental: false,
+        fail_fast: false,
+        quiet: false,
+        verbose: false,
+        video: None,
+        fps: Video::default().fps,
+        video_scale: Video::default().scale,
+    };
+    let mut manifest = None;
+    let mut filenames = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => {
+                let Some(dir) = args.next() else {
+                    println!("--output requires an argument.");
+                    return Ok(usage_error());
+                };
+                options.output = dir;
+            }
+            "--name-template" => {
+                let Some(template) = args.next().as_deref().and_then(NameTemplate::parse) else {
+                    println!("--name-template requires a template using {{name}} and {{frame}}.");
+                    return Ok(usage_error());
+                };
+                options.name_template = Some(template);
+            }
+            "--engine" => {
+                let Some(name) = args.next() else {
+                    println!("--engine requires an argument.");
+                    return Ok(usage_error());
+                };
+                let Some(e) = Engine::from_name(&name) else {
+                    println!("Unknown engine `{name}`, expected godot, unity or generic.");
+                    return Ok(usage_error());
+                };
+                options.engine = Some(e);
+            }
+            "--size" => {
+                let Some(size) = args.next().as_deref().and_then(ega::parse_size) else {
+                    println!("--size requires a WxH argument, with W a multiple of 8.");
+                    return Ok(usage_error());
+                };
+                options.size = Some(size);
+            }
+            "--dimensions" => {
+                let Some(size) = args.next().as_deref().and_then(ega::parse_size) else {
+                    println!("--dimensions requires a WxH argument, with W a multiple of 8.");
+                    return Ok(usage_error());
+                };
+                options.dimensions = Some(size);
+            }
+            "--tiles" => {
+                let Some(size) = args.next().as_deref().and_then(tiles::parse_tile_size) else {
+                    println!("--tiles requires a WxH argument.");
+                    return Ok(usage_error());
+                };
+                options.tiles = Some(size);
+            }
+            "--raw-planes" => options.raw_planes = true,
+            "--no-remap" => options.remap = false,
+            "--dedupe" => options.dedupe = true,
+            "--dedupe-inputs" => options.dedupe_inputs = true,
+            "--export-palette" => options.export_palette = true,
+            "--native" => options.native = true,
+            "--mirror" => options.mirror = true,
+            "--flip-h" => options.geometry.flip_horizontally = true,
+            "--flip-v" => options.geometry.flip_vertically = true,
+            "--rotate" => {
+                let turns = match args.next().as_deref() {
+                    Some("0") => 0,
+                    Some("90") => 1,
+                    Some("180") => 2,
+                    Some("270") => 3,
+                    _ => {
+                        println!("--rotate requires 90, 180 or 270.");
+                        return Ok(usage_error());
+                    }
+                };
+                options.geometry.rotate = turns;
+            }
+            "--crop" => {
+                let Some(crop) = args.next().as_deref().and_then(parse_crop) else {
+                    println!("--crop requires x,y,width,height, with a positive size.");
+                    return Ok(usage_error());
+                };
+                options.geometry.crop = Some(crop);
+            }
+            "--svg" => options.svg = true,
+            "--repair" => options.repair = true,
+            "--strict" => options.strict = true,
+            "--hitboxes" => options.hitboxes = true,
+            "--frame-diffs" => options.frame_diffs = true,
+            "--aseprite" => options.aseprite = true,
+            "--html-gallery" => options.html_gallery = true,
+            "--exec" => {
+                let Some(command) = args.next().as_deref().and_then(ExecCommand::parse) else {
+                    println!("--exec requires a command, like 'oxipng {{}}'.");
+                    return Ok(usage_error());
+                };
+                options.exec = Some(command);
+            }
+            "--thumbnails" => {
+                let Some(size) = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0) else {
+                    println!("--thumbnails requires a positive number.");
+                    return Ok(usage_error());
+                };
+                options.thumbnails = Some(size);
+            }
+            "--catalog" => {
+                let Some(filename) = args.next() else {
+                    println!("--catalog requires an argument.");
+                    return Ok(usage_error());
+                };
+                options.catalog = Some(filename);
+            }
+            "--frames" => {
+                let selection = options.frames.get_or_insert_with(FrameSelection::default);
+                if !args.next().is_some_and(|list| selection.add_list(&list)) {
+                    println!("--frames requires a list of frames, like 0,3,7-12.");
+                    return Ok(usage_error());
+                }
+            }
+            "--first" => options
+                .frames
+                .get_or_insert_with(FrameSelection::default)
+                .add_first(),
+            "--last" => options
+                .frames
+                .get_or_insert_with(FrameSelection::default)
+                .add_last(),
+            "--stdout" => options.stdout = true,
+            "--incremental" => options.incremental = true,
+            "--fail-fast" => options.fail_fast = true,
+            "--quiet" => options.quiet = true,
+            "--verbose" => options.verbose = true,
+            "--video" => {
+                let Some(container) = args.next().as_deref().and_then(Container::from_name) else {
+                    println!("--video requires mp4 or webm.");
+                    return Ok(usage_error());
+                };
+                options.video = Some(container);
+            }
+            "--fps" => {
+                let Some(fps) = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0) else {
+                    println!("--fps requires a positive number.");
+                    return Ok(usage_error());
+                };
+                options.fps = fps;
+            }
+            "--video-scale" => {
+                let Some(scale) = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0)
+                else {
+                    println!("--video-scale requires a positive number.");
+                    return Ok(usage_error());
+                };
+                options.video_scale = scale;
+            }
+            "--monitor" => {
+                let Some(monitor) = args.next().as_deref().and_then(Monitor::from_name) else {
+                    println!("--monitor requires ibm5153, generic-ega or vga-dac.");
+                    return Ok(usage_error());
+                };
+                options.monitor = monitor;
+            }
+            "--phosphor" => {
+                let Some(phosphor) = args.next().as_deref().and_then(Phosphor::from_name) else {
+                    println!("--phosphor requires green, amber or white.");
+                    return Ok(usage_error());
+                };
+                options.phosphor = phosphor;
+            }
+            "--gamma" => {
+                let Some(gamma) = args.next().and_then(|g| g.parse::<f64>().ok()) else {
+                    println!("--gamma requires a number.");
+                    return Ok(usage_error());
+                };
+                if gamma <= 0.0 || !gamma.is_finite() {
+                    println!("--gamma requires a positive number.");
+                    return Ok(usage_error());
+                }
+                options.gamma = gamma;
+            }
+            "--transform" => {
+                let Some(transform) = args.next().as_deref().and_then(Transform::from_name) else {
+                    println!("--transform requires grayscale, protanopia or deuteranopia.");
+                    return Ok(usage_error());
+                };
+                options.transform = Some(transform);
+            }
+            "--crt" => {
+                options.crt.get_or_insert_with(Crt::default);
+            }
+            "--crt-intensity" => {
+                let Some(intensity) = args
+                    .next()
+                    .and_then(|n| n.parse::<f64>().ok())
+                    .filter(|n| (0.0..=1.0).contains(n))
+                else {
+                    println!("--crt-intensity requires a number from 0 to 1.");
+                    return Ok(usage_error());
+                };
+                options.crt = Some(Crt { intensity });
+            }
+            "--group-by" => {
+                options.group_by = match args.next().as_deref() {
+                    Some("none") => None,
+                    name => match name.and_then(GroupBy::from_name) {
+                        Some(group_by) => Some(group_by),
+                        None => {
+                            println!("--group-by requires file, type or none.");
+                            return Ok(usage_error());
+                        }
+                    },
+                };
+            }
+            "--png-compression" => {
+                let Some(compression) = args.next().as_deref().and_then(PngCompression::from_name)
+                else {
+                    println!("--png-compression requires fast, default or best.");
+                    return Ok(usage_error());
+                };
+                options.compression = compression;
+            }
+            "--jobs" => {
+                let Some(jobs) = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0) else {
+                    println!("--jobs requires a positive number.");
+                    return Ok(usage_error());
+                };
+                options.jobs = jobs;
+            }
+            "--matte" => {
+                let Some(matte) = args.next().as_deref().and_then(Matte::parse) else {
+                    println!("--matte requires a rrggbb color or `checker`.");
+                    return Ok(usage_error());
+                };
+                options.matte = Some(matte);
+            }
+            "--force" => options.overwrite = OverwritePolicy::Force,
+            "--backup" => options.overwrite = OverwritePolicy::Backup,
+            "--group" => {
+                let Some(filename) = args.next() else {
+                    println!("--group requires an argument.");
+                    return Ok(usage_error());
+                };
+                manifest = Some(filename);
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                println!("Unknown option `{arg}`.");
+                return Ok(usage_error());
+            }
+            _ => filenames.push(arg),
+        }
+    }
+
+    if let Some(manifest) = manifest {
+        if !filenames.is_empty() {
+            print_usage();
+            return Ok(usage_error());
+        }
+        return group_command(&manifest, &options);
+    }
+
+    if options.video.is_some() {
+        println!("Videos are written for the animations of a --group manifest.");
+        return Ok(usage_error());
+    }
+
+    if filenames.is_empty() {
+        print_usage();
+        return Ok(usage_error());
+    }
+
+    if options.stdout && filenames.len() != 1 {
+        println!("--stdout takes a single input file.");
+        return Ok(usage_error());
+    }
+
+    if options.crt.is_some() && options.native {
+        println!("--crt draws scanlines between the rows of scaled frames, not with --native.");
+        return Ok(usage_error());
+    }
+
+    if options.stdout && options.incremental {
+        println!("--incremental records the files written to the output directory, not --stdout.");
+        return Ok(usage_error());
+    }
 
-        if src.len() == 32000 {
-            extract_fullscreen_ega(src, filename)?;
+    if options.stdout && options.html_gallery {
+        println!("--html-gallery shows the files written to the output directory, not --stdout.");
+        return Ok(usage_error());
+    }
+    if options.stdout && options.exec.is_some() {
+        println!("--exec runs on the files written to the output directory, not --stdout.");
+        return Ok(usage_error());
+    }
+
+    // Sizes of the input files for estimating the time left, stdin is
+    // not known up front.
+    let total_bytes = filenames
+        .iter()
+        .filter(|f| *f != "-")
+        .filter_map(|f| fs::metadata(f).ok())
+        .map(|m| m.len())
+        .sum();
+    let mut progress = Progress::new(filenames.len(), total_bytes);
+    if options.quiet || options.stdout {
+        progress = progress.disabled();
+    }
+    let progress = RefCell::new(progress);
+
+    let gallery = RefCell::new(Gallery::new());
+    let executor = RefCell::new(
+        options
+            .exec
+            .clone()
+            .map(|command| Executor::new(command, options.jobs)),
+    );
+    let frame_size = Cell::new((0, 0));
+    let unchanged = Cell::new(0);
+
+    // With --stdout, the PNG goes to stdout and the messages to stderr.
+    let stdout = options.stdout;
+    let quiet = options.quiet;
+    let verbose = options.verbose;
+    let report = |message: String| {
+        if stdout {
+            eprintln!("{message}");
         } else {
-            extract_sprites_ega(src, filename)?;
+            progress.borrow_mut().message(&message);
         }
+    };
+
+    let Some(catalog) = load_catalog(options.catalog.as_deref())? else {
+        return Ok(usage_error());
+    };
+
+    let output_dir = Path::new(&options.output);
+    if !stdout {
+        fs::create_dir_all(output_dir)?;
     }
 
-    Ok(())
+    let state = if options.incremental {
+        match State::load(output_dir) {
+            Ok(state) => Some(state),
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                println!("Invalid {STATE_FILENAME}, extracting everything again ({e}).");
+                Some(State::default())
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        None
+    };
+
+    let extract_options = ExtractOptions::new()
+        .engine(options.engine)
+        .overwrite(options.overwrite)
+        .size(options.size)
+        .dimensions(options.dimensions)
+        .tiles(options.tiles)
+        .repair(options.repair)
+        .strict(options.strict)
+        .hitboxes(options.hitboxes)
+        .raw_planes(options.raw_planes)
+        .remap(options.remap)
+        .matte(options.matte)
+        .crt(options.crt)
+        .png_compression(options.compression)
+        .jobs(options.jobs)
+        .monitor(options.monitor)
+        .phosphor(options.phosphor)
+        .gamma(options.gamma)
+        .transform(options.transform)
+        .dedupe(options.dedupe)
+        .dedupe_inputs(options.dedupe_inputs)
+        .export_palette(options.export_palette)
+        .native(options.native)
+        .mirror(options.mirror)
+        .svg(options.svg)
+        .thumbnails(options.thumbnails)
+        .frame_diffs(options.frame_diffs)
+        .aseprite(options.aseprite)
+        .frames(options.frames.clone())
+        .geometry(options.geometry)
+        .name_template(options.name_template.clone())
+        .group_by(options.group_by);
+
+    let mut extractor = Extractor::new(output_dir)
+        .options(extract_options)
+        .incremental(state)
+        .catalog(catalog)
+        .on_event(|event| {
+            match event {
+                Event::FileStarted { path } => {
+                    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                    progress
+                        .borrow_mut()
+                        .start_file(&path.display().to_string(), size);
+                    gallery
+                        .borrow_mut()
+                        .start_section(&path.display().to_string());
+                }
+                Event::Identified { description } => {
+                    gallery.borrow_mut().set_description(description)
+                }
+                Event::FrameDecoded { width, height, .. } => {
+                    progress.borrow_mut().frame();
+                    frame_size.set((*width, *height));
+                }
+                Event::FrameWritten { index, path } => {
+                    let (width, height) = frame_size.get();
+                    let href = path.strip_prefix(output_dir).unwrap_or(path);
+                    gallery.borrow_mut().add_image(GalleryImage {
+                        index: *index,
+                        width,
+                        height,
+                        href: href.to_string_lossy().replace('\\', "/"),
+                        thumbnail: None,
+                    });
+                }
+                Event::OutputWritten { path } => {
+                    if let Some(executor) = executor.borrow_mut().as_mut() {
+                        executor.run(path);
+                    }
+                }
+                Event::ThumbnailWritten { path, .. } => {
+                    let href = path.strip_prefix(output_dir).unwrap_or(path);
+                    gallery
+                        .borrow_mut()
+                        .set_thumbnail(&href.to_string_lossy().replace('\\', "/"));
+                }
+                Event::FileFinished { .. } | Event::FileFailed { .. } => {
+                    progress.borrow_mut().finish_file()
+                }
+                Event::FileUnchanged { path } => {
+                    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                    let mut progress = progress.borrow_mut();
+                    progress.start_file(&path.display().to_string(), size);
+                    progress.finish_file();
+                    unchanged.set(unchanged.get() + 1);
+                }
+                _ => {}
+            }
+
+            let message = match event {
+                Event::FileStarted { path } if !quiet => format!("Extracting {}", path.display()),
+                Event::OutputSkipped { path } if !quiet => {
+                    format!("Skipping existing {}", path.display())
+                }
+                Event::FileDuplicate { of, .. } if !quiet => {
+                    format!("Same as {}, linking its outputs", of.display())
+                }
+                Event::Warning { message } => message.clone(),
+                Event::Note { message } if verbose => message.clone(),
+                Event::FileUnchanged { path } if verbose => {
+                    format!("Skipping unchanged {}", path.display())
+                }
+                Event::FrameDuplicate { index, of } if !quiet => {
+                    format!("Frame {index} is a duplicate of frame {of}, skipping.")
+                }
+                Event::FileFailed { error, .. } if error.kind() == ErrorKind::InvalidData => {
+                    format!("{error}.")
+                }
+                _ => return,
+            };
+            report(message);
+        });
+
+    let mut updated = 0;
+    let mut failures = Vec::new();
+    for filename in &filenames {
+        let unchanged_before = unchanged.get();
+        let result = match (filename.as_str(), stdout) {
+            ("-", false) => read_stdin().and_then(|src| extractor.extract_data(STDIN_NAME, &src)),
+            ("-", true) => read_stdin()
+                .and_then(|src| extractor.extract_png(STDIN_NAME, &src, io::stdout().lock())),
+            (_, false) => extractor.extract_file(filename),
+            (_, true) => fs::read(filename)
+                .and_then(|src| extractor.extract_png(filename, &src, io::stdout().lock())),
+        };
+
+        match result {
+            Err(e) if options.fail_fast && e.kind() != ErrorKind::InvalidData => {
+                progress.borrow_mut().finish();
+                if let Some(state) = extractor.take_state() {
+                    state.save(output_dir)?;
+                }
+                return Err(e);
+            }
+            Err(e) => failures.push((filename, e)),
+            Ok(()) if unchanged.get() == unchanged_before => updated += 1,
+            Ok(()) => {}
+        }
+    }
+
+    progress.borrow_mut().finish();
+    if let Some(state) = extractor.take_state() {
+        state.save(output_dir)?;
+        if !quiet {
+            println!(
+                "Files updated: {updated}, unchanged: {}, failed: {}.",
+                unchanged.get(),
+                failures.len()
+            );
+        }
+    }
+    drop(extractor);
+
+    if let Some(executor) = executor.into_inner() {
+        for failure in executor.finish() {
+            println!(
+                "--exec failed on {}, {}.",
+                failure.path.display(),
+                failure.reason
+            );
+        }
+    }
+
+    if options.html_gallery {
+        let index = output_dir.join("index.html");
+        if options.overwrite.prepare(&index)? {
+            gallery.into_inner().write_to_file(&index)?;
+            println!("Wrote {}", index.display());
+        } else {
+            println!("Skipping existing {}", index.display());
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!(
+        "Extracted {} files, {} failed:",
+        filenames.len() - failures.len(),
+        failures.len()
+    );
+    for (filename, error) in failures {
+        println!("  {filename}: {error}");
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+/// Polls the watched paths until something changed, reruns the command
+/// and waits again.
+fn watch_command(mut args: &[String]) -> Result<ExitCode, std::io::Error> {
+    let mut interval = Duration::from_millis(500);
+    if let [flag, ms, rest @ ..] = args {
+        if flag == "--interval" {
+            let Some(ms) = ms.parse().ok().filter(|&ms| ms > 0) else {
+                println!("--interval requires a number of milliseconds.");
+                return Ok(usage_error());
+            };
+            interval = Duration::from_millis(ms);
+            args = rest;
+        }
+    }
+    let Some(separator) = args.iter().position(|a| a == "--") else {
+        print_usage();
+        return Ok(usage_error());
+    };
+    let (paths, command) = (&args[..separator], &args[separator + 1..]);
+    if paths.is_empty() || command.is_empty() || command[0] == "watch" {
+        print_usage();
+        return Ok(usage_error());
+    }
+
+    MAP_INPUTS.store(false, Ordering::Relaxed);
+    let run_command = || match run(command.to_vec()) {
+        Ok(code) if code == ExitCode::SUCCESS => println!("Done."),
+        Ok(_) => println!("Done, with errors."),
+        Err(e) => println!("Failed: {e}"),
+    };
+
+    run_command();
+    let mut snapshot = watch::Snapshot::take(paths)?;
+    println!("Watching {} files, press Ctrl-C to stop.", snapshot.len());
+    loop {
+        let changes;
+        (snapshot, changes) = watch::wait_for_changes(paths, &snapshot, interval)?;
+        if changes.is_empty() {
+            continue;
+        }
+        for path in &changes {
+            println!("Changed {}", path.display());
+        }
+        run_command();
+        // Whatever the command wrote to the watched paths is not a change.
+        snapshot = watch::Snapshot::take(paths)?;
+    }
+}
+
+fn run(args: Vec<String>) -> Result<ExitCode, std::io::Error> {
+    let result = match args.first().map(String::as_str) {
+        Some("--version" | "version") => version_command(),
+        Some("formats") => formats_command(),
+        Some("pack") => return pack_command(&args[1..]),
+        Some("diff") => return diff_command(&args[1..]),
+        Some("patch") => return patch_command(&args[1..]),
+        Some("analyze") => return analyze_command(&args[1..]),
+        Some("info") => return info_command(&args[1..]),
+        Some("list") => return list_command(&args[1..]),
+        Some("debug") => return debug_command(&args[1..]),
+        Some("scan") => return scan_command(&args[1..]),
+        Some("compare-screenshot") => return compare_command(&args[1..]),
+        Some("watch") => return watch_command(&args[1..]),
+        Some("extract") => return extract_command(args[1..].to_vec()),
+        _ => return extract_command(args),
+    };
+    result.map(|()| ExitCode::SUCCESS)
+}
+
+fn main() -> Result<ExitCode, std::io::Error> {
+    run(std::env::args().skip(1).collect())
 }
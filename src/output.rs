@@ -0,0 +1,721 @@
+use std::fs::{self, File};
+#[cfg(feature = "png")]
+use std::io::BufReader;
+#[cfg(any(feature = "png", feature = "gif"))]
+use std::io::ErrorKind;
+use std::io::{BufWriter, Error, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "gif")]
+use crate::ega::EGA_PAL;
+
+pub const SCALE_FACTOR_WIDTH: usize = 5;
+pub const SCALE_FACTOR_HEIGHT: usize = 6;
+
+/// What to do when an output file already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Leave the existing file alone and skip the output.
+    #[default]
+    Skip,
+    /// Overwrite the existing file.
+    Force,
+    /// Rename the existing file to `<name>.bak` first, or `<name>.bak.N`
+    /// with the lowest free N if there is a backup already.
+    Backup,
+}
+
+impl OverwritePolicy {
+    /// Prepares `path` for writing. Returns false if the output should be
+    /// skipped.
+    pub fn prepare(self, path: &Path) -> Result<bool, Error> {
+        if !path.exists() {
+            return Ok(true);
+        }
+
+        match self {
+            OverwritePolicy::Skip => Ok(false),
+            OverwritePolicy::Force => Ok(true),
+            OverwritePolicy::Backup => {
+                fs::rename(path, backup_path(path))?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Background composited under transparent pixels, for previews.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Matte {
+    Color([u8; 3]),
+    /// Light and dark gray squares of `CHECKER_SIZE` pixels.
+    Checker,
+}
+
+pub const CHECKER_SIZE: usize = 4;
+
+impl Matte {
+    /// Parses `checker` or a `rrggbb` hex color, optionally prefixed by `#`.
+    pub fn parse(s: &str) -> Option<Matte> {
+        if s == "checker" {
+            return Some(Matte::Checker);
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let c = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Matte::Color([c(0)?, c(2)?, c(4)?]))
+    }
+
+    fn color_at(self, x: usize, y: usize) -> [u8; 3] {
+        match self {
+            Matte::Color(c) => c,
+            Matte::Checker if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) => [0xcc; 3],
+            Matte::Checker => [0x99; 3],
+        }
+    }
+
+    /// Composites RGBA data over the matte, leaving it opaque.
+    pub fn apply(self, data: &mut [u8], width: usize) {
+        for (i, px) in data.chunks_mut(4).enumerate() {
+            let bg = self.color_at(i % width, i / width);
+            let a = px[3] as u32;
+            for c in 0..3 {
+                px[c] = ((px[c] as u32 * a + bg[c] as u32 * (255 - a)) / 255) as u8;
+            }
+            px[3] = 0xff;
+        }
+    }
+}
+
+/// A CRT look for scaled frames: darker scanlines between the rows of
+/// source pixels, an aperture grille phosphor mask and a slight horizontal
+/// blur.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crt {
+    /// Strength of the effects, from 0 for none to 1.
+    pub intensity: f64,
+}
+
+/// How much each of the rows making up a source row is darkened, the last
+/// ones forming the gap between scanlines.
+const SCANLINE_DARKENING: [f64; SCALE_FACTOR_HEIGHT] = [0.0, 0.0, 0.0, 0.0, 0.25, 0.5];
+
+/// How much the two channels a mask stripe does not pass are darkened.
+const MASK_DARKENING: f64 = 0.25;
+
+/// How far pixels are blended towards their horizontal neighbours.
+const BLUR: f64 = 0.5;
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt { intensity: 1.0 }
+    }
+}
+
+impl Crt {
+    /// Applies the effects to RGBA data scaled by the output scale factors.
+    /// The blur weighs colors by their alpha, so transparent pixels don't
+    /// darken the edges.
+    pub fn apply(self, data: &mut [u8], width: usize) {
+        let k = self.intensity.clamp(0.0, 1.0);
+        if width == 0 || k == 0.0 {
+            return;
+        }
+
+        let src = data.to_vec();
+        for (i, px) in data.chunks_mut(4).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let row = &src[4 * y * width..4 * (y + 1) * width];
+
+            let taps = [
+                (x.saturating_sub(1), 1.0),
+                (x, 2.0),
+                ((x + 1).min(width - 1), 1.0),
+            ];
+            let mut color = [0.0; 3];
+            let mut alpha = 0.0;
+            for (tx, weight) in taps {
+                let a = row[4 * tx + 3] as f64 * weight;
+                for (c, v) in color.iter_mut().enumerate() {
+                    *v += row[4 * tx + c] as f64 * a;
+                }
+                alpha += a;
+            }
+
+            let scanline = 1.0 - k * SCANLINE_DARKENING[y % SCALE_FACTOR_HEIGHT];
+            for (c, v) in color.iter().enumerate() {
+                let own = px[c] as f64;
+                let blurred = if alpha > 0.0 { v / alpha } else { own };
+                let mask = if x % 3 == c {
+                    1.0
+                } else {
+                    1.0 - k * MASK_DARKENING
+                };
+                let value = (own + k * BLUR * (blurred - own)) * mask * scanline;
+                px[c] = value.round().clamp(0.0, 255.0) as u8;
+            }
+            let own = px[3] as f64;
+            px[3] = (own + k * BLUR * (alpha / 4.0 - own)).round() as u8;
+        }
+    }
+}
+
+/// How hard to compress PNGs, trading file size for encoding time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngCompression {
+    /// Fast compression without row filters, for quick previews.
+    Fast,
+    /// The `png` crate's defaults.
+    #[default]
+    Default,
+    /// The best compression, trying every row filter.
+    Best,
+}
+
+impl PngCompression {
+    pub fn from_name(name: &str) -> Option<PngCompression> {
+        match name {
+            "fast" => Some(PngCompression::Fast),
+            "default" => Some(PngCompression::Default),
+            "best" => Some(PngCompression::Best),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "png")]
+    fn configure<W: Write>(self, encoder: &mut png::Encoder<W>) {
+        match self {
+            PngCompression::Fast => {
+                encoder.set_compression(png::Compression::Fast);
+                encoder.set_filter(png::FilterType::NoFilter);
+            }
+            PngCompression::Default => {}
+            PngCompression::Best => {
+                encoder.set_compression(png::Compression::Best);
+                encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+            }
+        }
+    }
+}
+
+/// Turns the path of an input file into the stem its outputs are named
+/// after, the file name without extension. Anything that does not make a
+/// portable file name is percent-encoded, so different names never end up
+/// the same: bytes that are not UTF-8, path separators, control
+/// characters, `%` itself and trailing dots and spaces, which Windows
+/// drops. Paths without a file name, like `..`, give `unnamed`.
+pub fn output_stem(path: &Path) -> String {
+    let Some(stem) = path.file_stem().filter(|s| !s.is_empty()) else {
+        return "unnamed".to_string();
+    };
+
+    let mut out = String::new();
+    for chunk in stem.as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '%' || c == '/' || c == '\\' || c.is_control() {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{b:02X}"));
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+
+    let kept = out.trim_end_matches(['.', ' ']).len();
+    let trailing = out.split_off(kept);
+    for b in trailing.bytes() {
+        out.push_str(&format!("%{b:02X}"));
+    }
+    out
+}
+
+/// The name to back up `path` to, which earlier backups don't have.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    let first = path.with_file_name(&name);
+    if !first.exists() {
+        return first;
+    }
+
+    (1..)
+        .map(|n| {
+            let mut numbered = name.clone();
+            numbered.push(format!(".{n}"));
+            path.with_file_name(numbered)
+        })
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// Scales `bpp` bytes per pixel image data by the output scale factors.
+pub fn scale_pixels(data: &[u8], bpp: usize, width: usize, height: usize) -> Vec<u8> {
+    let scaled_width = SCALE_FACTOR_WIDTH * width;
+    let scaled_height = SCALE_FACTOR_HEIGHT * height;
+
+    let mut scaled_data = vec![0; bpp * scaled_width * scaled_height];
+
+    for y in 0..height {
+        for dy in 0..SCALE_FACTOR_HEIGHT {
+            for x in 0..width {
+                let ofs = y * width + x;
+                for dx in 0..SCALE_FACTOR_WIDTH {
+                    let sy = SCALE_FACTOR_HEIGHT * y + dy;
+                    let sx = SCALE_FACTOR_WIDTH * x + dx;
+
+                    let scaled_ofs = sy * scaled_width + sx;
+
+                    for c in 0..bpp {
+                        scaled_data[bpp * scaled_ofs + c] = data[bpp * ofs + c];
+                    }
+                }
+            }
+        }
+    }
+
+    scaled_data
+}
+
+/// For each of `to` pixels along an axis scaled down from `from` pixels,
+/// the input pixels it covers and by how much.
+fn coverage(from: usize, to: usize) -> Vec<Vec<(usize, f64)>> {
+    let step = from as f64 / to as f64;
+    (0..to)
+        .map(|i| {
+            let (start, end) = (i as f64 * step, (i + 1) as f64 * step);
+            (start.floor() as usize..(end.ceil() as usize).min(from))
+                .map(|j| (j, end.min(j as f64 + 1.0) - start.max(j as f64)))
+                .filter(|&(_, weight)| weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// Shrinks RGBA data to fit `size` pixels on either side, keeping the
+/// aspect, by averaging the area each output pixel covers. Colors are
+/// weighed by their alpha, so transparent pixels don't darken the edges.
+/// Images that already fit are returned as they are.
+pub fn downscale(data: &[u8], width: usize, height: usize, size: usize) -> (Vec<u8>, usize, usize) {
+    if width.max(height) <= size || size == 0 {
+        return (data.to_vec(), width, height);
+    }
+
+    let scale = size as f64 / width.max(height) as f64;
+    let out_width = ((width as f64 * scale).round() as usize).max(1);
+    let out_height = ((height as f64 * scale).round() as usize).max(1);
+    let columns = coverage(width, out_width);
+    let rows = coverage(height, out_height);
+
+    let mut out = Vec::with_capacity(4 * out_width * out_height);
+    for row in &rows {
+        for column in &columns {
+            // Colors premultiplied by alpha, then alpha.
+            let mut sum = [0.0; 4];
+            let mut area = 0.0;
+            for &(y, wy) in row {
+                for &(x, wx) in column {
+                    let px = &data[4 * (y * width + x)..4 * (y * width + x) + 4];
+                    let alpha = px[3] as f64 * wy * wx;
+                    for (s, &c) in sum.iter_mut().zip(&px[..3]) {
+                        *s += c as f64 * alpha;
+                    }
+                    sum[3] += alpha;
+                    area += wy * wx;
+                }
+            }
+
+            let alpha = sum[3];
+            for s in &sum[..3] {
+                out.push(if alpha > 0.0 {
+                    (s / alpha).round() as u8
+                } else {
+                    0
+                });
+            }
+            out.push((alpha / area).round() as u8);
+        }
+    }
+
+    (out, out_width, out_height)
+}
+
+#[cfg(feature = "png")]
+pub fn write_rgba_to_png<P: AsRef<Path>>(
+    filename: P,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), std::io::Error> {
+    let file = File::create(filename)?;
+    write_rgba_png(BufWriter::new(file), data, width, height)
+}
+
+/// Like [`write_rgba_to_png`], writing to `w`.
+#[cfg(feature = "png")]
+pub fn write_rgba_png<W: Write>(
+    w: W,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), std::io::Error> {
+    encode_png(
+        w,
+        data,
+        width,
+        height,
+        false,
+        None,
+        PngCompression::default(),
+        &[],
+    )
+}
+
+/// Like [`write_rgba_to_png`], but writes the pixels 1:1 and records their
+/// aspect in the pHYs chunk instead of scaling them.
+#[cfg(feature = "png")]
+pub fn write_native_rgba_to_png<P: AsRef<Path>>(
+    filename: P,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), std::io::Error> {
+    let file = File::create(filename)?;
+    write_native_rgba_png(BufWriter::new(file), data, width, height)
+}
+
+/// Like [`write_native_rgba_to_png`], writing to `w`.
+#[cfg(feature = "png")]
+pub fn write_native_rgba_png<W: Write>(
+    w: W,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), std::io::Error> {
+    encode_png(
+        w,
+        data,
+        width,
+        height,
+        true,
+        None,
+        PngCompression::default(),
+        &[],
+    )
+}
+
+/// Writes a frame like [`write_rgba_to_png`], or like
+/// [`write_native_rgba_to_png`] if `native`, with `text` as text chunks
+/// of keyword and text. The CRT look only applies to scaled frames.
+#[cfg(feature = "png")]
+#[allow(clippy::too_many_arguments)]
+pub fn write_frame_to_png<P: AsRef<Path>>(
+    filename: P,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    native: bool,
+    crt: Option<Crt>,
+    compression: PngCompression,
+    text: &[(String, String)],
+) -> Result<(), std::io::Error> {
+    let file = File::create(filename)?;
+    write_frame_png(
+        BufWriter::new(file),
+        data,
+        width,
+        height,
+        native,
+        crt,
+        compression,
+        text,
+    )
+}
+
+/// Like [`write_frame_to_png`], to a writer.
+#[cfg(feature = "png")]
+#[allow(clippy::too_many_arguments)]
+pub fn write_frame_png<W: Write>(
+    w: W,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    native: bool,
+    crt: Option<Crt>,
+    compression: PngCompression,
+    text: &[(String, String)],
+) -> Result<(), std::io::Error> {
+    encode_png(w, data, width, height, native, crt, compression, text)
+}
+
+#[cfg(feature = "png")]
+#[allow(clippy::too_many_arguments)]
+fn encode_png<W: Write>(
+    w: W,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    native: bool,
+    crt: Option<Crt>,
+    compression: PngCompression,
+    text: &[(String, String)],
+) -> Result<(), std::io::Error> {
+    let (out_width, out_height) = if native {
+        (width, height)
+    } else {
+        (SCALE_FACTOR_WIDTH * width, SCALE_FACTOR_HEIGHT * height)
+    };
+
+    let mut encoder = png::Encoder::new(w, out_width as u32, out_height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    compression.configure(&mut encoder);
+
+    if native {
+        // A pixel is as wide as SCALE_FACTOR_WIDTH and as high as
+        // SCALE_FACTOR_HEIGHT, so fewer of them fit across a unit.
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: SCALE_FACTOR_HEIGHT as u32,
+            yppu: SCALE_FACTOR_WIDTH as u32,
+            unit: png::Unit::Unspecified,
+        }));
+        encoder.add_text_chunk(
+            "Comment".to_string(),
+            format!(
+                "Unscaled, display with a {SCALE_FACTOR_WIDTH}:{SCALE_FACTOR_HEIGHT} pixel aspect ratio"
+            ),
+        )?;
+    }
+
+    // tEXt only holds Latin-1, anything else goes into iTXt as UTF-8.
+    for (keyword, value) in text {
+        if value.chars().all(|c| (c as u32) < 0x100) {
+            encoder.add_text_chunk(keyword.clone(), value.clone())?;
+        } else {
+            encoder.add_itxt_chunk(keyword.clone(), value.clone())?;
+        }
+    }
+
+    let mut writer = encoder.write_header()?;
+    if native {
+        writer.write_image_data(data)?;
+    } else {
+        let mut scaled = scale_pixels(data, 4, width, height);
+        if let Some(crt) = crt {
+            crt.apply(&mut scaled, out_width);
+        }
+        writer.write_image_data(&scaled)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a PNG as RGBA, whatever its color type.
+#[cfg(feature = "png")]
+pub fn read_png_rgba<P: AsRef<Path>>(path: P) -> Result<(Vec<u8>, usize, usize), Error> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    buf.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xff])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 0xff]).collect(),
+        png::ColorType::Indexed => unreachable!("expanded by the decoder"),
+    };
+    Ok((rgba, info.width as usize, info.height as usize))
+}
+
+/// Writes RGBA data as is, for previews.
+#[cfg(feature = "png")]
+pub fn write_unscaled_rgba_to_png<P: AsRef<Path>>(
+    filename: P,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), std::io::Error> {
+    let file = File::create(filename)?;
+    let w = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+
+    Ok(())
+}
+
+/// Writes unscaled grayscale data packed at `bits` per pixel, most
+/// significant bits first, as is. For looking at data that does not decode.
+#[cfg(feature = "png")]
+pub fn write_raw_to_png<P: AsRef<Path>>(
+    filename: P,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bits: u8,
+) -> Result<(), std::io::Error> {
+    let depth = match bits {
+        1 => png::BitDepth::One,
+        4 => png::BitDepth::Four,
+        8 => png::BitDepth::Eight,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported bit depth {bits}"),
+            ))
+        }
+    };
+
+    let file = File::create(filename)?;
+    let w = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(depth);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+
+    Ok(())
+}
+
+/// Writes indexed pixels as an SVG of rectangles, one per run of equal
+/// pixels in a row, grouped by color. Transparent pixels are left out.
+/// With `aspect`, the image is sized for the 5:6 pixel aspect ratio,
+/// otherwise pixels are square.
+pub fn write_indexed_to_svg<P: AsRef<Path>>(
+    filename: P,
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    pal: &[[u8; 4]; 16],
+    aspect: bool,
+) -> Result<(), std::io::Error> {
+    let mut w = BufWriter::new(File::create(filename)?);
+    write_indexed_svg(&mut w, pixels, width, height, pal, aspect)?;
+    w.flush()
+}
+
+/// Like [`write_indexed_to_svg`], writing to `w`.
+pub fn write_indexed_svg<W: Write>(
+    mut w: W,
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    pal: &[[u8; 4]; 16],
+    aspect: bool,
+) -> Result<(), std::io::Error> {
+    let (outer_width, outer_height) = if aspect {
+        (SCALE_FACTOR_WIDTH * width, SCALE_FACTOR_HEIGHT * height)
+    } else {
+        (width, height)
+    };
+
+    // Runs of each palette index as (x, y, length).
+    let mut runs: [Vec<(usize, usize, usize)>; 16] = Default::default();
+    for (y, row) in pixels.chunks(width.max(1)).enumerate() {
+        let mut x = 0;
+        for run in row.chunk_by(|a, b| a == b) {
+            runs[run[0] as usize & 0x0f].push((x, y, run.len()));
+            x += run.len();
+        }
+    }
+
+    writeln!(
+        w,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{outer_width}\" height=\"{outer_height}\" viewBox=\"0 0 {width} {height}\" preserveAspectRatio=\"none\" shape-rendering=\"crispEdges\">"
+    )?;
+    for (index, runs) in runs.iter().enumerate() {
+        let [r, g, b, a] = pal[index];
+        if a == 0 || runs.is_empty() {
+            continue;
+        }
+        writeln!(w, "<g fill=\"#{r:02x}{g:02x}{b:02x}\">")?;
+        for (x, y, len) in runs {
+            writeln!(
+                w,
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{len}\" height=\"1\"/>"
+            )?;
+        }
+        writeln!(w, "</g>")?;
+    }
+    writeln!(w, "</svg>")?;
+
+    Ok(())
+}
+
+/// Writes indexed frames as an animated GIF using the EGA palette, with
+/// index 0 transparent. Frames may differ in size, the canvas is as large
+/// as the largest frame and each frame is placed in the top left corner.
+#[cfg(feature = "gif")]
+pub fn write_indexed_to_gif<P: AsRef<Path>>(
+    filename: P,
+    frames: &[(Vec<u8>, usize, usize)],
+    delay: u16,
+) -> Result<(), std::io::Error> {
+    let width = frames.iter().map(|(_, w, _)| *w).max().unwrap_or(0);
+    let height = frames.iter().map(|(_, _, h)| *h).max().unwrap_or(0);
+
+    let scaled_width = SCALE_FACTOR_WIDTH * width;
+    let scaled_height = SCALE_FACTOR_HEIGHT * height;
+
+    if scaled_width > u16::MAX as usize || scaled_height > u16::MAX as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "image too large for GIF",
+        ));
+    }
+
+    let palette = EGA_PAL
+        .iter()
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect::<Vec<_>>();
+
+    let file = File::create(filename)?;
+    let w = BufWriter::new(file);
+
+    let mut encoder = gif::Encoder::new(w, scaled_width as u16, scaled_height as u16, &palette)
+        .map_err(Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(Error::other)?;
+
+    for (data, w, h) in frames {
+        let frame = gif::Frame {
+            width: (SCALE_FACTOR_WIDTH * w) as u16,
+            height: (SCALE_FACTOR_HEIGHT * h) as u16,
+            buffer: scale_pixels(data, 1, *w, *h).into(),
+            transparent: Some(0),
+            delay,
+            dispose: gif::DisposalMethod::Background,
+            ..Default::default()
+        };
+        encoder.write_frame(&frame).map_err(Error::other)?;
+    }
+
+    Ok(())
+}
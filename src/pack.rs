@@ -0,0 +1,215 @@
+//! Builds new sprite sheets from PNGs, for adding, removing and reordering
+//! frames.
+//!
+//! A pack manifest lists the frames of the sheet in order, one PNG per
+//! line, optionally followed by the size of the frame, where the image
+//! goes within it and the frame's hit boxes:
+//!
+//! ```text
+//! # Raven walking
+//! SPR07-00.png
+//! SPR07-01.png size=32x40 offset=4,0
+//! new-frame.png hitbox=0,0,8,8 hitbox=8,8,8,8
+//! ```
+//!
+//! Without a size, the frame is the size of the image. Parts of the image
+//! outside the frame are cut off. Colors are mapped to the nearest
+//! palette index, and transparent pixels to index 0.
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::ega::EGA_PAL;
+use crate::format::IndexedFrame;
+use crate::output::{read_png_rgba, SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH};
+use crate::sprite::{self, HitBox};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackFrame {
+    pub image: PathBuf,
+    /// Size of the frame, the size of the image if `None`.
+    pub size: Option<(usize, usize)>,
+    /// Where the top left corner of the image goes in the frame.
+    pub offset: (usize, usize),
+    pub hitboxes: Vec<HitBox>,
+}
+
+/// A packed sprite sheet, with what did not go cleanly.
+pub struct Packed {
+    pub data: Vec<u8>,
+    pub warnings: Vec<String>,
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {line}: {msg}"))
+}
+
+fn parse_numbers<const N: usize>(s: &str, separator: char) -> Option<[usize; N]> {
+    let values = s
+        .split(separator)
+        .map(|v| v.parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    values.try_into().ok()
+}
+
+pub fn parse_manifest(text: &str) -> Result<Vec<PackFrame>, Error> {
+    let mut frames = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(ofs) => &line[..ofs],
+            None => line,
+        };
+        let mut words = line.split_whitespace();
+        let Some(image) = words.next() else {
+            continue;
+        };
+
+        let mut frame = PackFrame {
+            image: PathBuf::from(image),
+            size: None,
+            offset: (0, 0),
+            hitboxes: Vec::new(),
+        };
+
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                return Err(invalid(
+                    n + 1,
+                    &format!("expected `key=value`, got `{word}`"),
+                ));
+            };
+            match key {
+                "size" => match parse_numbers(value, 'x') {
+                    Some([w, h]) if w > 0 && h > 0 => frame.size = Some((w, h)),
+                    _ => return Err(invalid(n + 1, "size requires WxH")),
+                },
+                "offset" => match parse_numbers(value, ',') {
+                    Some([x, y]) => frame.offset = (x, y),
+                    None => return Err(invalid(n + 1, "offset requires X,Y")),
+                },
+                "hitbox" => match parse_numbers(value, ',') {
+                    Some([x, y, w, h]) => frame.hitboxes.push(HitBox { x, y, w, h }),
+                    None => return Err(invalid(n + 1, "hitbox requires X,Y,W,H")),
+                },
+                _ => return Err(invalid(n + 1, &format!("unknown key `{key}`"))),
+            }
+        }
+
+        frames.push(frame);
+    }
+
+    if frames.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "no frames listed"));
+    }
+
+    Ok(frames)
+}
+
+/// Undoes the 5x6 scaling of extracted frames, if every block is a
+/// single color.
+fn unscale(rgba: &[u8], width: usize, height: usize) -> Option<(Vec<u8>, usize, usize)> {
+    if !width.is_multiple_of(SCALE_FACTOR_WIDTH) || !height.is_multiple_of(SCALE_FACTOR_HEIGHT) {
+        return None;
+    }
+
+    let (w, h) = (width / SCALE_FACTOR_WIDTH, height / SCALE_FACTOR_HEIGHT);
+    let pixel = |x: usize, y: usize| &rgba[4 * (y * width + x)..4 * (y * width + x) + 4];
+
+    let mut out = Vec::with_capacity(4 * w * h);
+    for y in 0..h {
+        for x in 0..w {
+            let first = pixel(x * SCALE_FACTOR_WIDTH, y * SCALE_FACTOR_HEIGHT);
+            for by in 0..SCALE_FACTOR_HEIGHT {
+                for bx in 0..SCALE_FACTOR_WIDTH {
+                    let p = pixel(x * SCALE_FACTOR_WIDTH + bx, y * SCALE_FACTOR_HEIGHT + by);
+                    if p != first {
+                        return None;
+                    }
+                }
+            }
+            out.extend_from_slice(first);
+        }
+    }
+    Some((out, w, h))
+}
+
+/// The palette index closest to a color, and whether it is exact. Index 0
+/// is transparent and only used for transparent pixels, opaque black is
+/// index 8.
+fn nearest_index(pixel: &[u8]) -> (u8, bool) {
+    if pixel[3] == 0 {
+        return (0, true);
+    }
+
+    let distance = |c: &[u8; 4]| {
+        (0..3)
+            .map(|i| (c[i] as i32 - pixel[i] as i32).pow(2))
+            .sum::<i32>()
+    };
+    let (index, color) = EGA_PAL
+        .iter()
+        .enumerate()
+        .skip(1)
+        .min_by_key(|(_, c)| distance(c))
+        .unwrap();
+    (index as u8, distance(color) == 0)
+}
+
+/// Builds a sprite sheet of the frames listed, with the images relative
+/// to `base_dir`. Images are expected to be scaled 5x6 as extracted,
+/// unless `native` is set.
+pub fn pack(frames: &[PackFrame], base_dir: &Path, native: bool) -> Result<Packed, Error> {
+    let mut indexed = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (index, frame) in frames.iter().enumerate() {
+        let path = base_dir.join(&frame.image);
+        let (rgba, width, height) = read_png_rgba(&path)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        let (rgba, width, height) = if native {
+            (rgba, width, height)
+        } else {
+            unscale(&rgba, width, height).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} is not scaled {SCALE_FACTOR_WIDTH}x{SCALE_FACTOR_HEIGHT}, \
+                         use --native for unscaled images",
+                        path.display()
+                    ),
+                )
+            })?
+        };
+
+        let (frame_width, frame_height) = frame.size.unwrap_or((width, height));
+        let mut pixels = vec![0; frame_width * frame_height];
+        let mut inexact = 0;
+        for y in 0..height.min(frame_height.saturating_sub(frame.offset.1)) {
+            for x in 0..width.min(frame_width.saturating_sub(frame.offset.0)) {
+                let (v, exact) = nearest_index(&rgba[4 * (y * width + x)..]);
+                inexact += usize::from(!exact);
+                pixels[(y + frame.offset.1) * frame_width + x + frame.offset.0] = v;
+            }
+        }
+        if inexact > 0 {
+            warnings.push(format!(
+                "{}: {inexact} pixels not in the palette, mapped to the nearest color",
+                path.display()
+            ));
+        }
+
+        indexed.push(IndexedFrame {
+            index,
+            width: frame_width,
+            height: frame_height,
+            pixels,
+            hitboxes: frame.hitboxes.clone(),
+        });
+    }
+
+    Ok(Packed {
+        data: sprite::encode_sheet(&indexed)?,
+        warnings,
+    })
+}
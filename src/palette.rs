@@ -0,0 +1,109 @@
+//! The standard palettes, and palette files for pixel art editors, so
+//! mods can be drawn in exactly the colors the frames were extracted with.
+//!
+//! Index 0 of sprites is transparent. GIMP and JASC palettes have no
+//! notion of that and list its color like any other; Photoshop color
+//! tables record it as the transparent index.
+
+use std::fs::File;
+use std::io::{BufWriter, Error, Write};
+use std::path::Path;
+
+use crate::ega::{self, Monitor};
+
+/// The colors frames are extracted with by default, the BIOS palette
+/// registers on an IBM 5153. As in the game, index 0 is transparent and
+/// index 8 black.
+pub const EGA_5153: [[u8; 4]; 16] = ega::EGA_PAL;
+
+/// The BIOS palette registers with evenly spaced levels, keeping dark
+/// yellow rather than brown.
+pub const EGA_GENERIC: [[u8; 4]; 16] = ega::default_palette(Monitor::GenericEga);
+
+/// The BIOS palette registers through the 6-bit VGA DAC, as shown by VGA
+/// cards and most emulators.
+pub const VGA_DAC: [[u8; 4]; 16] = ega::default_palette(Monitor::VgaDac);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP palette, also read by Aseprite and Krita.
+    Gpl,
+    /// JASC palette, as Paint Shop Pro writes them.
+    JascPal,
+    /// Adobe color table, for Photoshop.
+    Act,
+}
+
+pub const PALETTE_FORMATS: [PaletteFormat; 3] = [
+    PaletteFormat::Gpl,
+    PaletteFormat::JascPal,
+    PaletteFormat::Act,
+];
+
+impl PaletteFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteFormat::Gpl => "gpl",
+            PaletteFormat::JascPal => "pal",
+            PaletteFormat::Act => "act",
+        }
+    }
+
+    /// Writes `pal` in this format, under `name` where the format keeps
+    /// one.
+    pub fn write<W: Write>(self, w: &mut W, pal: &[[u8; 4]; 16], name: &str) -> Result<(), Error> {
+        match self {
+            PaletteFormat::Gpl => write_gpl(w, pal, name),
+            PaletteFormat::JascPal => write_jasc_pal(w, pal),
+            PaletteFormat::Act => write_act(w, pal),
+        }
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(
+        self,
+        filename: P,
+        pal: &[[u8; 4]; 16],
+        name: &str,
+    ) -> Result<(), Error> {
+        let mut w = BufWriter::new(File::create(filename)?);
+        self.write(&mut w, pal, name)?;
+        w.flush()
+    }
+}
+
+fn write_gpl<W: Write>(w: &mut W, pal: &[[u8; 4]; 16], name: &str) -> Result<(), Error> {
+    writeln!(w, "GIMP Palette")?;
+    writeln!(w, "Name: {}", name.replace(['\r', '\n'], " "))?;
+    writeln!(w, "Columns: 16")?;
+    writeln!(w, "#")?;
+    for (index, [r, g, b, a]) in pal.iter().enumerate() {
+        let transparent = if *a == 0 { ", transparent" } else { "" };
+        writeln!(w, "{r:3} {g:3} {b:3}\tIndex {index}{transparent}")?;
+    }
+    Ok(())
+}
+
+fn write_jasc_pal<W: Write>(w: &mut W, pal: &[[u8; 4]; 16]) -> Result<(), Error> {
+    write!(w, "JASC-PAL\r\n0100\r\n{}\r\n", pal.len())?;
+    for [r, g, b, _] in pal {
+        write!(w, "{r} {g} {b}\r\n")?;
+    }
+    Ok(())
+}
+
+/// 256 RGB triplets, followed by the number of colors used and the
+/// transparent index, or 0xffff for none, as big-endian words.
+fn write_act<W: Write>(w: &mut W, pal: &[[u8; 4]; 16]) -> Result<(), Error> {
+    let mut table = [0u8; 3 * 256];
+    for (entry, [r, g, b, _]) in table.chunks_mut(3).zip(pal) {
+        entry.copy_from_slice(&[*r, *g, *b]);
+    }
+    w.write_all(&table)?;
+
+    let transparent = pal
+        .iter()
+        .position(|c| c[3] == 0)
+        .map_or(0xffff, |i| i as u16);
+    w.write_all(&(pal.len() as u16).to_be_bytes())?;
+    w.write_all(&transparent.to_be_bytes())
+}
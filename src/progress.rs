@@ -0,0 +1,144 @@
+//! A progress bar for extracting many files, drawn on a terminal.
+
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 20;
+
+/// Redraws for decoded frames are limited to this interval.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tracks files and frames done. The estimates are based on the number of
+/// input bytes extracted so far.
+pub struct Progress {
+    enabled: bool,
+    total_files: usize,
+    total_bytes: u64,
+    files_done: usize,
+    bytes_done: u64,
+    current: Option<(String, u64)>,
+    frames: usize,
+    started: Instant,
+    file_started: Instant,
+    drawn: Option<Instant>,
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+impl Progress {
+    /// Creates a progress bar for `total_files` files of `total_bytes` bytes
+    /// in total. Nothing is drawn when stdout is not a terminal.
+    pub fn new(total_files: usize, total_bytes: u64) -> Self {
+        let now = Instant::now();
+        Progress {
+            enabled: io::stdout().is_terminal(),
+            total_files,
+            total_bytes,
+            files_done: 0,
+            bytes_done: 0,
+            current: None,
+            frames: 0,
+            started: now,
+            file_started: now,
+            drawn: None,
+        }
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn start_file(&mut self, name: &str, size: u64) {
+        self.current = Some((name.to_string(), size));
+        self.file_started = Instant::now();
+        self.draw();
+    }
+
+    pub fn frame(&mut self) {
+        self.frames += 1;
+        if self.drawn.is_none_or(|t| t.elapsed() >= REDRAW_INTERVAL) {
+            self.draw();
+        }
+    }
+
+    pub fn finish_file(&mut self) {
+        if let Some((_, size)) = self.current.take() {
+            self.bytes_done += size;
+        }
+        self.files_done += 1;
+        self.draw();
+    }
+
+    /// Prints a message above the bar.
+    pub fn message(&mut self, message: &str) {
+        self.clear();
+        println!("{message}");
+        self.draw();
+    }
+
+    /// Removes the bar.
+    pub fn finish(&mut self) {
+        self.clear();
+        self.enabled = false;
+    }
+
+    /// The status line, given the time since the start and since the
+    /// current file was started.
+    pub fn render(&self, elapsed: Duration, in_file: Duration) -> String {
+        let filled = BAR_WIDTH * self.files_done / self.total_files.max(1);
+        let mut line = format!(
+            "[{}{}] {}/{} files, {} frames",
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.files_done,
+            self.total_files,
+            self.frames
+        );
+
+        let rate = self.bytes_done as f64 / elapsed.as_secs_f64();
+        let estimate = |bytes: u64| {
+            if self.bytes_done == 0 || !rate.is_finite() {
+                "-:--".to_string()
+            } else {
+                format_duration(Duration::from_secs_f64(bytes as f64 / rate))
+            }
+        };
+
+        if let Some((name, size)) = &self.current {
+            let in_file_bytes = (in_file.as_secs_f64() * rate) as u64;
+            line += &format!(
+                ", {name} ETA {}",
+                estimate(size.saturating_sub(in_file_bytes))
+            );
+        }
+        line += &format!(
+            ", total ETA {}",
+            estimate(self.total_bytes.saturating_sub(self.bytes_done))
+        );
+        line
+    }
+
+    fn draw(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let line = self.render(self.started.elapsed(), self.file_started.elapsed());
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "\r\x1b[2K{line}");
+        let _ = stdout.flush();
+        self.drawn = Some(Instant::now());
+    }
+
+    fn clear(&mut self) {
+        if self.enabled && self.drawn.is_some() {
+            let mut stdout = io::stdout().lock();
+            let _ = write!(stdout, "\r\x1b[2K");
+            let _ = stdout.flush();
+            self.drawn = None;
+        }
+    }
+}
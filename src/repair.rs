@@ -0,0 +1,90 @@
+//! Repairing planar screens dumped from disks with bad sectors.
+//!
+//! Disk copiers fill sectors they cannot read with the byte the disk was
+//! formatted with, 0xF6 on DOS and 0xE5 on the Atari ST. In a planar
+//! image such a sector turns a band of one plane into the same bit
+//! pattern, which shows as stripes of wrong colors across the screen.
+//! Runs of a fill byte at least a sector long are taken for such damage
+//! and replaced by the bytes of the nearest undamaged rows of the plane,
+//! or by zeros if the whole column of the plane is damaged.
+
+use crate::ega;
+
+/// Bytes disks are formatted with, which unreadable sectors come out as.
+pub const FILL_BYTES: [u8; 2] = [0xf6, 0xe5];
+
+/// The shortest run of a fill byte taken for an unreadable sector.
+pub const SECTOR_SIZE: usize = 512;
+
+/// A damaged part of one plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Damage {
+    pub plane: usize,
+    /// The pixel rows affected, inclusive.
+    pub first_row: usize,
+    pub last_row: usize,
+    /// The number of damaged bytes, and what they were filled with.
+    pub len: usize,
+    pub fill: u8,
+}
+
+impl Damage {
+    pub fn describe(&self) -> String {
+        format!(
+            "Plane {}, rows {}-{}: {} bytes of 0x{:02X}, likely an unreadable sector, repaired from the neighbouring rows.",
+            self.plane, self.first_row, self.last_row, self.len, self.fill
+        )
+    }
+}
+
+/// Finds and repairs damage in the four planes of a `width` x `height`
+/// image at the start of `data`.
+pub fn repair_planes(data: &mut [u8], width: usize, height: usize) -> Vec<Damage> {
+    let plane_size = ega::planar_size(width, height) / 4;
+    let row_size = width / 8;
+    if row_size == 0 || data.len() < 4 * plane_size {
+        return Vec::new();
+    }
+
+    let mut damages = Vec::new();
+    for (plane, bytes) in data.chunks_mut(plane_size).take(4).enumerate() {
+        let mut damaged = vec![false; plane_size];
+        let mut start = 0;
+        while start < plane_size {
+            let fill = bytes[start];
+            let len = bytes[start..].iter().take_while(|&&b| b == fill).count();
+            if len >= SECTOR_SIZE && FILL_BYTES.contains(&fill) {
+                damaged[start..start + len].fill(true);
+                damages.push(Damage {
+                    plane,
+                    first_row: start / row_size,
+                    last_row: (start + len - 1) / row_size,
+                    len,
+                    fill,
+                });
+            }
+            start += len;
+        }
+
+        for ofs in 0..plane_size {
+            if !damaged[ofs] {
+                continue;
+            }
+            let undamaged = |ofs: &usize| !damaged[*ofs];
+            let above = (0..ofs / row_size)
+                .rev()
+                .map(|row| row * row_size + ofs % row_size)
+                .find(undamaged);
+            let below = (ofs / row_size + 1..plane_size / row_size)
+                .map(|row| row * row_size + ofs % row_size)
+                .find(undamaged);
+            bytes[ofs] = match (above, below) {
+                (Some(a), Some(b)) if ofs - a <= b - ofs => bytes[a],
+                (_, Some(b)) => bytes[b],
+                (Some(a), None) => bytes[a],
+                (None, None) => 0,
+            };
+        }
+    }
+    damages
+}
@@ -0,0 +1,189 @@
+//! Locating images inside files of unknown layout.
+//!
+//! Every offset is tried as the start of a sprite sheet, which is cheap to
+//! rule out, and every `step` bytes as the start of each planar fullscreen
+//! layout. Whatever decodes is scored on how much it looks like a picture
+//! and the best non-overlapping candidates are kept.
+//!
+//! Decoding a screen at every step would take minutes on a dump of a few
+//! megabytes, so a handful of rows spread over the screen are decoded and
+//! scored first, and only screens whose rows look like a picture are
+//! decoded in full.
+
+use crate::ega::{self, planar_size};
+use crate::format::{Extract, IndexedFrame, PlanarScreen, Sprites};
+use crate::sprite::{Entry, SpriteSheet, SHEET_HEADER_SIZE};
+
+/// Candidates scoring lower than this are dropped.
+pub const MIN_SCORE: f64 = 0.5;
+
+/// Rows of a screen decoded to decide whether to decode all of it.
+pub const SAMPLE_ROWS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CandidateKind {
+    Screen { width: usize, height: usize },
+    Sheet { frames: usize },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    pub offset: usize,
+    pub len: usize,
+    pub kind: CandidateKind,
+    pub score: f64,
+}
+
+/// Scores indexed frames between 0 and 1 on how much they look like
+/// pictures rather than noise: the share of horizontally adjacent pixels
+/// that have the same index. Frames using (almost) a single index score 0.
+pub fn score(frames: &[IndexedFrame]) -> f64 {
+    let mut histogram = [0usize; 16];
+    let mut pairs = 0;
+    let mut equal = 0;
+
+    for f in frames {
+        for row in f.pixels.chunks(f.width.max(1)) {
+            for &v in row {
+                histogram[v as usize & 0x0f] += 1;
+            }
+            for p in row.windows(2) {
+                pairs += 1;
+                if p[0] == p[1] {
+                    equal += 1;
+                }
+            }
+        }
+    }
+
+    let total = histogram.iter().sum::<usize>() as f64;
+    let entropy = histogram
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / total;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+
+    if pairs == 0 || entropy < 0.5 {
+        return 0.0;
+    }
+
+    equal as f64 / pairs as f64
+}
+
+fn sheet_at(src: &[u8], offset: usize) -> Option<(usize, Vec<IndexedFrame>)> {
+    let header = src.get(offset..offset + SHEET_HEADER_SIZE)?;
+    let size = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+    let sheet_src = src.get(offset..offset.checked_add(SHEET_HEADER_SIZE + size)?)?;
+
+    let sheet = SpriteSheet::parse(sheet_src).ok()?;
+    if !sheet.entries().all(|e| matches!(e, Entry::Frame(_))) {
+        return None;
+    }
+
+    let frames = Sprites::new().extract(sheet_src).ok()?.frames;
+    (!frames.is_empty()).then_some((sheet_src.len(), frames))
+}
+
+/// Decodes [`SAMPLE_ROWS`] rows spread evenly over a planar screen at the
+/// start of `src`, as a frame of that many rows.
+fn sample_rows(src: &[u8], width: usize, height: usize) -> IndexedFrame {
+    let plane_size = planar_size(width, height) / 4;
+    let rows = SAMPLE_ROWS.min(height);
+
+    let mut pixels = Vec::with_capacity(width * rows);
+    for n in 0..rows {
+        let y = n * height / rows;
+        for x in 0..width {
+            let ofs = (width * y + x) / 8;
+            let bitofs = 7 - x % 8;
+            pixels.push((0..4).fold(0, |v, plane| {
+                v | ((src[plane * plane_size + ofs] >> bitofs) & 1) << plane
+            }));
+        }
+    }
+
+    IndexedFrame {
+        index: 0,
+        width,
+        height: rows,
+        pixels,
+        hitboxes: Vec::new(),
+    }
+}
+
+/// Decodes the image a candidate points at.
+pub fn decode(src: &[u8], candidate: &Candidate) -> Vec<IndexedFrame> {
+    let src = &src[candidate.offset..candidate.offset + candidate.len];
+    let extracted = match candidate.kind {
+        CandidateKind::Screen { width, height } => {
+            PlanarScreen::with_size(width, height).extract(src)
+        }
+        CandidateKind::Sheet { .. } => Sprites::new().extract(src),
+    };
+    extracted.map(|e| e.frames).unwrap_or_default()
+}
+
+/// Looks for images in `src`, trying fullscreen images every `step`
+/// bytes. Returns the candidates best first.
+pub fn scan(src: &[u8], step: usize) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+
+    for offset in 0..src.len() {
+        if let Some((len, frames)) = sheet_at(src, offset) {
+            candidates.push(Candidate {
+                offset,
+                len,
+                kind: CandidateKind::Sheet {
+                    frames: frames.len(),
+                },
+                score: score(&frames),
+            });
+        }
+
+        if offset % step.max(1) != 0 {
+            continue;
+        }
+
+        for (width, height) in ega::FULLSCREEN_LAYOUTS {
+            let len = planar_size(width, height);
+            if offset + len > src.len() {
+                continue;
+            }
+
+            if score(&[sample_rows(&src[offset..], width, height)]) < MIN_SCORE {
+                continue;
+            }
+
+            let candidate = Candidate {
+                offset,
+                len,
+                kind: CandidateKind::Screen { width, height },
+                score: 0.0,
+            };
+            candidates.push(Candidate {
+                score: score(&decode(src, &candidate)),
+                ..candidate
+            });
+        }
+    }
+
+    // Best first, earlier offsets first among equals, then drop whatever
+    // overlaps a better candidate.
+    candidates.retain(|c| c.score >= MIN_SCORE);
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.offset.cmp(&b.offset)));
+
+    let mut kept: Vec<Candidate> = Vec::new();
+    for c in candidates {
+        let overlaps = kept
+            .iter()
+            .any(|k| c.offset < k.offset + k.len && k.offset < c.offset + c.len);
+        if !overlaps {
+            kept.push(c);
+        }
+    }
+
+    kept
+}
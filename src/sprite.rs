@@ -0,0 +1,315 @@
+use std::io::{Error, ErrorKind};
+
+use crate::ega::{decode_interleaved_ega_to_indexed, decode_interleaved_ega_to_rgba};
+use crate::format::IndexedFrame;
+
+pub const SHEET_HEADER_SIZE: usize = 4;
+pub const FRAME_HEADER_SIZE: usize = 4;
+
+/// A sprite sheet: a big-endian total size followed by a sequence of
+/// frames, each with a small little-endian header of its own.
+pub struct SpriteSheet<'a> {
+    body: &'a [u8],
+    size_order: SizeOrder,
+}
+
+/// Byte order of the total size in a sheet header. The engine reads it
+/// big-endian, but some files have it little-endian like the frame sizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+impl SizeOrder {
+    /// The byte order in which `header` holds `size`, trying big-endian
+    /// first.
+    pub fn of(header: [u8; SHEET_HEADER_SIZE], size: usize) -> Option<SizeOrder> {
+        if u32::from_be_bytes(header) as usize == size {
+            Some(SizeOrder::BigEndian)
+        } else if u32::from_le_bytes(header) as usize == size {
+            Some(SizeOrder::LittleEndian)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Frame<'a> {
+    pub index: usize,
+    /// Bytes per row of pixel data.
+    pub span: usize,
+    /// Width in pixels, at most `2 * span`.
+    pub width: usize,
+    pub height: usize,
+    /// Everything after the frame header, pixel data and trailer.
+    pub data: &'a [u8],
+}
+
+/// The most rectangles a frame trailer is taken to hold.
+pub const MAX_HITBOXES: usize = 8;
+
+/// A collision or interaction rectangle, relative to the frame, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HitBox {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+pub enum Entry<'a> {
+    Frame(Frame<'a>),
+    /// The entry could not be decoded, but the following entries can.
+    Skipped {
+        index: usize,
+        reason: String,
+    },
+    /// The entry could not be decoded, and the remainder of the sheet is
+    /// unreachable.
+    Stopped {
+        index: usize,
+        reason: String,
+    },
+    /// The sheet ends with fewer bytes than a frame header, which some files
+    /// pad their last frame with.
+    Padding {
+        len: usize,
+    },
+}
+
+pub struct Entries<'a> {
+    src: &'a [u8],
+    body_len: usize,
+    index: usize,
+    done: bool,
+}
+
+impl<'a> SpriteSheet<'a> {
+    pub fn parse(src: &'a [u8]) -> Result<SpriteSheet<'a>, Error> {
+        if src.len() < SHEET_HEADER_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "file too small"));
+        }
+
+        let header = src[..SHEET_HEADER_SIZE].try_into().unwrap();
+        let body = &src[SHEET_HEADER_SIZE..];
+        let Some(size_order) = SizeOrder::of(header, body.len()) else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "size in header incorrect",
+            ));
+        };
+
+        Ok(SpriteSheet { body, size_order })
+    }
+
+    /// The byte order the size in the header was found in.
+    pub fn size_order(&self) -> SizeOrder {
+        self.size_order
+    }
+
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            src: self.body,
+            body_len: self.body.len(),
+            index: 0,
+            done: false,
+        }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'a>> {
+        self.entries().filter_map(|e| match e {
+            Entry::Frame(f) => Some(f),
+            _ => None,
+        })
+    }
+}
+
+impl Frame<'_> {
+    /// Bytes following the pixel data within the frame.
+    pub fn trailer(&self) -> &[u8] {
+        &self.data[self.span * self.height..]
+    }
+
+    /// Some frames are followed by more bytes than their pixels take, which
+    /// may be the rectangles the engine uses for collisions and
+    /// interactions, four bytes each: x, y, width and height in pixels.
+    /// That layout is a guess that has not been confirmed against the
+    /// game's files, so it is only used when asked for, and only trailers
+    /// of 1 to [`MAX_HITBOXES`] rectangles that are not empty and lie
+    /// within the frame are taken for rectangles. Anything else, like zero
+    /// padding, carries none.
+    pub fn hitboxes(&self) -> Vec<HitBox> {
+        let trailer = self.trailer();
+        if trailer.is_empty()
+            || !trailer.len().is_multiple_of(4)
+            || trailer.len() / 4 > MAX_HITBOXES
+        {
+            return Vec::new();
+        }
+
+        let boxes = trailer
+            .chunks(4)
+            .map(|r| HitBox {
+                x: r[0] as usize,
+                y: r[1] as usize,
+                w: r[2] as usize,
+                h: r[3] as usize,
+            })
+            .collect::<Vec<_>>();
+
+        if boxes
+            .iter()
+            .any(|b| b.w == 0 || b.h == 0 || b.x + b.w > self.width || b.y + b.h > self.height)
+        {
+            return Vec::new();
+        }
+
+        boxes
+    }
+
+    pub fn to_indexed(&self) -> Vec<u8> {
+        decode_interleaved_ega_to_indexed(self.data, self.span, self.width, self.height)
+    }
+
+    pub fn to_rgba(&self) -> Vec<u8> {
+        decode_interleaved_ega_to_rgba(self.data, self.span, self.width, self.height)
+    }
+}
+
+/// Encodes frames as a sprite sheet, the inverse of [`SpriteSheet::parse`].
+/// Frames are padded with index 0 to a multiple of 4 pixels wide, and
+/// their hit boxes are written as the trailer.
+pub fn encode_sheet(frames: &[IndexedFrame]) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+
+    for frame in frames {
+        let invalid = |msg: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("frame {}: {msg}", frame.index),
+            )
+        };
+
+        let words = frame.width.div_ceil(4);
+        let span = 2 * words;
+        if words == 0 || frame.height == 0 {
+            return Err(invalid("empty frame".to_string()));
+        }
+        if words > u8::MAX as usize || frame.height > u8::MAX as usize {
+            return Err(invalid(format!(
+                "{}x{} exceeds the largest frame, 1020x255",
+                frame.width, frame.height
+            )));
+        }
+        if let Some(b) = frame
+            .hitboxes
+            .iter()
+            .find(|b| b.x + b.w > frame.width || b.y + b.h > frame.height || b.x.max(b.w) > 255)
+        {
+            return Err(invalid(format!(
+                "hit box {},{} {}x{} outside the frame",
+                b.x, b.y, b.w, b.h
+            )));
+        }
+
+        let input_size = FRAME_HEADER_SIZE + span * frame.height + 4 * frame.hitboxes.len();
+        let Ok(input_size) = u16::try_from(input_size) else {
+            return Err(invalid(format!(
+                "{input_size} bytes, more than a frame holds"
+            )));
+        };
+
+        body.extend_from_slice(&input_size.to_le_bytes());
+        body.push(words as u8);
+        body.push(frame.height as u8);
+        for row in frame.pixels.chunks(frame.width) {
+            let pixel = |x: usize| row.get(x).copied().unwrap_or(0) & 0x0f;
+            body.extend((0..span).map(|x| (pixel(2 * x) << 4) | pixel(2 * x + 1)));
+        }
+        for b in &frame.hitboxes {
+            body.extend([b.x, b.y, b.w, b.h].map(|v| v as u8));
+        }
+    }
+
+    let mut out = (body.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+impl Entries<'_> {
+    /// Offset of the next entry in the file.
+    pub fn offset(&self) -> usize {
+        SHEET_HEADER_SIZE + self.body_len - self.src.len()
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        if self.done || self.src.is_empty() {
+            return None;
+        }
+
+        let src = self.src;
+
+        if src.len() < FRAME_HEADER_SIZE {
+            self.done = true;
+            return Some(Entry::Padding { len: src.len() });
+        }
+
+        let index = self.index;
+        self.index += 1;
+
+        // The width in the header counts 16-bit words, so each unit is two
+        // bytes of pixel data, or four pixels.
+        let input_size = u16::from_le_bytes(src[0..2].try_into().unwrap()) as usize;
+        let span = 2 * src[2] as usize;
+        let height = src[3] as usize;
+
+        let width = 2 * span;
+
+        // An entry must at least cover its own header, otherwise we would
+        // never make progress. Step over the header and carry on.
+        if input_size < FRAME_HEADER_SIZE {
+            self.src = &src[FRAME_HEADER_SIZE..];
+            return Some(Entry::Skipped {
+                index,
+                reason: format!("invalid frame size {input_size}"),
+            });
+        }
+
+        if input_size > src.len() {
+            self.done = true;
+            return Some(Entry::Stopped {
+                index,
+                reason: format!("frame size {input_size} exceeds remaining data"),
+            });
+        }
+
+        self.src = &src[input_size..];
+
+        if width == 0 || height == 0 {
+            return Some(Entry::Skipped {
+                index,
+                reason: format!("zero-sized frame ({width}x{height})"),
+            });
+        }
+
+        if FRAME_HEADER_SIZE + span * height > input_size {
+            return Some(Entry::Skipped {
+                index,
+                reason: format!("pixel data exceeds frame size {input_size}"),
+            });
+        }
+
+        Some(Entry::Frame(Frame {
+            index,
+            span,
+            width,
+            height,
+            data: &src[FRAME_HEADER_SIZE..input_size],
+        }))
+    }
+}
@@ -0,0 +1,133 @@
+//! Splitting screens into tiles, for backgrounds built from a grid of
+//! tiles reused across rooms.
+//!
+//! The distinct tiles of a screen make its tileset, in order of first
+//! appearance, and the tilemap gives the tileset index of every grid
+//! cell, row by row. Screens that do not divide into whole tiles have
+//! their last column and row of tiles padded with index 0.
+
+use std::collections::HashMap;
+use std::io::{Error, Write};
+
+use crate::engine::json_escape;
+use crate::format::IndexedFrame;
+
+/// Tiles per row of the tileset image.
+pub const TILESET_COLUMNS: usize = 16;
+
+pub struct Tiling {
+    pub tile_width: usize,
+    pub tile_height: usize,
+    /// Size of the map, in tiles.
+    pub columns: usize,
+    pub rows: usize,
+    /// The pixels of each distinct tile.
+    pub tiles: Vec<Vec<u8>>,
+    /// Index into `tiles` for each cell of the map, row by row.
+    pub map: Vec<usize>,
+}
+
+/// Parses a tile size, `WxH`.
+pub fn parse_tile_size(s: &str) -> Option<(usize, usize)> {
+    let (w, h) = s.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+pub fn split(frame: &IndexedFrame, tile_width: usize, tile_height: usize) -> Tiling {
+    let columns = frame.width.div_ceil(tile_width);
+    let rows = frame.height.div_ceil(tile_height);
+
+    let mut tiles = Vec::new();
+    let mut map = Vec::with_capacity(columns * rows);
+    let mut seen = HashMap::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut tile = Vec::with_capacity(tile_width * tile_height);
+            for y in row * tile_height..(row + 1) * tile_height {
+                for x in column * tile_width..(column + 1) * tile_width {
+                    let inside = x < frame.width && y < frame.height;
+                    tile.push(if inside {
+                        frame.pixels[y * frame.width + x]
+                    } else {
+                        0
+                    });
+                }
+            }
+            let index = *seen.entry(tile.clone()).or_insert_with(|| {
+                tiles.push(tile);
+                tiles.len() - 1
+            });
+            map.push(index);
+        }
+    }
+
+    Tiling {
+        tile_width,
+        tile_height,
+        columns,
+        rows,
+        tiles,
+        map,
+    }
+}
+
+impl Tiling {
+    /// The tiles laid out [`TILESET_COLUMNS`] to a row.
+    pub fn tileset(&self) -> IndexedFrame {
+        let columns = self.tiles.len().clamp(1, TILESET_COLUMNS);
+        let rows = self.tiles.len().div_ceil(TILESET_COLUMNS).max(1);
+        let width = columns * self.tile_width;
+        let height = rows * self.tile_height;
+
+        let mut pixels = vec![0; width * height];
+        for (n, tile) in self.tiles.iter().enumerate() {
+            let x = n % TILESET_COLUMNS * self.tile_width;
+            let y = n / TILESET_COLUMNS * self.tile_height;
+            for (ty, row) in tile.chunks(self.tile_width).enumerate() {
+                let pos = (y + ty) * width + x;
+                pixels[pos..pos + self.tile_width].copy_from_slice(row);
+            }
+        }
+
+        IndexedFrame {
+            index: 0,
+            width,
+            height,
+            pixels,
+            hitboxes: Vec::new(),
+        }
+    }
+
+    /// Writes the tilemap as JSON, referring to the tileset image by
+    /// `tileset_name`.
+    pub fn write_json<W: Write>(&self, w: &mut W, tileset_name: &str) -> Result<(), Error> {
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"tileset\": \"{}\",", json_escape(tileset_name))?;
+        writeln!(w, "  \"tile_width\": {},", self.tile_width)?;
+        writeln!(w, "  \"tile_height\": {},", self.tile_height)?;
+        writeln!(w, "  \"tileset_columns\": {TILESET_COLUMNS},")?;
+        writeln!(w, "  \"tiles\": {},", self.tiles.len())?;
+        writeln!(w, "  \"columns\": {},", self.columns)?;
+        writeln!(w, "  \"rows\": {},", self.rows)?;
+        writeln!(w, "  \"map\": [")?;
+        for (n, row) in self.map.chunks(self.columns.max(1)).enumerate() {
+            let row = row.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+            let sep = if n + 1 < self.rows { "," } else { "" };
+            writeln!(w, "    [{}]{sep}", row.join(", "))?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")?;
+        Ok(())
+    }
+
+    /// Writes the tilemap as CSV, one line per row of tiles, as Tiled
+    /// imports them.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        for row in self.map.chunks(self.columns.max(1)) {
+            let row = row.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+            writeln!(w, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Video files of animations, encoded by piping raw frames to `ffmpeg`,
+//! which has to be on the `PATH`.
+
+#[cfg(feature = "video")]
+use std::io::{Error, ErrorKind, Write};
+#[cfg(feature = "video")]
+use std::path::Path;
+#[cfg(feature = "video")]
+use std::process::{Command, Stdio};
+
+use crate::ega::indexed_to_rgba_with_palette;
+use crate::format::IndexedFrame;
+use crate::output::{scale_pixels, SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Webm,
+}
+
+impl Container {
+    pub fn from_name(name: &str) -> Option<Container> {
+        match name {
+            "mp4" => Some(Container::Mp4),
+            "webm" => Some(Container::Webm),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+        }
+    }
+
+    #[cfg(feature = "video")]
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            Container::Mp4 => &["-c:v", "libx264", "-pix_fmt", "yuv420p"],
+            Container::Webm => &["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Video {
+    pub container: Container,
+    pub fps: u32,
+    /// Multiplies the usual output scaling.
+    pub scale: usize,
+}
+
+impl Default for Video {
+    fn default() -> Self {
+        Video {
+            container: Container::Mp4,
+            fps: 10,
+            scale: 1,
+        }
+    }
+}
+
+/// Places the frames on a black canvas as large as the largest frame,
+/// in the top left corner like the GIFs, and scales them. Returns the
+/// canvas size and the RGB frames. The size is rounded up to even, as
+/// most codecs need.
+pub fn video_frames(
+    frames: &[IndexedFrame],
+    pal: &[[u8; 4]; 16],
+    scale: usize,
+) -> (usize, usize, Vec<Vec<u8>>) {
+    let width = frames.iter().map(|f| f.width).max().unwrap_or(0);
+    let height = frames.iter().map(|f| f.height).max().unwrap_or(0);
+
+    let sx = SCALE_FACTOR_WIDTH * scale;
+    let sy = SCALE_FACTOR_HEIGHT * scale;
+    let canvas_width = (sx * width).next_multiple_of(2);
+    let canvas_height = (sy * height).next_multiple_of(2);
+
+    let rgb_frames = frames
+        .iter()
+        .map(|f| {
+            let rgba = indexed_to_rgba_with_palette(&f.pixels, pal);
+            let scaled = scale_pixels(&rgba, 4, f.width, f.height);
+            let scaled = scale_by(&scaled, SCALE_FACTOR_WIDTH * f.width, scale);
+            let (fw, fh) = (sx * f.width, sy * f.height);
+
+            let mut canvas = vec![0u8; 3 * canvas_width * canvas_height];
+            for y in 0..fh {
+                for x in 0..fw {
+                    let px = &scaled[4 * (y * fw + x)..][..4];
+                    // Transparent pixels stay black.
+                    if px[3] != 0 {
+                        canvas[3 * (y * canvas_width + x)..][..3].copy_from_slice(&px[..3]);
+                    }
+                }
+            }
+            canvas
+        })
+        .collect();
+
+    (canvas_width, canvas_height, rgb_frames)
+}
+
+// Scales RGBA data by the same integer factor in both directions.
+fn scale_by(data: &[u8], width: usize, scale: usize) -> Vec<u8> {
+    if scale == 1 {
+        return data.to_vec();
+    }
+
+    let mut out = Vec::with_capacity(data.len() * scale * scale);
+    for row in data.chunks(4 * width) {
+        let scaled_row = row
+            .chunks(4)
+            .flat_map(|px| std::iter::repeat_n(px, scale).flatten())
+            .copied()
+            .collect::<Vec<_>>();
+        for _ in 0..scale {
+            out.extend_from_slice(&scaled_row);
+        }
+    }
+    out
+}
+
+/// Encodes the frames into a video at `filename`.
+#[cfg(feature = "video")]
+pub fn write_video<P: AsRef<Path>>(
+    filename: P,
+    frames: &[IndexedFrame],
+    pal: &[[u8; 4]; 16],
+    video: &Video,
+) -> Result<(), Error> {
+    let (width, height, rgb_frames) = video_frames(frames, pal, video.scale);
+
+    let mut child = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+        .args(["-s", &format!("{width}x{height}")])
+        .args(["-r", &video.fps.to_string()])
+        .args(["-i", "-"])
+        .args(video.container.codec_args())
+        .arg(filename.as_ref())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::new(
+                ErrorKind::NotFound,
+                "ffmpeg not found, it is needed for video output",
+            ),
+            _ => e,
+        })?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    for frame in &rgb_frames {
+        stdin.write_all(frame)?;
+    }
+    drop(stdin);
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::other(format!("ffmpeg failed, {status}")));
+    }
+
+    Ok(())
+}
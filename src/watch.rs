@@ -0,0 +1,110 @@
+//! Noticing changes to files, for re-running extraction or packing while
+//! game files or edited frames are worked on.
+//!
+//! Files are polled rather than watched through the operating system, by
+//! their modification time and size, which works the same everywhere and
+//! is cheap for directories the size of a game.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// The modification time and size of files at one point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of `paths`, and of every file below those that are
+    /// directories. Hidden files, like the state of incremental extraction,
+    /// are left out. Paths that do not exist are left out as well, so they
+    /// are noticed when they are created.
+    pub fn take<P: AsRef<Path>>(paths: &[P]) -> Result<Snapshot, Error> {
+        let mut snapshot = Snapshot::default();
+        for path in paths {
+            snapshot.add(path.as_ref())?;
+        }
+        Ok(snapshot)
+    }
+
+    fn add(&mut self, path: &Path) -> Result<(), Error> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    self.add(&entry.path())?;
+                }
+            }
+        } else {
+            self.files
+                .insert(path.to_path_buf(), (metadata.modified()?, metadata.len()));
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The files added, changed or removed in `newer`, in order.
+    pub fn changes(&self, newer: &Snapshot) -> Vec<PathBuf> {
+        let mut changes = newer
+            .files
+            .iter()
+            .filter(|(path, state)| self.files.get(*path) != Some(state))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        changes.extend(
+            self.files
+                .keys()
+                .filter(|path| !newer.files.contains_key(*path))
+                .cloned(),
+        );
+        changes.sort();
+        changes
+    }
+}
+
+/// Polls `paths` every `interval` until they differ from `snapshot`, then
+/// until they stay the same for an interval, so an editor saving several
+/// files or writing one in steps causes one change. Returns the new
+/// snapshot and the files that changed.
+pub fn wait_for_changes<P: AsRef<Path>>(
+    paths: &[P],
+    snapshot: &Snapshot,
+    interval: Duration,
+) -> Result<(Snapshot, Vec<PathBuf>), Error> {
+    let mut current = loop {
+        thread::sleep(interval);
+        let current = Snapshot::take(paths)?;
+        if current != *snapshot {
+            break current;
+        }
+    };
+
+    loop {
+        thread::sleep(interval);
+        let settled = Snapshot::take(paths)?;
+        if settled == current {
+            break;
+        }
+        current = settled;
+    }
+
+    let changes = snapshot.changes(&current);
+    Ok((current, changes))
+}
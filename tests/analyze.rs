@@ -0,0 +1,88 @@
+use exxos_kult_extract::analyze::{frame_stats, repetition, DuplicateFinder, Repetition};
+use exxos_kult_extract::format::IndexedFrame;
+
+fn frame(width: usize, height: usize, pixels: &[u8]) -> IndexedFrame {
+    IndexedFrame {
+        index: 0,
+        width,
+        height,
+        pixels: pixels.to_vec(),
+        hitboxes: Vec::new(),
+    }
+}
+
+#[test]
+fn enclosed_zero_pixels_are_interior() {
+    #[rustfmt::skip]
+    let f = frame(5, 4, &[
+        0, 7, 7, 7, 0,
+        0, 7, 0, 7, 0,
+        0, 7, 0, 7, 0,
+        0, 7, 7, 7, 0,
+    ]);
+
+    let stats = frame_stats(&f);
+    assert_eq!(stats.histogram[0], 10);
+    assert_eq!(stats.histogram[7], 10);
+    assert_eq!(stats.interior_zero, 2);
+}
+
+#[test]
+fn zero_connected_to_edge_is_not_interior() {
+    #[rustfmt::skip]
+    let f = frame(3, 3, &[
+        7, 0, 7,
+        7, 0, 7,
+        7, 7, 7,
+    ]);
+
+    assert_eq!(frame_stats(&f).interior_zero, 0);
+}
+
+#[test]
+fn duplicates_need_equal_dimensions() {
+    let mut finder = DuplicateFinder::new();
+
+    assert_eq!(finder.check(&frame(2, 1, &[1, 2]), "a"), None);
+    assert_eq!(finder.check(&frame(1, 2, &[1, 2]), "b"), None);
+    assert_eq!(finder.check(&frame(2, 1, &[1, 2]), "c"), Some("a"));
+}
+
+#[test]
+fn repeated_and_mirrored_halves_are_found() {
+    #[rustfmt::skip]
+    let repeated = frame(4, 2, &[
+        1, 2, 1, 2,
+        3, 4, 3, 4,
+    ]);
+    #[rustfmt::skip]
+    let stacked = frame(2, 4, &[
+        1, 2,
+        3, 4,
+        1, 2,
+        3, 4,
+    ]);
+    #[rustfmt::skip]
+    let mirrored = frame(4, 2, &[
+        1, 2, 2, 1,
+        3, 4, 4, 3,
+    ]);
+    #[rustfmt::skip]
+    let distinct = frame(4, 2, &[
+        1, 2, 3, 4,
+        5, 6, 7, 8,
+    ]);
+
+    assert_eq!(repetition(&repeated), Some(Repetition::Horizontal));
+    assert_eq!(repetition(&stacked), Some(Repetition::Vertical));
+    assert_eq!(repetition(&mirrored), Some(Repetition::Mirrored));
+    assert_eq!(repetition(&distinct), None);
+    // A blank frame matches any way, so it is not reported.
+    assert_eq!(repetition(&frame(4, 2, &[0; 8])), None);
+    assert_eq!(repetition(&frame(0, 0, &[])), None);
+
+    assert_eq!(
+        Repetition::Horizontal.describe(320, 200),
+        "The right half repeats the left half, it may be a 160x200 image padded to 320x200."
+    );
+}
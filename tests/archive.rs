@@ -0,0 +1,270 @@
+#![cfg(feature = "archives")]
+
+mod common;
+
+use std::fs;
+use std::io::ErrorKind;
+
+use common::*;
+use exxos_kult_extract::archive::{self, ArchiveKind};
+
+fn crc32(src: &[u8]) -> u32 {
+    !src.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| {
+            if c & 1 != 0 {
+                (c >> 1) ^ 0xedb88320
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+fn crc16(src: &[u8]) -> u16 {
+    src.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ b as u16, |c, _| {
+            if c & 1 != 0 {
+                (c >> 1) ^ 0xa001
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+/// A ZIP of `(name, method, data)` members, deflating those with method 8.
+fn zip(members: &[(&str, u16, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+
+    for &(name, method, data) in members {
+        let packed = match method {
+            8 => miniz_oxide::deflate::compress_to_vec(data, 6),
+            _ => data.to_vec(),
+        };
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&[0; 4]);
+        fields.extend_from_slice(&crc32(data).to_le_bytes());
+        fields.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&[0; 2]);
+
+        directory.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+        directory.extend_from_slice(&fields);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&(out.len() as u32).to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+
+        out.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00");
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&packed);
+    }
+
+    let offset = out.len() as u32;
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.extend_from_slice(&[0; 2]);
+    out
+}
+
+/// A level 0 LHA header and the packed data of a member.
+fn lha_level0(name: &str, method: &[u8; 5], packed: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut header = method.to_vec();
+    header.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+    header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&[0x20, 0]);
+    header.push(name.len() as u8);
+    header.extend_from_slice(name.as_bytes());
+    header.extend_from_slice(&crc16(data).to_le_bytes());
+
+    let sum = header.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+    let mut out = vec![header.len() as u8, sum];
+    out.extend_from_slice(&header);
+    out.extend_from_slice(packed);
+    out
+}
+
+/// A level 2 LHA header, with the name in an extended header.
+fn lha_level2(dir: &[u8], name: &str, data: &[u8]) -> Vec<u8> {
+    let file_ext = [&[1][..], name.as_bytes()].concat();
+    let dir_ext = [&[2][..], dir].concat();
+    let header_size = 26 + file_ext.len() + 2 + dir_ext.len() + 2;
+
+    let mut out = (header_size as u16).to_le_bytes().to_vec();
+    out.extend_from_slice(b"-lh0-");
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&[0x20, 2]);
+    out.extend_from_slice(&crc16(data).to_le_bytes());
+    out.push(b'M');
+    out.extend_from_slice(&((file_ext.len() + 2) as u16).to_le_bytes());
+    out.extend_from_slice(&file_ext);
+    out.extend_from_slice(&((dir_ext.len() + 2) as u16).to_le_bytes());
+    out.extend_from_slice(&dir_ext);
+    out.extend_from_slice(&[0; 2]);
+    out.extend_from_slice(data);
+    out
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: usize, n: usize) {
+        for i in (0..n).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.out.push(0);
+            }
+            let bit = (value >> i) & 1;
+            *self.out.last_mut().unwrap() |= (bit as u8) << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+}
+
+#[test]
+fn zip_members_are_read() {
+    let sheet = sprite_sheet(&[interleaved_frame(1, 2, |x, _| x as u8)]);
+    let text = b"KULT KULT KULT KULT KULT KULT".to_vec();
+    let src = zip(&[
+        ("kult/", 0, b""),
+        ("kult/SPR.EGA", 8, &sheet),
+        ("README", 0, &text),
+    ]);
+
+    assert_eq!(archive::detect(&src), Some(ArchiveKind::Zip));
+    let members = archive::members(&src).unwrap();
+    let names = members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["kult/SPR.EGA", "README"]);
+    assert_eq!(members[0].data.as_ref().unwrap(), &sheet);
+    assert_eq!(members[1].data.as_ref().unwrap(), &text);
+}
+
+#[test]
+fn corrupt_members_do_not_affect_others() {
+    let mut src = zip(&[("A", 0, b"first"), ("B", 0, b"second"), ("C", 7, b"x")]);
+    let first = src.windows(5).position(|w| w == b"first").unwrap();
+    src[first] = b'F';
+
+    let members = archive::members(&src).unwrap();
+    let err = members[0].data.as_ref().unwrap_err();
+    assert!(err.to_string().contains("checksum"), "{err}");
+    assert_eq!(members[1].data.as_ref().unwrap(), b"second");
+    let err = members[2].data.as_ref().unwrap_err();
+    assert!(err.to_string().contains("method 7"), "{err}");
+
+    // Without its central directory a ZIP is unreadable.
+    src.truncate(first + 20);
+    assert!(archive::members(&src).is_err());
+}
+
+#[test]
+fn stored_lha_members() {
+    let mut src = lha_level0("PIC\\SCREEN.EGA", b"-lh0-", b"abc", b"abc");
+    src.extend(lha_level2(b"GFX\xff", "SPR.EGA", b"frames"));
+    src.push(0);
+
+    assert_eq!(archive::detect(&src), Some(ArchiveKind::Lha));
+    let members = archive::members(&src).unwrap();
+    let names = members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["PIC/SCREEN.EGA", "GFX/SPR.EGA"]);
+    assert_eq!(members[0].data.as_ref().unwrap(), b"abc");
+    assert_eq!(members[1].data.as_ref().unwrap(), b"frames");
+}
+
+#[test]
+fn lh5_members() {
+    let mut w = BitWriter::default();
+
+    // A block of four codes, the literals A and B with one bit codes.
+    w.put(4, 16);
+    // Code lengths of the code length code: 1 for symbols 2 and 3.
+    w.put(4, 5);
+    w.put(0, 3);
+    w.put(0, 3);
+    w.put(1, 3);
+    w.put(0, 2);
+    w.put(1, 3);
+    // 65 zero lengths, then 1 for A and B.
+    w.put(67, 9);
+    w.put(0, 1);
+    w.put(65 - 20, 9);
+    w.put(1, 1);
+    w.put(1, 1);
+    // A position code that is never used.
+    w.put(0, 4);
+    w.put(0, 4);
+    for bit in [0, 1, 1, 0] {
+        w.put(bit, 1);
+    }
+
+    // A block repeating the last byte with single symbol codes: a match
+    // of length 4 at distance 1.
+    w.put(2, 16);
+    w.put(0, 5);
+    w.put(0, 5);
+    w.put(0, 9);
+    w.put(256 + 4 - 3, 9);
+    w.put(0, 4);
+    w.put(0, 4);
+
+    let data = b"ABBAAAAAAAAA";
+    let src = lha_level0("LH5", b"-lh5-", &w.out, data);
+    let members = archive::members(&src).unwrap();
+    assert_eq!(members[0].data.as_ref().unwrap(), data);
+
+    // Losing the end of the data must fail cleanly.
+    let src = lha_level0("LH5", b"-lh5-", &w.out[..6], data);
+    assert!(archive::members(&src).unwrap()[0].data.is_err());
+}
+
+#[test]
+fn unsupported_lha_methods() {
+    let src = lha_level0("OLD", b"-lh1-", b"\x12\x34", b"ab");
+    let members = archive::members(&src).unwrap();
+    let err = members[0].data.as_ref().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains("-lh1-"), "{err}");
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn archives_are_extracted_member_by_member() {
+    use exxos_kult_extract::extract::{Event, Extractor};
+
+    let dir = scratch_dir("archive");
+    let sheet = sprite_sheet(&[interleaved_frame(1, 2, |x, _| x as u8)]);
+    let input = dir.join("KULT.ZIP");
+    fs::write(
+        &input,
+        zip(&[("SPR.EGA", 8, &sheet), ("KULT.EXE", 0, b"MZ")]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut failed = Vec::new();
+    Extractor::new(&out)
+        .on_event(|e| {
+            if let Event::FileFailed { path, .. } = e {
+                failed.push(path.to_path_buf());
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    assert!(out.join("SPR-00.png").exists());
+    assert_eq!(failed, [input.join("KULT.EXE")]);
+}
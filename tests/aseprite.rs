@@ -0,0 +1,118 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::aseprite::write_aseprite;
+use exxos_kult_extract::ega::EGA_PAL;
+use exxos_kult_extract::format::IndexedFrame;
+
+fn word(data: &[u8], pos: usize) -> usize {
+    u16::from_le_bytes([data[pos], data[pos + 1]]) as usize
+}
+
+fn dword(data: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize
+}
+
+/// The chunks of each frame, as type and data.
+fn chunks(data: &[u8]) -> Vec<Vec<(usize, &[u8])>> {
+    let mut frames = Vec::new();
+    let mut pos = 128;
+    for _ in 0..word(data, 6) {
+        let end = pos + dword(data, pos);
+        assert_eq!(word(data, pos + 4), 0xf1fa);
+        let count = dword(data, pos + 12);
+        let mut chunk = pos + 16;
+        let mut chunks = Vec::new();
+        for _ in 0..count {
+            let size = dword(data, chunk);
+            chunks.push((word(data, chunk + 4), &data[chunk + 6..chunk + size]));
+            chunk += size;
+        }
+        assert_eq!(chunk, end);
+        frames.push(chunks);
+        pos = end;
+    }
+    assert_eq!(pos, data.len());
+    frames
+}
+
+fn frame(index: usize, width: usize, height: usize, color: u8) -> IndexedFrame {
+    IndexedFrame {
+        index,
+        width,
+        height,
+        pixels: vec![color; width * height],
+        hitboxes: Vec::new(),
+    }
+}
+
+#[test]
+fn frames_are_cels_on_one_layer() {
+    let frames = [frame(0, 4, 2, 3), frame(1, 8, 1, 5)];
+    let mut data = Vec::new();
+    write_aseprite(&mut data, &frames, &EGA_PAL, 100).unwrap();
+
+    assert_eq!(dword(&data, 0), data.len());
+    assert_eq!(word(&data, 4), 0xa5e0);
+    assert_eq!((word(&data, 8), word(&data, 10)), (8, 2));
+    // Indexed, with index 0 transparent and 16 colors.
+    assert_eq!(word(&data, 12), 8);
+    assert_eq!(data[28], 0);
+    assert_eq!(word(&data, 32), 16);
+    assert_eq!((data[34], data[35]), (5, 6));
+
+    let frames = chunks(&data);
+    assert_eq!(frames.len(), 2);
+    let types = |chunks: &[(usize, &[u8])]| chunks.iter().map(|c| c.0).collect::<Vec<_>>();
+    assert_eq!(types(&frames[0]), [0x2019, 0x2004, 0x2005]);
+    assert_eq!(types(&frames[1]), [0x2005]);
+
+    let palette = frames[0][0].1;
+    assert_eq!(dword(palette, 0), 16);
+    assert_eq!(&palette[20 + 2..20 + 6], EGA_PAL[0]);
+    assert_eq!(&palette[20 + 6 * 9 + 2..20 + 6 * 10], EGA_PAL[9]);
+
+    let layer = frames[0][1].1;
+    assert_eq!(word(layer, 0) & 8, 0);
+
+    let cel = frames[1][0].1;
+    assert_eq!((word(cel, 16), word(cel, 18)), (8, 1));
+    assert_eq!(&cel[20..], [5; 8]);
+}
+
+#[test]
+fn opaque_palettes_make_a_background_layer() {
+    let mut pal = EGA_PAL;
+    pal[0][3] = 0xff;
+    let mut data = Vec::new();
+    write_aseprite(&mut data, &[frame(0, 4, 1, 0)], &pal, 100).unwrap();
+    let layer = chunks(&data)[0][1].1;
+    assert_eq!(word(layer, 0) & 8, 8);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn sheets_are_extracted_to_aseprite_documents() {
+    use exxos_kult_extract::extract::{ExtractOptions, Extractor};
+
+    let dir = scratch_dir("aseprite");
+    let input = dir.join("SPR.EGA");
+    let sheet = sprite_sheet(&[
+        interleaved_frame(1, 2, |x, _| x as u8),
+        interleaved_frame(1, 2, |_, y| y as u8),
+    ]);
+    std::fs::write(&input, sheet).unwrap();
+
+    let out = dir.join("png");
+    std::fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().aseprite(true))
+        .extract_file(&input)
+        .unwrap();
+
+    let data = std::fs::read(out.join("SPR.aseprite")).unwrap();
+    let frames = chunks(&data);
+    assert_eq!(frames.len(), 2);
+    let cel = frames[1].last().unwrap().1;
+    assert_eq!(&cel[20..], [0, 0, 0, 0, 1, 1, 1, 1]);
+}
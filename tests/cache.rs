@@ -0,0 +1,70 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use exxos_kult_extract::cache::SpriteSheetCache;
+use exxos_kult_extract::format::{Extract, Sprites};
+use exxos_kult_extract::sprite::SizeOrder;
+
+fn sheet(color: u8) -> Vec<u8> {
+    sprite_sheet(&[
+        interleaved_frame(1, 2, |_, _| color),
+        interleaved_frame(2, 1, |x, _| x as u8),
+    ])
+}
+
+#[test]
+fn sheets_are_decoded_once() {
+    let mut cache = SpriteSheetCache::new(4);
+    let src = sheet(3);
+
+    let first = cache.get(&src).unwrap();
+    assert_eq!(first.size_order, SizeOrder::BigEndian);
+    assert_eq!(first.frames.len(), 2);
+    assert_eq!(first.frames[0].pixels, [3; 8]);
+    assert_eq!(first.frames[1].width, 8);
+    let extracted = Sprites::new().extract(&src).unwrap();
+    for (cached, frame) in first.frames.iter().zip(&extracted.frames) {
+        assert_eq!((cached.width, &cached.pixels), (frame.width, &frame.pixels));
+    }
+
+    // The same contents from another buffer are found too.
+    let again = cache.get(&src.clone()).unwrap();
+    assert!(Arc::ptr_eq(&first, &again));
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn least_recently_used_sheets_are_dropped() {
+    let mut cache = SpriteSheetCache::new(2);
+    let (a, b, c) = (sheet(1), sheet(2), sheet(3));
+
+    let cached_a = cache.get(&a).unwrap();
+    let cached_b = cache.get(&b).unwrap();
+    // Using a again makes b the least recently used.
+    assert!(Arc::ptr_eq(&cached_a, &cache.get(&a).unwrap()));
+    cache.get(&c).unwrap();
+    assert_eq!(cache.len(), 2);
+
+    assert!(Arc::ptr_eq(&cached_a, &cache.get(&a).unwrap()));
+    assert!(!Arc::ptr_eq(&cached_b, &cache.get(&b).unwrap()));
+
+    cache.set_capacity(1);
+    assert_eq!(cache.len(), 1);
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn invalid_sheets_are_not_cached() {
+    let mut cache = SpriteSheetCache::new(2);
+    let mut src = sheet(1);
+    src.pop();
+    assert!(cache.get(&src).is_err());
+    assert!(cache.is_empty());
+
+    let mut cache = SpriteSheetCache::new(0);
+    cache.get(&sheet(1)).unwrap();
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,60 @@
+use exxos_kult_extract::catalog::{content_hash, slug, Catalog};
+
+#[test]
+fn looks_up_names_and_hashes() {
+    let src = b"pixels";
+    let text = format!(
+        "# Sprites\n\
+         [files]\n\
+         SPR07.EGA = \"Raven idle animation\"\n\
+         \"fnv:{:016x}\" = \"Title screen\"\n",
+        content_hash(src)
+    );
+    let catalog = Catalog::parse(&text).unwrap();
+
+    assert_eq!(
+        catalog.lookup("spr07.ega", b"other"),
+        Some("Raven idle animation")
+    );
+    // The contents win over the name.
+    assert_eq!(catalog.lookup("SPR07.EGA", src), Some("Title screen"));
+    assert_eq!(catalog.lookup("SPR08.EGA", b"other"), None);
+
+    let mut merged = Catalog::builtin();
+    merged.merge(catalog);
+    assert!(!merged.is_empty());
+
+    assert!(Catalog::parse("SPR07.EGA = Raven").is_err());
+    assert!(Catalog::parse("\"fnv:xyz\" = \"Raven\"").is_err());
+}
+
+#[test]
+fn slugs_are_filename_safe() {
+    assert_eq!(slug("Raven idle animation"), "raven_idle_animation");
+    assert_eq!(slug("  Door (open/closed) #2 "), "door_open_closed_2");
+}
+
+#[test]
+fn entries_can_give_the_size_of_padded_screens() {
+    let catalog = Catalog::parse(
+        "OVERLAY.EGA = \"Inventory overlay\" size=160x200\n\
+         TITLE.EGA = \"Title screen\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(catalog.size("overlay.ega", b""), Some((160, 200)));
+    assert_eq!(
+        catalog.lookup("OVERLAY.EGA", b""),
+        Some("Inventory overlay")
+    );
+    assert_eq!(catalog.size("TITLE.EGA", b""), None);
+    assert_eq!(catalog.size("OTHER.EGA", b""), None);
+
+    for entry in [
+        "OVERLAY.EGA = \"Overlay\" size=161x200",
+        "OVERLAY.EGA = \"Overlay\" size=160",
+        "OVERLAY.EGA = \"Overlay\" width=160",
+    ] {
+        assert!(Catalog::parse(entry).is_err(), "{entry}");
+    }
+}
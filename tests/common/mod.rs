@@ -0,0 +1,134 @@
+//! Synthetic fixtures and golden image helpers shared by the integration
+//! tests.
+//!
+//! Golden images are stored as text, one hex digit per palette index and
+//! one line per row, so that differences show up in a plain diff. Run the
+//! tests with `UPDATE_GOLDEN=1` to (re)write them.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use exxos_kult_extract::ega::{self, EGA_PAL};
+
+/// Encodes a 320x200 image given as palette indices into four planes.
+pub fn planar_fullscreen<F: Fn(usize, usize) -> u8>(f: F) -> Vec<u8> {
+    planar_image(ega::FULLSCREEN_WIDTH, ega::FULLSCREEN_HEIGHT, f)
+}
+
+/// Encodes an image given as palette indices into four planes.
+pub fn planar_image<F: Fn(usize, usize) -> u8>(width: usize, height: usize, f: F) -> Vec<u8> {
+    let plane_size = width * height / 8;
+
+    let mut out = vec![0u8; 4 * plane_size];
+    for y in 0..height {
+        for x in 0..width {
+            let v = f(x, y);
+            let ofs = (y * width + x) / 8;
+            let bit = 7 - x % 8;
+            for plane in 0..4 {
+                out[plane * plane_size + ofs] |= ((v >> plane) & 1) << bit;
+            }
+        }
+    }
+    out
+}
+
+/// Encodes one sprite sheet frame, including its header. The header width
+/// counts 16-bit words, so `words` is a quarter of the pixel width.
+pub fn interleaved_frame<F: Fn(usize, usize) -> u8>(words: u8, height: u8, f: F) -> Vec<u8> {
+    let span = 2 * words as usize;
+    let size = 4 + span * height as usize;
+
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&(size as u16).to_le_bytes());
+    out.push(words);
+    out.push(height);
+
+    for y in 0..height as usize {
+        for x in 0..span {
+            out.push((f(2 * x, y) << 4) | f(2 * x + 1, y));
+        }
+    }
+    out
+}
+
+/// Wraps encoded frames in a sprite sheet with a correct size header.
+pub fn sprite_sheet(frames: &[Vec<u8>]) -> Vec<u8> {
+    let body = frames.concat();
+    let mut out = (body.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Maps RGBA pixels back to palette indices.
+pub fn rgba_to_indices(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks(4)
+        .map(|px| {
+            EGA_PAL
+                .iter()
+                .position(|c| c == px)
+                .unwrap_or_else(|| panic!("{px:?} is not a palette color")) as u8
+        })
+        .collect()
+}
+
+pub fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"))
+}
+
+fn indices_to_text(indices: &[u8], width: usize) -> String {
+    let mut out = String::new();
+    for row in indices.chunks(width) {
+        for v in row {
+            out.push(char::from_digit(*v as u32, 16).unwrap());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Compares decoded RGBA pixels against the named golden image.
+pub fn assert_golden(name: &str, rgba: &[u8], width: usize) {
+    let actual = indices_to_text(&rgba_to_indices(rgba), width);
+    let path = golden_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}, run with UPDATE_GOLDEN=1", path.display()));
+
+    if actual != expected {
+        let row = actual
+            .lines()
+            .zip(expected.lines())
+            .position(|(a, e)| a != e)
+            .unwrap_or(0);
+        panic!("{name} differs from golden image, first at row {row}");
+    }
+}
+
+/// A fresh, empty directory for a test to write into.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("exxos-kult-extract-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Reads a PNG written by the extractor, returning its RGBA data and size.
+pub fn read_png(path: &Path) -> (Vec<u8>, usize, usize) {
+    let decoder = png::Decoder::new(fs::File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+    (buf, info.width as usize, info.height as usize)
+}
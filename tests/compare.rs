@@ -0,0 +1,87 @@
+mod common;
+
+use exxos_kult_extract::compare::compare;
+
+fn image(width: usize, height: usize, f: impl Fn(usize, usize) -> [u8; 4]) -> Vec<u8> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| f(x, y))
+        .collect()
+}
+
+fn checkers(x: usize, y: usize) -> [u8; 4] {
+    if (x + y).is_multiple_of(2) {
+        [0xaa, 0x55, 0x00, 0xff]
+    } else {
+        [0x55, 0xff, 0xff, 0xff]
+    }
+}
+
+#[test]
+fn identical_images_match() {
+    let decoded = image(4, 3, checkers);
+    let comparison = compare(&decoded, 4, 3, &decoded, 4, 3).unwrap();
+    assert_eq!(comparison.scale, (1, 1));
+    assert!(comparison.differences.is_empty());
+    assert_eq!(comparison.heatmap.len(), 4 * 4 * 3);
+}
+
+#[test]
+fn scaled_screenshots_are_compared_pixel_by_pixel() {
+    let decoded = image(4, 3, checkers);
+    let mut screenshot = image(8, 6, |x, y| checkers(x / 2, y / 2));
+    assert!(compare(&decoded, 4, 3, &screenshot, 8, 6)
+        .unwrap()
+        .differences
+        .is_empty());
+
+    // One of the four screenshot pixels showing pixel 1,2 is off.
+    let pos = 4 * (5 * 8 + 3);
+    screenshot[pos..pos + 3].copy_from_slice(&[0x55, 0xff, 0xef]);
+    let comparison = compare(&decoded, 4, 3, &screenshot, 8, 6).unwrap();
+    assert_eq!(comparison.scale, (2, 2));
+    assert_eq!(comparison.differences.len(), 1);
+    let difference = comparison.differences[0];
+    assert_eq!((difference.x, difference.y), (1, 2));
+    assert_eq!(difference.expected, [0x55, 0xff, 0xff]);
+    assert_eq!(difference.actual, [0x55, 0xff, 0xef]);
+    assert_eq!(difference.amount(), 0x10);
+
+    let pos = 4 * (2 * 4 + 1);
+    assert_eq!(&comparison.heatmap[pos..pos + 4], [0xff, 0x10, 0, 0xff]);
+    let gray = comparison.heatmap[0];
+    assert_eq!(&comparison.heatmap[..4], [gray, gray, gray, 0xff]);
+}
+
+#[test]
+fn screenshots_must_be_whole_multiples() {
+    let decoded = image(4, 3, checkers);
+    let screenshot = image(6, 6, checkers);
+    let error = compare(&decoded, 4, 3, &screenshot, 6, 6).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "the screenshot is 6x6, not a multiple of the 4x3 image"
+    );
+    assert!(compare(&decoded, 4, 3, &screenshot[..4 * 2 * 3], 2, 3).is_err());
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn decoded_screens_compare_with_their_own_png() {
+    use common::*;
+    use exxos_kult_extract::extract::Extractor;
+
+    let dir = scratch_dir("compare");
+    let src = planar_fullscreen(|x, y| ((x / 8 + y) % 16) as u8);
+    let mut extractor = Extractor::new(&dir);
+    let (decoded, width, height) = extractor.extract_rgba("PIC.EGA", &src).unwrap();
+    assert_eq!((width, height), (320, 200));
+
+    let png = dir.join("PIC.png");
+    let mut file = std::fs::File::create(&png).unwrap();
+    extractor.extract_png("PIC.EGA", &src, &mut file).unwrap();
+    drop(file);
+    let (screenshot, sw, sh) = read_png(&png);
+    let comparison = compare(&decoded, width, height, &screenshot, sw, sh).unwrap();
+    assert!(comparison.differences.is_empty());
+}
@@ -0,0 +1,74 @@
+use exxos_kult_extract::config::{negation, Config};
+
+const OPTIONS: [&str; 3] = ["output", "engine", "gamma"];
+const FLAGS: [&str; 3] = ["native", "no-remap", "quiet"];
+
+#[test]
+fn settings_become_arguments() {
+    let config = Config::parse(
+        "# Pipeline defaults\n\
+         output = \"assets/kult\"  # relative to the game\n\
+         engine = \"godot\"\n\
+         gamma = 1.2 # brighter\n\
+         native = true\n\
+         quiet = false\n\
+         remap = false\n",
+    )
+    .unwrap();
+
+    let args = config.to_args(&OPTIONS, &FLAGS).unwrap();
+    assert_eq!(
+        args,
+        [
+            "--output",
+            "assets/kult",
+            "--engine",
+            "godot",
+            "--gamma",
+            "1.2",
+            "--native",
+            "--no-remap"
+        ]
+    );
+
+    // Flags at their default add nothing.
+    let config = Config::parse("remap = true").unwrap();
+    assert!(config.to_args(&OPTIONS, &FLAGS).unwrap().is_empty());
+    assert!(Config::parse("").unwrap().is_empty());
+}
+
+#[test]
+fn invalid_settings() {
+    for text in [
+        "native",
+        "output = \"png",
+        "output = \"png\" png",
+        "= true",
+        "gamma =",
+        "gamma = # none",
+        "[extract]",
+        "frames = [1, 2]",
+        "a.b = true",
+    ] {
+        assert!(Config::parse(text).is_err(), "{text}");
+    }
+
+    for (text, message) in [
+        ("\n\nstdout = true", "line 3: unknown option `stdout`"),
+        ("output = true", "`output` requires a value"),
+        ("native = \"yes\"", "`native` requires true or false"),
+        ("remap = 1", "`remap` requires true or false"),
+    ] {
+        let err = Config::parse(text)
+            .unwrap()
+            .to_args(&OPTIONS, &FLAGS)
+            .unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+    }
+}
+
+#[test]
+fn flags_are_negated_with_no() {
+    assert_eq!(negation("strict"), "no-strict");
+    assert_eq!(negation("no-remap"), "remap");
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::debug::{annotate, SpanKind};
+use exxos_kult_extract::format::FormatRegistry;
+
+fn kinds_and_starts(src: &[u8]) -> Vec<(SpanKind, usize)> {
+    annotate(src, &FormatRegistry::default())
+        .spans
+        .iter()
+        .map(|s| (s.kind, s.start))
+        .collect()
+}
+
+#[test]
+fn sheets_are_split_into_frames() {
+    let mut frame = interleaved_frame(1, 2, |x, _| x as u8);
+    frame.extend_from_slice(&[0, 0, 2, 1]);
+    frame[0] += 4;
+    let mut src = sprite_sheet(&[frame]);
+    src.extend_from_slice(&[0, 0]);
+    src[3] += 2;
+
+    assert_eq!(
+        kinds_and_starts(&src),
+        [
+            (SpanKind::Header, 0),
+            (SpanKind::FrameHeader, 4),
+            (SpanKind::Pixels, 8),
+            (SpanKind::Trailer, 12),
+            (SpanKind::Padding, 16),
+        ]
+    );
+
+    let annotated = annotate(&src, &FormatRegistry::default());
+    assert!(annotated
+        .findings
+        .iter()
+        .any(|f| f == "sprites: detected, sprite sheet of interleaved EGA frames"));
+    assert_eq!(annotated.spans[3].label, "frame 0 trailer, 1 hit boxes");
+}
+
+#[test]
+fn problems_are_pointed_out() {
+    let mut src = sprite_sheet(&[interleaved_frame(1, 1, |_, _| 1)]);
+    // A frame claiming more than is left, and a wrong sheet size.
+    src.extend_from_slice(&[0x40, 0, 1, 1, 0xff]);
+    src.extend_from_slice(&[0xee; 3]);
+    let declared = (src.len() - 4 - 3) as u32;
+    src[..4].copy_from_slice(&declared.to_be_bytes());
+
+    let annotated = annotate(&src, &FormatRegistry::default());
+    let kinds = annotated.spans.iter().map(|s| s.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            SpanKind::Problem,
+            SpanKind::FrameHeader,
+            SpanKind::Pixels,
+            SpanKind::Problem,
+            SpanKind::Problem,
+        ]
+    );
+    assert!(annotated.spans[4].label.contains("3 bytes beyond"));
+    assert!(annotated
+        .findings
+        .iter()
+        .any(|f| f.contains("frame 1 at 0xa: frame size 64 exceeds")));
+}
+
+#[test]
+fn screens_show_planes_and_registers() {
+    let mut src = planar_fullscreen(|x, _| (x % 16) as u8);
+    src.extend_from_slice(&[0, 1, 2, 3, 4, 5, 20, 7, 56, 57, 58, 59, 60, 61, 62, 63]);
+
+    let spans = annotate(&src, &FormatRegistry::default()).spans;
+    assert_eq!(spans.len(), 5);
+    assert_eq!(spans[1].start, 8000);
+    assert_eq!(spans[4].kind, SpanKind::Header);
+    assert!(spans[4].label.ends_with("4 5 20 7 56 57 58 59 60 61 62 63"));
+}
+
+#[test]
+fn long_spans_are_shortened() {
+    let src = planar_fullscreen(|x, y| ((x + y) % 16) as u8);
+    let annotated = annotate(&src, &FormatRegistry::default());
+
+    let mut out = Vec::new();
+    annotated
+        .write_hexdump(&mut out, &src, false, false)
+        .unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(!text.contains('\x1b'));
+    assert!(text.contains("00001f40  plane 1 of a 320x200 screen"));
+    assert!(text.contains("7952 more bytes"), "{text}");
+
+    let mut out = Vec::new();
+    annotated.write_hexdump(&mut out, &src, true, true).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains('\x1b'));
+    assert!(!text.contains("more bytes"));
+    assert_eq!(
+        text.lines().filter(|l| l.starts_with("  0000")).count(),
+        2000
+    );
+}
@@ -0,0 +1,363 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::ega::{
+    self, decode_planar_ega_to_indexed, decode_planar_ega_to_rgba, indexed_to_rgba,
+    indexed_to_rgba_with_palette, palette, Monitor, Transform, DEFAULT_PALETTE_REGISTERS, EGA_PAL,
+};
+use exxos_kult_extract::format::{FormatRegistry, Problem};
+use exxos_kult_extract::hercules::{Phosphor, HERCULES_HEIGHT, HERCULES_SIZE, HERCULES_WIDTH};
+use exxos_kult_extract::palette::{EGA_5153, EGA_GENERIC, VGA_DAC};
+use exxos_kult_extract::sprite::{Entry, SizeOrder, SpriteSheet, FRAME_HEADER_SIZE, MAX_HITBOXES};
+
+fn diagonal_stripes(x: usize, y: usize) -> u8 {
+    ((x / 3 + y / 2) % 16) as u8
+}
+
+#[test]
+fn fullscreen_roundtrip() {
+    let src = planar_fullscreen(diagonal_stripes);
+    let rgba = decode_planar_ega_to_rgba(&src, ega::FULLSCREEN_WIDTH, ega::FULLSCREEN_HEIGHT);
+
+    let indices = rgba_to_indices(&rgba);
+    for y in 0..ega::FULLSCREEN_HEIGHT {
+        for x in 0..ega::FULLSCREEN_WIDTH {
+            assert_eq!(
+                indices[y * ega::FULLSCREEN_WIDTH + x],
+                diagonal_stripes(x, y),
+                "pixel {x},{y}"
+            );
+        }
+    }
+}
+
+#[test]
+fn fullscreen_plane_order() {
+    // Plane 0 is the least significant bit.
+    let mut src = vec![0u8; ega::FULLSCREEN_SIZE];
+    src[0] = 0x80;
+    src[2 * 8000] = 0x40;
+    src[3 * 8000] = 0x40;
+
+    let rgba = decode_planar_ega_to_rgba(&src, ega::FULLSCREEN_WIDTH, ega::FULLSCREEN_HEIGHT);
+    let indices = rgba_to_indices(&rgba[..4 * 8]);
+
+    assert_eq!(indices, [1, 12, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn high_resolution_layouts() {
+    for (width, height) in [(640, 200), (640, 350)] {
+        let src = planar_image(width, height, diagonal_stripes);
+        assert_eq!(ega::fullscreen_layout(src.len()), Some((width, height)));
+
+        let indices = rgba_to_indices(&decode_planar_ega_to_rgba(&src, width, height));
+        let last = width * height - 1;
+        assert_eq!(indices[last], diagonal_stripes(width - 1, height - 1));
+        assert_eq!(indices[width], diagonal_stripes(0, 1));
+    }
+}
+
+#[test]
+fn sprite_sheet_golden() {
+    let src = sprite_sheet(&[
+        interleaved_frame(2, 8, |x, y| ((x + y) % 16) as u8),
+        interleaved_frame(1, 4, |x, _| if x % 2 == 0 { 15 } else { 0 }),
+        interleaved_frame(3, 2, |x, y| (x * y % 16) as u8),
+    ]);
+
+    let sheet = SpriteSheet::parse(&src).unwrap();
+    let frames = sheet.frames().collect::<Vec<_>>();
+
+    let sizes = frames
+        .iter()
+        .map(|f| (f.index, f.width, f.height))
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [(0, 8, 8), (1, 4, 4), (2, 12, 2)]);
+
+    for frame in &frames {
+        assert_golden(
+            &format!("sheet-frame-{}", frame.index),
+            &frame.to_rgba(),
+            frame.width,
+        );
+    }
+}
+
+#[test]
+fn truncated_sheet_header() {
+    assert!(SpriteSheet::parse(&[0, 0, 0]).is_err());
+
+    let mut src = sprite_sheet(&[interleaved_frame(1, 1, |_, _| 1)]);
+    src.pop();
+    assert!(SpriteSheet::parse(&src).is_err());
+}
+
+#[test]
+fn little_endian_sheet_size() {
+    let mut src = sprite_sheet(&[interleaved_frame(1, 1, |_, _| 1)]);
+    assert_eq!(
+        SpriteSheet::parse(&src).unwrap().size_order(),
+        SizeOrder::BigEndian
+    );
+
+    src[..4].reverse();
+    let sheet = SpriteSheet::parse(&src).unwrap();
+    assert_eq!(sheet.size_order(), SizeOrder::LittleEndian);
+    assert_eq!(sheet.frames().count(), 1);
+
+    let (_, extracted) = FormatRegistry::default().extract(&src).unwrap();
+    assert_eq!(extracted.frames.len(), 1);
+    assert_eq!(extracted.notes, ["Sheet size is little-endian."]);
+    assert_eq!(extracted.problems, [Problem::LittleEndianSize]);
+}
+
+#[test]
+fn trailers_without_plausible_hitboxes_have_none() {
+    let with_trailer = |trailer: &[u8]| {
+        let mut frame = interleaved_frame(2, 4, |_, _| 3);
+        frame.extend_from_slice(trailer);
+        let size = frame.len() as u16;
+        frame[..2].copy_from_slice(&size.to_le_bytes());
+        sprite_sheet(&[frame])
+    };
+    let hitboxes = |src: &[u8]| {
+        let sheet = SpriteSheet::parse(src).unwrap();
+        let frame = sheet.frames().next().unwrap();
+        frame.hitboxes()
+    };
+
+    assert_eq!(hitboxes(&with_trailer(&[1, 0, 2, 1])).len(), 1);
+    // Zero padding, a box of no size, too many boxes.
+    assert!(hitboxes(&with_trailer(&[0; 8])).is_empty());
+    assert!(hitboxes(&with_trailer(&[1, 0, 2, 1, 2, 2, 0, 1])).is_empty());
+    assert!(hitboxes(&with_trailer(&[1, 0, 1, 1].repeat(MAX_HITBOXES + 1))).is_empty());
+
+    let (_, extracted) = FormatRegistry::default()
+        .extract(&with_trailer(&[1, 0, 2, 1]))
+        .unwrap();
+    assert!(extracted.frames[0].hitboxes.is_empty());
+}
+
+#[test]
+fn truncated_last_frame_stops() {
+    let mut body = interleaved_frame(1, 2, |_, _| 3);
+    let mut last = interleaved_frame(1, 8, |_, _| 4);
+    last.truncate(10);
+    body.extend_from_slice(&last);
+
+    let mut src = (body.len() as u32).to_be_bytes().to_vec();
+    src.extend_from_slice(&body);
+
+    let sheet = SpriteSheet::parse(&src).unwrap();
+    let entries = sheet.entries().collect::<Vec<_>>();
+
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(entries[0], Entry::Frame(_)));
+    assert!(matches!(entries[1], Entry::Stopped { index: 1, .. }));
+
+    let (_, extracted) = FormatRegistry::default().extract(&src).unwrap();
+    assert_eq!(
+        extracted.problems,
+        [Problem::Frame {
+            index: 1,
+            reason: "frame size 20 exceeds remaining data".to_string(),
+            stopped: true,
+        }]
+    );
+}
+
+#[test]
+fn trailing_padding_ends_sheet() {
+    for padding in 1..FRAME_HEADER_SIZE {
+        let mut body = interleaved_frame(1, 2, |_, _| 3);
+        body.extend(std::iter::repeat_n(0, padding));
+
+        let mut src = (body.len() as u32).to_be_bytes().to_vec();
+        src.extend_from_slice(&body);
+
+        let sheet = SpriteSheet::parse(&src).unwrap();
+        let entries = sheet.entries().collect::<Vec<_>>();
+
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[0], Entry::Frame(_)));
+        assert!(matches!(entries[1], Entry::Padding { len } if len == padding));
+
+        let (_, extracted) = FormatRegistry::default().extract(&src).unwrap();
+        assert_eq!(extracted.frames.len(), 1);
+        assert!(extracted.warnings.is_empty());
+        assert_eq!(extracted.notes.len(), 1);
+    }
+}
+
+#[test]
+fn screens_with_palette_prefix() {
+    let mut registers = DEFAULT_PALETTE_REGISTERS;
+    registers[1] = 4;
+    let words = registers
+        .iter()
+        .flat_map(|&v| (v as u16).to_le_bytes())
+        .collect::<Vec<_>>();
+    let screen = planar_fullscreen(|x, _| (x % 2) as u8);
+    let registry = FormatRegistry::default();
+
+    for header in [&[][..], &[0, 0]] {
+        let src = [header, &words, &screen].concat();
+
+        let (format, extracted) = registry.extract(&src).unwrap();
+        assert_eq!(format.name(), "prefixed-screen");
+        assert_eq!(extracted.remap, Some(registers));
+        assert_eq!(extracted.frames[0].width, ega::FULLSCREEN_WIDTH);
+        assert_eq!(&extracted.frames[0].pixels[..4], [0, 1, 0, 1]);
+    }
+
+    // Words too large for a palette register are no palette.
+    let mut bad = words.clone();
+    bad[3] = 1;
+    let src = [&bad, &screen[..]].concat();
+    assert!(registry.extract(&src).is_err());
+}
+
+#[test]
+fn palette_transparency() {
+    let rgba = indexed_to_rgba(&[0, 8, 15]);
+
+    assert_eq!(rgba[3], 0, "index 0 is transparent");
+    assert_eq!(&rgba[4..8], [0, 0, 0, 0xff], "index 8 is opaque black");
+    assert_eq!(&rgba[8..12], EGA_PAL[15]);
+}
+
+#[test]
+fn color_transforms() {
+    let gray = Transform::Grayscale.apply(&EGA_PAL);
+    for [r, g, b, _] in gray {
+        assert!(r == g && g == b);
+    }
+    assert_eq!(gray[15], [0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(gray[0][3], 0, "alpha is kept");
+
+    // Red and green both become shades of the same yellow.
+    let mut pal = EGA_PAL;
+    pal[1] = [0xff, 0x00, 0x00, 0xff];
+    pal[2] = [0x00, 0xff, 0x00, 0xff];
+    for transform in [Transform::Protanopia, Transform::Deuteranopia] {
+        let t = transform.apply(&pal);
+        let ratio = |[r, g, b, _]: [u8; 4]| {
+            assert!(r >= g && g > b);
+            g as f64 / r as f64
+        };
+        assert!((ratio(t[1]) - ratio(t[2])).abs() < 0.05);
+        assert_eq!(t[15], [0xff, 0xff, 0xff, 0xff]);
+    }
+}
+
+#[test]
+fn standard_palettes_match_the_monitors() {
+    let standard = [
+        (Monitor::Ibm5153, EGA_5153),
+        (Monitor::GenericEga, EGA_GENERIC),
+        (Monitor::VgaDac, VGA_DAC),
+    ];
+    for (monitor, pal) in standard {
+        assert_eq!(palette(monitor, &DEFAULT_PALETTE_REGISTERS, 1.0), pal);
+    }
+    assert_eq!(EGA_5153, EGA_PAL);
+    assert_eq!(VGA_DAC[6], [0xa8, 0x54, 0x00, 0xff]);
+    assert_eq!(EGA_GENERIC[6], [0xaa, 0xaa, 0x00, 0xff]);
+}
+
+#[test]
+fn layouts_with_padded_planes() {
+    assert_eq!(ega::FULLSCREEN_SIZE, 32000);
+    assert_eq!(ega::HIRES_350.size(), 112000);
+
+    let packed = planar_fullscreen(diagonal_stripes);
+    let layout = ega::FULLSCREEN.with_plane_size(8192);
+    let mut padded = vec![0; layout.size()];
+    for (plane, data) in packed.chunks(8000).enumerate() {
+        padded[plane * 8192..plane * 8192 + 8000].copy_from_slice(data);
+    }
+    assert_eq!(
+        layout.decode_to_indexed(&padded),
+        decode_planar_ega_to_indexed(&packed, 320, 200)
+    );
+    assert_eq!(
+        layout.decode_to_rgba(&padded, &VGA_DAC),
+        indexed_to_rgba_with_palette(&layout.decode_to_indexed(&padded), &VGA_DAC)
+    );
+}
+
+#[test]
+fn monitor_profiles() {
+    // Dark yellow, which the 5153 shows as brown.
+    assert_eq!(Monitor::Ibm5153.color(6), [0xc4, 0x7e, 0x00]);
+    assert_eq!(Monitor::GenericEga.color(6), [0xaa, 0xaa, 0x00]);
+    assert_eq!(Monitor::VgaDac.color(6), [0xa8, 0x54, 0x00]);
+    // Bit 4 is intensity.
+    assert_eq!(Monitor::GenericEga.color(16 + 1), [0x55, 0x55, 0xff]);
+    assert_eq!(Monitor::VgaDac.color(16 + 7), [0xfc, 0xfc, 0xfc]);
+
+    let pal = palette(Monitor::Ibm5153, &DEFAULT_PALETTE_REGISTERS, 1.0);
+    assert_eq!(pal, EGA_PAL);
+
+    let brighter = palette(Monitor::Ibm5153, &DEFAULT_PALETTE_REGISTERS, 2.0);
+    assert!(brighter[1][2] > EGA_PAL[1][2]);
+    assert_eq!(brighter[15], EGA_PAL[15]);
+}
+
+#[test]
+fn hercules_bank_interleave() {
+    let mut src = vec![0u8; HERCULES_SIZE];
+    src[0] = 0x80;
+    src[0x2000] = 0x01;
+    src[0x6000 + 89] = 0x01;
+    src[90] = 0x40;
+
+    let registry = FormatRegistry::default();
+    let (format, extracted) = registry.extract(&src).unwrap();
+    assert_eq!(format.name(), "hercules");
+    assert!(extracted.monochrome);
+
+    let frame = &extracted.frames[0];
+    assert_eq!(
+        (frame.width, frame.height),
+        (HERCULES_WIDTH, HERCULES_HEIGHT)
+    );
+    let lit = frame
+        .pixels
+        .iter()
+        .enumerate()
+        .filter(|&(_, &p)| p == 1)
+        .map(|(i, _)| (i % HERCULES_WIDTH, i / HERCULES_WIDTH))
+        .collect::<Vec<_>>();
+    assert_eq!(lit, [(0, 0), (7, 1), (719, 3), (1, 4)]);
+}
+
+#[test]
+fn sheets_of_hercules_size_are_sheets() {
+    let src = sprite_sheet(&[
+        interleaved_frame(64, 255, |x, y| ((x + y) % 16) as u8),
+        interleaved_frame(1, 58, |x, _| x as u8),
+    ]);
+    assert_eq!(src.len(), HERCULES_SIZE);
+
+    let registry = FormatRegistry::default();
+    let (format, extracted) = registry.extract(&src).unwrap();
+    assert_eq!(format.name(), "sprites");
+    assert_eq!(extracted.frames.len(), 2);
+}
+
+#[test]
+fn phosphor_palettes() {
+    assert_eq!(Phosphor::default(), Phosphor::Green);
+    for (name, color) in [
+        ("green", [0x33, 0xff, 0x00, 0xff]),
+        ("amber", [0xff, 0xb0, 0x00, 0xff]),
+        ("white", [0xff, 0xff, 0xff, 0xff]),
+    ] {
+        let pal = Phosphor::from_name(name).unwrap().palette();
+        assert_eq!(pal[0], [0x00, 0x00, 0x00, 0xff]);
+        assert_eq!(pal[1], color);
+    }
+    assert_eq!(Phosphor::from_name("blue"), None);
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "disk-images")]
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use exxos_kult_extract::disk::{self, DiskKind};
+
+const SECTOR: usize = 512;
+// One reserved sector, two FATs of one sector and one sector of root
+// directory, so cluster 2 starts at sector 4.
+const DATA_START: usize = 4 * SECTOR;
+
+/// A small FAT12 image of 40 sectors with one sector per cluster.
+struct Fat12Image {
+    image: Vec<u8>,
+    next_cluster: usize,
+}
+
+impl Fat12Image {
+    fn new(boot_jump: [u8; 3]) -> Fat12Image {
+        let mut image = vec![0; 40 * SECTOR];
+        image[..3].copy_from_slice(&boot_jump);
+        image[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+        image[13] = 1;
+        image[14..16].copy_from_slice(&1u16.to_le_bytes());
+        image[16] = 2;
+        image[17..19].copy_from_slice(&16u16.to_le_bytes());
+        image[19..21].copy_from_slice(&40u16.to_le_bytes());
+        image[21] = 0xf8;
+        image[22..24].copy_from_slice(&1u16.to_le_bytes());
+        Fat12Image {
+            image,
+            next_cluster: 2,
+        }
+    }
+
+    fn set_fat(&mut self, cluster: usize, value: usize) {
+        for fat in [SECTOR, 2 * SECTOR] {
+            let pos = fat + cluster + cluster / 2;
+            let old = u16::from_le_bytes([self.image[pos], self.image[pos + 1]]) as usize;
+            let new = if cluster.is_multiple_of(2) {
+                (old & 0xf000) | value
+            } else {
+                (old & 0x000f) | (value << 4)
+            };
+            self.image[pos..pos + 2].copy_from_slice(&(new as u16).to_le_bytes());
+        }
+    }
+
+    /// Stores `data` in clusters `skip` apart, to check the chain is
+    /// followed, and returns the first one.
+    fn store(&mut self, data: &[u8], skip: usize) -> usize {
+        let first = self.next_cluster;
+        let chunks = data.chunks(SECTOR).collect::<Vec<_>>();
+        for (n, chunk) in chunks.iter().enumerate() {
+            let cluster = self.next_cluster;
+            let pos = DATA_START + (cluster - 2) * SECTOR;
+            self.image[pos..pos + chunk.len()].copy_from_slice(chunk);
+            self.next_cluster += 1 + skip;
+            let next = if n + 1 == chunks.len() {
+                0xfff
+            } else {
+                self.next_cluster
+            };
+            self.set_fat(cluster, next);
+        }
+        first
+    }
+
+    fn entry(name: &[u8; 11], attr: u8, cluster: usize, size: usize) -> Vec<u8> {
+        let mut entry = name.to_vec();
+        entry.push(attr);
+        entry.extend_from_slice(&[0; 14]);
+        entry.extend_from_slice(&(cluster as u16).to_le_bytes());
+        entry.extend_from_slice(&(size as u32).to_le_bytes());
+        entry
+    }
+
+    fn set_root(&mut self, entries: &[Vec<u8>]) {
+        let root = entries.concat();
+        self.image[3 * SECTOR..3 * SECTOR + root.len()].copy_from_slice(&root);
+    }
+}
+
+fn sample_image(boot_jump: [u8; 3]) -> (Vec<u8>, Vec<u8>) {
+    let sheet = sprite_sheet(&[
+        interleaved_frame(8, 40, |x, _| x as u8 % 16),
+        interleaved_frame(8, 40, |_, y| y as u8 % 16),
+    ]);
+    assert!(sheet.len() > SECTOR);
+
+    let mut disk = Fat12Image::new(boot_jump);
+    let spr = disk.store(&sheet, 1);
+    let readme = disk.store(b"KULT", 0);
+    let subdir = [
+        Fat12Image::entry(b".          ", 0x10, 0, 0),
+        Fat12Image::entry(b"..         ", 0x10, 0, 0),
+        Fat12Image::entry(b"README  TXT", 0x20, readme, 4),
+    ]
+    .concat();
+    let gfx = disk.store(&subdir, 0);
+
+    disk.set_root(&[
+        Fat12Image::entry(b"KULT DISK 1", 0x08, 0, 0),
+        Fat12Image::entry(b"\xe5OLD    EGA", 0x20, readme, 4),
+        Fat12Image::entry(b"SPR07   EGA", 0x20, spr, sheet.len()),
+        Fat12Image::entry(b"EMPTY      ", 0x20, 0, 0),
+        Fat12Image::entry(b"GFX        ", 0x10, gfx, 0),
+    ]);
+    (disk.image, sheet)
+}
+
+#[test]
+fn fat12_images_are_read() {
+    for jump in [[0xeb, 0x3c, 0x90], [0x60, 0x1c, 0x00]] {
+        let (image, sheet) = sample_image(jump);
+        assert_eq!(disk::detect(&image), Some(DiskKind::Fat12));
+
+        let files = disk::files(&image).unwrap();
+        let names = files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["SPR07.EGA", "EMPTY", "GFX/README.TXT"]);
+        assert_eq!(files[0].data.as_ref().unwrap(), &sheet);
+        assert_eq!(files[1].data.as_ref().unwrap(), b"");
+        assert_eq!(files[2].data.as_ref().unwrap(), b"KULT");
+    }
+}
+
+#[test]
+fn other_files_are_not_disk_images() {
+    assert_eq!(disk::detect(&planar_fullscreen(|_, _| 3)), None);
+    assert_eq!(disk::detect(&vec![0; 40 * SECTOR]), None);
+
+    let (image, _) = sample_image([0xeb, 0x3c, 0x90]);
+    // Cut short of the sector count in the boot sector.
+    assert_eq!(disk::detect(&image[..20 * SECTOR]), None);
+}
+
+#[test]
+fn broken_chains_fail_only_their_file() {
+    let (mut image, _) = sample_image([0xeb, 0x3c, 0x90]);
+    // Break the chain of SPR07.EGA after its first cluster, 2.
+    image[SECTOR + 3] = 0;
+
+    let files = disk::files(&image).unwrap();
+    let error = files[0].data.as_ref().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "file is shorter than its directory entry"
+    );
+    assert_eq!(files[2].data.as_ref().unwrap(), b"KULT");
+}
+
+#[cfg(feature = "adf")]
+#[test]
+fn amiga_images_are_read() {
+    const ROOT: usize = 880;
+
+    fn put(image: &mut [u8], block: usize, pos: usize, v: u32) {
+        let pos = block * SECTOR + pos;
+        image[pos..pos + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    fn header(image: &mut [u8], block: usize, name: &str, sec_type: i32) {
+        put(image, block, 0, 2);
+        put(image, block, SECTOR - 4, sec_type as u32);
+        image[block * SECTOR + SECTOR - 80] = name.len() as u8;
+        let pos = block * SECTOR + SECTOR - 79;
+        image[pos..pos + name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    for ffs in [false, true] {
+        let mut image = vec![0; 901120];
+        image[..4].copy_from_slice(&[b'D', b'O', b'S', ffs as u8]);
+        header(&mut image, ROOT, "Kult", 1);
+
+        let data = (0..700).map(|v| v as u8).collect::<Vec<_>>();
+        header(&mut image, 882, "SPR07.EGA", -3);
+        put(&mut image, 882, SECTOR - 188, data.len() as u32);
+        let per_block = if ffs { SECTOR } else { SECTOR - 24 };
+        let blocks = data.chunks(per_block).collect::<Vec<_>>();
+        put(&mut image, 882, 8, blocks.len() as u32);
+        for (n, chunk) in blocks.iter().enumerate() {
+            let block = 890 + n;
+            put(&mut image, 882, 24 + 4 * (71 - n), block as u32);
+            let pos = if ffs {
+                block * SECTOR
+            } else {
+                put(&mut image, block, 0, 8);
+                put(&mut image, block, 12, chunk.len() as u32);
+                block * SECTOR + 24
+            };
+            image[pos..pos + chunk.len()].copy_from_slice(chunk);
+        }
+
+        header(&mut image, 883, "gfx", 2);
+        header(&mut image, 884, "a", -3);
+        put(&mut image, 883, 24, 884);
+        // Both hash to the same slot of the root, chained.
+        put(&mut image, ROOT, 24 + 4 * 5, 882);
+        put(&mut image, 882, SECTOR - 16, 883);
+
+        assert_eq!(disk::detect(&image), Some(DiskKind::Adf));
+        let files = disk::files(&image).unwrap();
+        let names = files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["SPR07.EGA", "gfx/a"]);
+        assert_eq!(files[0].data.as_ref().unwrap(), &data);
+        assert_eq!(files[1].data.as_ref().unwrap(), b"");
+    }
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn disk_images_are_extracted_file_by_file() {
+    use exxos_kult_extract::extract::{Event, Extractor};
+
+    let dir = scratch_dir("disk");
+    let input = dir.join("KULT.IMG");
+    let (image, _) = sample_image([0xeb, 0x3c, 0x90]);
+    fs::write(&input, image).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut failed = Vec::new();
+    Extractor::new(&out)
+        .on_event(|e| {
+            if let Event::FileFailed { path, .. } = e {
+                failed.push(path.to_path_buf());
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    assert!(out.join("SPR07-00.png").exists());
+    assert!(out.join("SPR07-01.png").exists());
+    assert_eq!(failed, [input.join("EMPTY"), input.join("GFX/README.TXT")]);
+}
@@ -0,0 +1,96 @@
+#![cfg(all(unix, feature = "png"))]
+
+mod common;
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use common::*;
+use exxos_kult_extract::exec::{ExecCommand, Executor};
+use exxos_kult_extract::extract::{Event, ExtractOptions, Extractor};
+
+fn args(command: &str, path: &str) -> Vec<OsString> {
+    ExecCommand::parse(command).unwrap().args(Path::new(path))
+}
+
+#[test]
+fn commands_are_split_like_a_shell_would() {
+    assert_eq!(
+        args("oxipng -o 4 {}", "a.png"),
+        ["oxipng", "-o", "4", "a.png"]
+    );
+    assert_eq!(args("optipng", "a.png"), ["optipng", "a.png"]);
+    assert_eq!(
+        args("cp {} '/games/kult mod/{}.bak'", "a.png"),
+        ["cp", "a.png", "/games/kult mod/a.png.bak"]
+    );
+    assert_eq!(
+        args(r#"echo "say \"hi\"" a\ b {}"#, "x"),
+        ["echo", "say \"hi\"", "a b", "x"]
+    );
+    assert_eq!(args("echo '' {}", "x"), ["echo", "", "x"]);
+
+    assert_eq!(ExecCommand::parse(""), None);
+    assert_eq!(ExecCommand::parse("  "), None);
+    assert_eq!(ExecCommand::parse("echo 'unterminated"), None);
+}
+
+#[test]
+fn commands_run_on_every_written_output() {
+    let dir = scratch_dir("exec");
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let src = sprite_sheet(&[
+        interleaved_frame(1, 2, |_, _| 1),
+        interleaved_frame(1, 2, |_, _| 2),
+    ]);
+    let mut executor = Executor::new(ExecCommand::parse("cp {} {}.copy").unwrap(), 2);
+    Extractor::new(&out)
+        .options(ExtractOptions::new().svg(true))
+        .on_event(|e| {
+            if let Event::OutputWritten { path } = e {
+                executor.run(path);
+            }
+        })
+        .extract_data("SPR", &src)
+        .unwrap();
+    assert_eq!(executor.finish(), []);
+
+    for name in ["SPR-00.png", "SPR-00.svg", "SPR-01.png", "SPR-01.svg"] {
+        let copy = fs::read(out.join(format!("{name}.copy"))).unwrap();
+        assert_eq!(copy, fs::read(out.join(name)).unwrap(), "{name}");
+    }
+}
+
+#[test]
+fn failures_are_reported_per_output() {
+    let mut executor = Executor::new(
+        ExecCommand::parse("sh -c 'echo broken {} >&2; exit 3'").unwrap(),
+        4,
+    );
+    executor.run(Path::new("a.png"));
+    executor.run(Path::new("b.png"));
+    let failures = executor.finish();
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0].path, Path::new("a.png"));
+    assert!(
+        failures[0].reason.ends_with("broken a.png"),
+        "{}",
+        failures[0].reason
+    );
+    assert!(failures[1].reason.contains('3'), "{}", failures[1].reason);
+
+    let mut executor = Executor::new(ExecCommand::parse("kult-no-such-program").unwrap(), 1);
+    executor.run(Path::new("a.png"));
+    let failures = executor.finish();
+    assert_eq!(failures.len(), 1);
+    assert!(
+        failures[0]
+            .reason
+            .starts_with("kult-no-such-program could not be run"),
+        "{}",
+        failures[0].reason
+    );
+}
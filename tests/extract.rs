@@ -0,0 +1,1260 @@
+#![cfg(feature = "png")]
+
+mod common;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use common::*;
+use exxos_kult_extract::catalog::Catalog;
+use exxos_kult_extract::ega::{DEFAULT_PALETTE_REGISTERS, EGA_PAL};
+use exxos_kult_extract::engine::{write_atlas, AtlasOutput, Engine};
+use exxos_kult_extract::extract::{
+    Event, ExtractOptions, Extractor, FrameSelection, GroupBy, NameTemplate,
+};
+use exxos_kult_extract::format::{
+    Detect, Extract, Extracted, Format, FormatRegistry, Geometry, IndexedFrame, Sprites,
+};
+use exxos_kult_extract::hercules::Phosphor;
+use exxos_kult_extract::output::{
+    downscale, output_stem, Crt, Matte, OverwritePolicy, PngCompression, SCALE_FACTOR_HEIGHT,
+    SCALE_FACTOR_WIDTH,
+};
+
+#[test]
+fn extracts_fullscreen_scaled() {
+    let dir = scratch_dir("fullscreen");
+    let input = dir.join("SCREEN.EGA");
+    fs::write(&input, planar_fullscreen(|x, _| (x % 16) as u8)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out).extract_file(&input).unwrap();
+
+    let (rgba, width, height) = read_png(&out.join("SCREEN.png"));
+    assert_eq!(width, 320 * SCALE_FACTOR_WIDTH);
+    assert_eq!(height, 200 * SCALE_FACTOR_HEIGHT);
+
+    // The first input pixel becomes a 5x6 block.
+    let indices = rgba_to_indices(&rgba[..4 * 2 * SCALE_FACTOR_WIDTH]);
+    assert_eq!(indices, [0, 0, 0, 0, 0, 1, 1, 1, 1, 1]);
+}
+
+#[test]
+fn extracts_sprite_frames_with_events() {
+    let dir = scratch_dir("sprites");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[
+            interleaved_frame(1, 3, |_, _| 2),
+            interleaved_frame(2, 1, |_, _| 5),
+        ]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let mut decoded = Vec::new();
+    Extractor::new(&out)
+        .on_event(|e| {
+            if let Event::FrameDecoded {
+                index,
+                width,
+                height,
+            } = e
+            {
+                decoded.push((*index, *width, *height));
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    assert_eq!(decoded, [(0, 4, 3), (1, 8, 1)]);
+    assert!(out.join("SPR-00.png").exists());
+    assert!(out.join("SPR-01.png").exists());
+}
+
+#[test]
+fn truncated_file_fails_gracefully() {
+    let dir = scratch_dir("truncated");
+    let input = dir.join("BROKEN.EGA");
+    fs::write(&input, [0, 0, 1, 0, 6]).unwrap();
+
+    let mut failed = false;
+    let err = Extractor::new(&dir)
+        .on_event(|e| failed |= matches!(e, Event::FileFailed { .. }))
+        .extract_file(&input)
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(failed);
+}
+
+#[test]
+fn strict_mode_rejects_inconsistent_headers() {
+    let dir = scratch_dir("strict");
+    let mut last = interleaved_frame(1, 8, |_, _| 4);
+    last.truncate(10);
+    let truncated = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 3), last]);
+    let mut little_endian = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 3)]);
+    little_endian[..4].reverse();
+
+    for (name, src) in [("TRUNC.EGA", &truncated), ("LE.EGA", &little_endian)] {
+        Extractor::new(&dir).extract_data(name, src).unwrap();
+    }
+    assert!(dir.join("TRUNC-00.png").exists());
+    assert!(dir.join("LE-00.png").exists());
+
+    let strict = dir.join("strict");
+    fs::create_dir_all(&strict).unwrap();
+    let mut extractor = Extractor::new(&strict).options(ExtractOptions::new().strict(true));
+    let err = extractor.extract_data("TRUNC.EGA", &truncated).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "Frame 1: frame size 20 exceeds remaining data, rejected in strict mode"
+    );
+    let err = extractor
+        .extract_data("LE.EGA", &little_endian)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Sheet size is little-endian, rejected in strict mode"
+    );
+    assert_eq!(fs::read_dir(&strict).unwrap().count(), 0);
+}
+
+#[test]
+fn existing_outputs_follow_overwrite_policy() {
+    let dir = scratch_dir("overwrite");
+    let input = dir.join("SPR.EGA");
+    fs::write(&input, sprite_sheet(&[interleaved_frame(1, 1, |_, _| 1)])).unwrap();
+
+    let output = dir.join("SPR-00.png");
+    fs::write(&output, "hand edited").unwrap();
+
+    let mut skipped = 0;
+    Extractor::new(&dir)
+        .on_event(|e| skipped += matches!(e, Event::OutputSkipped { .. }) as usize)
+        .extract_file(&input)
+        .unwrap();
+    assert_eq!(skipped, 1);
+    assert_eq!(fs::read(&output).unwrap(), b"hand edited");
+
+    Extractor::new(&dir)
+        .options(ExtractOptions::new().overwrite(OverwritePolicy::Backup))
+        .extract_file(&input)
+        .unwrap();
+    assert_eq!(
+        fs::read(dir.join("SPR-00.png.bak")).unwrap(),
+        b"hand edited"
+    );
+    assert_ne!(fs::read(&output).unwrap(), b"hand edited");
+
+    // Earlier backups are kept.
+    let extracted = fs::read(&output).unwrap();
+    Extractor::new(&dir)
+        .options(ExtractOptions::new().overwrite(OverwritePolicy::Backup))
+        .extract_file(&input)
+        .unwrap();
+    assert_eq!(
+        fs::read(dir.join("SPR-00.png.bak")).unwrap(),
+        b"hand edited"
+    );
+    assert_eq!(fs::read(dir.join("SPR-00.png.bak.1")).unwrap(), extracted);
+}
+
+#[test]
+fn hitboxes_trailing_frames_are_exported() {
+    let dir = scratch_dir("hitboxes");
+    let input = dir.join("HIT.EGA");
+
+    // One rectangle of 2x1 pixels at (1, 0) after the pixel data.
+    let mut frame = interleaved_frame(1, 2, |_, _| 3);
+    frame.extend_from_slice(&[1, 0, 2, 1]);
+    let size = frame.len() as u16;
+    frame[..2].copy_from_slice(&size.to_le_bytes());
+    fs::write(&input, sprite_sheet(&[frame])).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().engine(Some(Engine::Generic)))
+        .extract_file(&input)
+        .unwrap();
+    let json = fs::read_to_string(out.join("HIT.json")).unwrap();
+    assert!(!json.contains("hitboxes"), "{json}");
+
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .engine(Some(Engine::Generic))
+                .overwrite(OverwritePolicy::Force)
+                .hitboxes(true),
+        )
+        .extract_file(&input)
+        .unwrap();
+    let json = fs::read_to_string(out.join("HIT.json")).unwrap();
+    let expected = format!(
+        "\"hitboxes\": [{{ \"x\": {}, \"y\": 0, \"w\": {}, \"h\": {} }}]",
+        SCALE_FACTOR_WIDTH,
+        2 * SCALE_FACTOR_WIDTH,
+        SCALE_FACTOR_HEIGHT
+    );
+    assert!(json.contains(&expected), "{json}");
+}
+
+#[test]
+fn empty_atlases_are_not_written() {
+    let dir = scratch_dir("empty-atlas");
+    let empty = IndexedFrame {
+        index: 0,
+        width: 0,
+        height: 0,
+        pixels: Vec::new(),
+        hitboxes: Vec::new(),
+    };
+    let output = write_atlas(
+        Engine::Generic,
+        &dir,
+        "EMPTY",
+        &[empty],
+        &EGA_PAL,
+        false,
+        false,
+        OverwritePolicy::Skip,
+    )
+    .unwrap();
+    assert_eq!(output, AtlasOutput::Empty);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+}
+
+/// Sprite sheets whose frames are all read as 1x1 pixel of index 2.
+struct Dots;
+
+impl Detect for Dots {
+    fn detect(&self, src: &[u8]) -> Result<(), String> {
+        Sprites::new().detect(src)
+    }
+}
+
+impl Extract for Dots {
+    fn extract(&self, src: &[u8]) -> Result<Extracted, std::io::Error> {
+        let mut extracted = Sprites::new().extract(src)?;
+        for frame in &mut extracted.frames {
+            (frame.width, frame.height, frame.pixels) = (1, 1, vec![2]);
+        }
+        Ok(extracted)
+    }
+}
+
+impl Format for Dots {
+    fn name(&self) -> &'static str {
+        "sprites"
+    }
+
+    fn description(&self) -> &'static str {
+        "sprite sheet of dots"
+    }
+}
+
+#[test]
+fn hitboxes_keep_the_registered_format() {
+    let dir = scratch_dir("hitboxes-registry");
+    let input = dir.join("SPR.EGA");
+    fs::write(&input, sprite_sheet(&[interleaved_frame(1, 2, |_, _| 3)])).unwrap();
+
+    let mut registry = FormatRegistry::empty();
+    registry.register(Dots);
+    Extractor::new(&dir)
+        .options(ExtractOptions::new().hitboxes(true))
+        .registry(registry)
+        .extract_file(&input)
+        .unwrap();
+    let (rgba, width, height) = read_png(&dir.join("SPR-00.png"));
+    assert_eq!((width, height), (SCALE_FACTOR_WIDTH, SCALE_FACTOR_HEIGHT));
+    assert_eq!(&rgba[..4], EGA_PAL[2]);
+}
+
+#[test]
+fn raw_planes_are_unscaled() {
+    let dir = scratch_dir("raw-planes");
+    let input = dir.join("SCREEN.EGA");
+    fs::write(&input, planar_fullscreen(|x, _| (x % 16) as u8)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().raw_planes(true))
+        .extract_file(&input)
+        .unwrap();
+
+    for n in 0..4 {
+        let decoder =
+            png::Decoder::new(fs::File::open(out.join(format!("SCREEN-plane{n}.png"))).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (320, 200));
+        assert_eq!(info.bit_depth, png::BitDepth::One);
+    }
+
+    let decoder = png::Decoder::new(fs::File::open(out.join("SCREEN-planes.png")).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf).unwrap();
+    assert_eq!(reader.info().bit_depth, png::BitDepth::Four);
+    assert_eq!(&buf[..4], [0x01, 0x23, 0x45, 0x67]);
+}
+
+#[test]
+fn native_frames_record_pixel_aspect() {
+    let dir = scratch_dir("native");
+    let input = dir.join("SCREEN.EGA");
+    fs::write(&input, planar_fullscreen(|x, _| (x % 16) as u8)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().native(true))
+        .extract_file(&input)
+        .unwrap();
+
+    let decoder = png::Decoder::new(fs::File::open(out.join("SCREEN.png")).unwrap());
+    let info = decoder.read_info().unwrap().info().clone();
+    assert_eq!((info.width, info.height), (320, 200));
+
+    let dims = info.pixel_dims.unwrap();
+    assert_eq!(dims.unit, png::Unit::Unspecified);
+    assert_eq!(
+        (dims.xppu as usize, dims.yppu as usize),
+        (SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH)
+    );
+    assert!(!info.uncompressed_latin1_text.is_empty());
+}
+
+#[test]
+fn frames_record_their_provenance() {
+    let dir = scratch_dir("provenance");
+    let input = dir.join("SPR.EGA");
+    let frames = (0..2)
+        .map(|n| interleaved_frame(1, 3, move |_, _| n + 1))
+        .collect::<Vec<_>>();
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out).extract_file(&input).unwrap();
+
+    let decoder = png::Decoder::new(fs::File::open(out.join("SPR-01.png")).unwrap());
+    let info = decoder.read_info().unwrap().info().clone();
+    let text = |keyword: &str| {
+        info.uncompressed_latin1_text
+            .iter()
+            .find(|t| t.keyword == keyword)
+            .map(|t| t.text.clone())
+            .unwrap_or_else(|| panic!("no {keyword}"))
+    };
+
+    assert_eq!(text("Source file"), "SPR.EGA");
+    assert!(text("Source file hash").starts_with("fnv:"));
+    assert_eq!(text("Frame"), "1");
+    assert_eq!(text("Original size"), "4x3");
+    let palette = text("Palette");
+    assert_eq!(palette.split(' ').count(), 16);
+    assert!(palette.starts_with("#00000000 #0000c4ff "), "{palette}");
+    assert!(text("Software").starts_with("exxos-kult-extract "));
+}
+
+#[test]
+fn svg_merges_runs_of_pixels() {
+    let dir = scratch_dir("svg");
+    let input = dir.join("SPR.EGA");
+    let row0 = [0, 0, 3, 3, 3, 5, 5, 0];
+    let frame = interleaved_frame(2, 2, |x, y| if y == 0 { row0[x] } else { 3 });
+    fs::write(&input, sprite_sheet(&[frame])).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().svg(true))
+        .extract_file(&input)
+        .unwrap();
+
+    let svg = fs::read_to_string(out.join("SPR-00.svg")).unwrap();
+    assert!(
+        svg.contains("width=\"40\" height=\"12\" viewBox=\"0 0 8 2\""),
+        "{svg}"
+    );
+    let [r, g, b, _] = EGA_PAL[3];
+    let cyan = format!("<g fill=\"#{r:02x}{g:02x}{b:02x}\">\n<rect x=\"2\" y=\"0\" width=\"3\" height=\"1\"/>\n<rect x=\"0\" y=\"1\" width=\"8\" height=\"1\"/>\n</g>");
+    assert!(svg.contains(&cyan), "{svg}");
+    assert_eq!(svg.matches("<rect").count(), 3);
+
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .svg(true)
+                .native(true)
+                .overwrite(OverwritePolicy::Force),
+        )
+        .extract_file(&input)
+        .unwrap();
+    let svg = fs::read_to_string(out.join("SPR-00.svg")).unwrap();
+    assert!(svg.contains("width=\"8\" height=\"2\""), "{svg}");
+}
+
+#[test]
+fn only_selected_frames_are_written() {
+    let dir = scratch_dir("frames");
+    let input = dir.join("SPR.EGA");
+    let frames = (0..6)
+        .map(|n| interleaved_frame(1, 1, move |_, _| n))
+        .collect::<Vec<_>>();
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let mut selection = FrameSelection::default();
+    assert!(!selection.add_list("1,x"));
+    assert!(selection.add_list("1,3-4"));
+    selection.add_last();
+
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .engine(Some(Engine::Generic))
+                .frames(Some(selection)),
+        )
+        .extract_file(&input)
+        .unwrap();
+
+    let mut written = fs::read_dir(&out)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    written.sort();
+    assert_eq!(
+        written,
+        ["SPR-01.png", "SPR-03.png", "SPR-04.png", "SPR-05.png"]
+    );
+}
+
+#[test]
+fn palette_remap_following_screen_is_applied() {
+    let dir = scratch_dir("remap");
+    let input = dir.join("SCREEN.EGA");
+
+    // Index 1 is loaded with the color of index 4.
+    let mut registers = DEFAULT_PALETTE_REGISTERS;
+    registers[1] = 4;
+    let mut src = planar_fullscreen(|_, _| 1);
+    src.extend_from_slice(&registers);
+    fs::write(&input, src).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out).extract_file(&input).unwrap();
+    let (rgba, _, _) = read_png(&out.join("SCREEN.png"));
+    assert_eq!(&rgba[..4], EGA_PAL[4]);
+
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .overwrite(OverwritePolicy::Force)
+                .remap(false),
+        )
+        .extract_file(&input)
+        .unwrap();
+    let (rgba, _, _) = read_png(&out.join("SCREEN.png"));
+    assert_eq!(&rgba[..4], EGA_PAL[1]);
+}
+
+#[test]
+fn matte_fills_transparent_pixels() {
+    let dir = scratch_dir("matte");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[interleaved_frame(1, 1, |x, _| (x != 2) as u8)]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().matte(Matte::parse("#102030")))
+        .extract_file(&input)
+        .unwrap();
+
+    let (rgba, _, _) = read_png(&out.join("SPR-00.png"));
+    assert_eq!(&rgba[..4], EGA_PAL[1]);
+    let gap = 4 * SCALE_FACTOR_WIDTH * 2;
+    assert_eq!(&rgba[gap..gap + 4], [0x10, 0x20, 0x30, 0xff]);
+}
+
+#[test]
+fn dedupe_writes_identical_frames_once() {
+    let dir = scratch_dir("dedupe");
+    let input = dir.join("SPR.EGA");
+    let frame = interleaved_frame(1, 2, |x, _| x as u8);
+    fs::write(
+        &input,
+        sprite_sheet(&[frame.clone(), interleaved_frame(1, 2, |_, _| 7), frame]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut duplicates = Vec::new();
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .engine(Some(Engine::Generic))
+                .dedupe(true),
+        )
+        .on_event(|e| {
+            if let Event::FrameDuplicate { index, of } = e {
+                duplicates.push((*index, *of));
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    assert_eq!(duplicates, [(2, 0)]);
+    assert!(out.join("SPR-01.png").exists());
+    assert!(!out.join("SPR-02.png").exists());
+
+    let (_, width, _) = read_png(&out.join("SPR-atlas.png"));
+    assert_eq!(width, 2 * 4 * SCALE_FACTOR_WIDTH);
+
+    let json = fs::read_to_string(out.join("SPR.json")).unwrap();
+    assert!(json.contains("\"name\": \"SPR-02\", \"x\": 0,"), "{json}");
+    assert!(json.contains("\"duplicate_of\": \"SPR-00\""), "{json}");
+}
+
+#[test]
+fn mirrored_frames_are_flipped() {
+    let dir = scratch_dir("mirror");
+    let input = dir.join("SPR.EGA");
+    let frame = interleaved_frame(1, 2, |x, _| if x == 0 { 3 } else { 1 });
+    fs::write(&input, sprite_sheet(&[frame])).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .engine(Some(Engine::Generic))
+                .mirror(true),
+        )
+        .extract_file(&input)
+        .unwrap();
+
+    let (rgba, width, _) = read_png(&out.join("SPR-00.png"));
+    let (flipped, flipped_width, _) = read_png(&out.join("SPR-00-flip.png"));
+    assert_eq!(width, flipped_width);
+    let row = |rgba: &[u8]| rgba_to_indices(&rgba[..4 * width]);
+    let mut expected = row(&rgba);
+    expected.reverse();
+    assert_eq!(row(&flipped), expected);
+    assert_ne!(row(&flipped), row(&rgba));
+
+    let json = fs::read_to_string(out.join("SPR.json")).unwrap();
+    assert!(json.contains("\"name\": \"SPR-00-flip\""), "{json}");
+    assert!(json.contains("\"mirror_of\": \"SPR-00\""), "{json}");
+}
+
+#[test]
+fn frame_diffs_show_changed_pixels() {
+    let dir = scratch_dir("frame-diffs");
+    let input = dir.join("SPR.EGA");
+    let first = |x: usize, _| x as u8 + 1;
+    let frames = [
+        interleaved_frame(1, 2, first),
+        interleaved_frame(1, 2, |x, y| if (x, y) == (1, 0) { 9 } else { first(x, y) }),
+        interleaved_frame(2, 2, |x, y| if x < 4 { 9 } else { first(x, y) }),
+    ];
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().native(true).frame_diffs(true))
+        .extract_file(&input)
+        .unwrap();
+
+    assert!(!out.join("SPR-00-diff.png").exists());
+    let (diff, width, height) = read_png(&out.join("SPR-01-diff.png"));
+    assert_eq!((width, height), (4, 2));
+    let pixel = |rgba: &[u8], x: usize, y: usize| {
+        let pos = 4 * (y * width + x);
+        rgba[pos..pos + 4].to_vec()
+    };
+    assert_eq!(pixel(&diff, 1, 0), EGA_PAL[9]);
+    for (x, y) in [(0, 0), (2, 0), (1, 1)] {
+        let gray = pixel(&diff, x, y);
+        assert_eq!((gray[0], gray[1], gray[3]), (gray[2], gray[2], 0xff));
+        assert_ne!(gray, EGA_PAL[first(x, y) as usize]);
+    }
+
+    // Pixels beyond the smaller frame before count as changed.
+    let (diff, width, _) = read_png(&out.join("SPR-02-diff.png"));
+    assert_eq!(width, 8);
+    let row = rgba_to_indices(&diff[4 * 4..4 * 8]);
+    assert_eq!(row, [5, 6, 7, 8]);
+}
+
+#[test]
+fn data_is_named_after_the_given_name() {
+    let dir = scratch_dir("data");
+    let src = sprite_sheet(&[
+        interleaved_frame(1, 2, |_, _| 1),
+        interleaved_frame(2, 1, |_, _| 2),
+    ]);
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out);
+    extractor.extract_data("stdin", &src).unwrap();
+    assert!(out.join("stdin-00.png").exists());
+    assert!(out.join("stdin-01.png").exists());
+
+    // A single PNG with both frames side by side, and no files.
+    let mut png_data = Vec::new();
+    extractor.extract_png("stdin", &src, &mut png_data).unwrap();
+    let info = png::Decoder::new(&png_data[..])
+        .read_info()
+        .unwrap()
+        .info()
+        .clone();
+    assert_eq!(info.width as usize, (4 + 8) * SCALE_FACTOR_WIDTH);
+    assert_eq!(info.height as usize, 2 * SCALE_FACTOR_HEIGHT);
+    assert_eq!(fs::read_dir(&out).unwrap().count(), 2);
+}
+
+#[test]
+fn output_stems_survive_odd_names() {
+    assert_eq!(output_stem(Path::new("SPR07.DAT")), "SPR07");
+    assert_eq!(output_stem(Path::new("dir/NOEXT")), "NOEXT");
+    assert_eq!(output_stem(Path::new(".hidden")), ".hidden");
+    assert_eq!(output_stem(Path::new("FILE.")), "FILE");
+    assert_eq!(output_stem(Path::new("FILE..")), "FILE%2E");
+    assert_eq!(output_stem(Path::new("...")), "%2E%2E");
+    assert_eq!(output_stem(Path::new("a b .DAT")), "a b%20");
+    assert_eq!(output_stem(Path::new("100%.DAT")), "100%25");
+    assert_eq!(output_stem(Path::new("tab\there.DAT")), "tab%09here");
+    assert_eq!(output_stem(Path::new("..")), "unnamed");
+    assert_eq!(output_stem(Path::new(".")), "unnamed");
+    assert_eq!(output_stem(Path::new("")), "unnamed");
+    assert_eq!(output_stem(Path::new("/")), "unnamed");
+}
+
+#[cfg(unix)]
+#[test]
+fn output_stems_of_non_utf8_names_are_distinct() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let latin1 = Path::new(OsStr::from_bytes(b"R\xc9SUM\xc9.DAT"));
+    let other = Path::new(OsStr::from_bytes(b"R\xc8SUM\xc8.DAT"));
+    assert_eq!(output_stem(latin1), "R%C9SUM%C9");
+    assert_eq!(output_stem(other), "R%C8SUM%C8");
+    assert_eq!(output_stem(Path::new("RÉSUMÉ.DAT")), "RÉSUMÉ");
+}
+
+#[test]
+fn odd_names_are_extracted_without_panicking() {
+    let dir = scratch_dir("odd-names");
+    let src = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)]);
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out);
+    for name in ["NOEXT", "TRAIL.", "DOTS..", ".."] {
+        extractor.extract_data(name, &src).unwrap();
+    }
+    assert!(out.join("NOEXT-00.png").exists());
+    assert!(out.join("TRAIL-00.png").exists());
+    assert!(out.join("DOTS%2E-00.png").exists());
+    assert!(out.join("unnamed-00.png").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_files_are_extracted() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = scratch_dir("non-utf8");
+    let name = dir.join(OsStr::from_bytes(b"SPR\xff.DAT"));
+    fs::write(&name, sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)])).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out).extract_file(&name).unwrap();
+    assert!(out.join("SPR%FF-00.png").exists());
+}
+
+#[test]
+fn hercules_screens_use_the_phosphor() {
+    let dir = scratch_dir("hercules");
+    let input = dir.join("MONO.HGC");
+    let mut src = vec![0u8; 32768];
+    src[0] = 0x80;
+    fs::write(&input, src).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().native(true).phosphor(Phosphor::Amber))
+        .extract_file(&input)
+        .unwrap();
+
+    let (rgba, width, height) = read_png(&out.join("MONO.png"));
+    assert_eq!((width, height), (720, 348));
+    assert_eq!(rgba[..8], [0xff, 0xb0, 0x00, 0xff, 0x00, 0x00, 0x00, 0xff]);
+}
+
+#[test]
+fn frames_follow_the_name_template() {
+    let dir = scratch_dir("name-template");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[
+            interleaved_frame(1, 2, |x, _| x as u8),
+            interleaved_frame(1, 2, |_, y| y as u8),
+        ]),
+    )
+    .unwrap();
+    fs::write(dir.join("PIC.EGA"), planar_fullscreen(|_, _| 1)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out)
+        .options(ExtractOptions::new().name_template(NameTemplate::parse("{name}/kult_{frame}")));
+    extractor.extract_file(&input).unwrap();
+    extractor.extract_file(dir.join("PIC.EGA")).unwrap();
+
+    assert!(out.join("SPR/kult_00.png").exists());
+    assert!(out.join("SPR/kult_01.png").exists());
+    assert!(out.join("PIC/kult_00.png").exists());
+
+    for template in ["{name}-{index}", "/{name}", "../{name}", "{name}//{frame}"] {
+        assert_eq!(NameTemplate::parse(template), None, "{template}");
+    }
+}
+
+#[test]
+fn frames_are_cropped_and_rotated_before_scaling() {
+    let dir = scratch_dir("geometry");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[
+            interleaved_frame(2, 3, |x, _| (x % 16) as u8),
+            interleaved_frame(1, 1, |_, _| 1),
+        ]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut warnings = Vec::new();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().native(true).geometry(Geometry {
+            crop: Some((4, 0, 4, 2)),
+            rotate: 1,
+            ..Geometry::default()
+        }))
+        .on_event(|e| {
+            if let Event::Warning { message } = e {
+                warnings.push(message.clone());
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    let (rgba, width, height) = read_png(&out.join("SPR-00.png"));
+    assert_eq!((width, height), (2, 4));
+    assert_eq!(rgba_to_indices(&rgba), [4, 4, 5, 5, 6, 6, 7, 7]);
+    assert!(!out.join("SPR-01.png").exists());
+    assert_eq!(warnings, ["Frame 1 lies outside the crop, skipping."]);
+}
+
+#[test]
+fn crt_darkens_scanlines_and_masks_channels() {
+    let white = [0xff, 0xff, 0xff, 0xff];
+    let width = 3 * SCALE_FACTOR_WIDTH;
+    let mut data = white.repeat(width * SCALE_FACTOR_HEIGHT);
+    let plain = data.clone();
+
+    Crt { intensity: 0.0 }.apply(&mut data, width);
+    assert_eq!(data, plain);
+
+    Crt::default().apply(&mut data, width);
+    let pixel = |x: usize, y: usize| &data[4 * (y * width + x)..4 * (y * width + x) + 4];
+    // Each stripe of the mask passes one channel.
+    assert_eq!(pixel(0, 0), [0xff, 0xbf, 0xbf, 0xff]);
+    assert_eq!(pixel(1, 0), [0xbf, 0xff, 0xbf, 0xff]);
+    assert_eq!(pixel(5, 0), [0xbf, 0xbf, 0xff, 0xff]);
+    // The last row of each source row is the darkest.
+    assert_eq!(pixel(0, SCALE_FACTOR_HEIGHT - 1), [0x80, 0x60, 0x60, 0xff]);
+
+    // Transparent neighbours don't darken the edge of a sprite.
+    let mut edge = [white, [0; 4]].concat();
+    Crt::default().apply(&mut edge, 2);
+    assert_eq!(edge[..4], [0xff, 0xbf, 0xbf, 0xdf]);
+    assert_eq!(edge[4..], [0x60, 0x80, 0x60, 0x20]);
+}
+
+#[test]
+fn crt_applies_to_scaled_frames() {
+    let dir = scratch_dir("crt");
+    let input = dir.join("SCREEN.EGA");
+    fs::write(&input, planar_fullscreen(|_, _| 15)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().crt(Some(Crt::default())))
+        .extract_file(&input)
+        .unwrap();
+
+    let (rgba, width, _) = read_png(&out.join("SCREEN.png"));
+    let row = |y: usize| rgba[4 * y * width..4 * y * width + 4].to_vec();
+    assert!(row(SCALE_FACTOR_HEIGHT - 1)[0] < row(0)[0]);
+    assert_eq!(row(0), row(SCALE_FACTOR_HEIGHT));
+}
+
+#[test]
+fn concurrent_encoding_writes_the_same_files_in_order() {
+    let dir = scratch_dir("jobs");
+    let input = dir.join("SPR.EGA");
+    let frames = (0..7)
+        .map(|n| interleaved_frame(1 + n % 3, 2 + n, |x, y| ((x + y + n as usize) % 16) as u8))
+        .collect::<Vec<_>>();
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+
+    let mut written = Vec::new();
+    for jobs in [1, 3] {
+        let out = dir.join(format!("png-{jobs}"));
+        fs::create_dir_all(&out).unwrap();
+        let mut events = Vec::new();
+        Extractor::new(&out)
+            .options(ExtractOptions::new().mirror(true).jobs(jobs))
+            .on_event(|e| {
+                if let Event::FrameWritten { index, path } = e {
+                    assert!(path.exists());
+                    events.push(*index);
+                }
+            })
+            .extract_file(&input)
+            .unwrap();
+        assert_eq!(events, (0..7).collect::<Vec<_>>());
+
+        let mut files = fs::read_dir(&out)
+            .unwrap()
+            .map(|e| {
+                let path = e.unwrap().path();
+                (
+                    path.file_name().unwrap().to_owned(),
+                    fs::read(&path).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        written.push(files);
+    }
+
+    assert_eq!(written[0].len(), 14);
+    assert_eq!(written[0], written[1]);
+}
+
+#[test]
+fn png_compression_keeps_the_pixels() {
+    let dir = scratch_dir("compression");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[interleaved_frame(4, 40, |x, y| {
+            ((x / 3 + y / 5) % 16) as u8
+        })]),
+    )
+    .unwrap();
+
+    let mut sizes = Vec::new();
+    let mut images = Vec::new();
+    for (name, compression) in [
+        ("fast", PngCompression::Fast),
+        ("default", PngCompression::Default),
+        ("best", PngCompression::Best),
+    ] {
+        assert_eq!(PngCompression::from_name(name), Some(compression));
+        let out = dir.join(name);
+        fs::create_dir_all(&out).unwrap();
+        Extractor::new(&out)
+            .options(ExtractOptions::new().png_compression(compression))
+            .extract_file(&input)
+            .unwrap();
+        let path = out.join("SPR-00.png");
+        sizes.push(fs::metadata(&path).unwrap().len());
+        images.push(read_png(&path));
+    }
+
+    assert_eq!(images[0], images[1]);
+    assert_eq!(images[1], images[2]);
+    assert!(sizes[2] <= sizes[1], "{sizes:?}");
+    assert!(sizes[1] < sizes[0], "{sizes:?}");
+    assert_eq!(PngCompression::from_name("max"), None);
+}
+
+#[test]
+fn padded_screens_are_decoded_at_their_dimensions() {
+    let dir = scratch_dir("dimensions");
+    let pixel = |x: usize, y: usize| ((x / 8 + y) % 16) as u8;
+    let mut src = planar_image(160, 200, pixel);
+    src.resize(32000, 0);
+
+    let input = dir.join("OVERLAY.EGA");
+    fs::write(&input, &src).unwrap();
+
+    // From the command line.
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut notes = Vec::new();
+    Extractor::new(&out)
+        .options(
+            ExtractOptions::new()
+                .native(true)
+                .dimensions(Some((160, 200))),
+        )
+        .on_event(|e| {
+            if let Event::Note { message } = e {
+                notes.push(message.clone());
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+    let (rgba, width, height) = read_png(&out.join("OVERLAY.png"));
+    assert_eq!((width, height), (160, 200));
+    assert_eq!(
+        rgba_to_indices(&rgba)[..24],
+        (0..24).map(|x| pixel(x, 0)).collect::<Vec<_>>()
+    );
+    assert_eq!(notes, ["16000 bytes after the 160x200 image, ignored."]);
+
+    // From the catalog.
+    let out = dir.join("catalog");
+    fs::create_dir_all(&out).unwrap();
+    let catalog = Catalog::parse("OVERLAY.EGA = \"Overlay\" size=160x200").unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().native(true))
+        .catalog(catalog)
+        .extract_file(&input)
+        .unwrap();
+    let (_, width, height) = read_png(&out.join("OVERLAY-overlay.png"));
+    assert_eq!((width, height), (160, 200));
+}
+
+#[test]
+fn dimensions_leave_other_formats_alone() {
+    let dir = scratch_dir("dimensions-sheet");
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let src = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)]);
+    let mut notes = Vec::new();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().dimensions(Some((160, 200))))
+        .on_event(|e| {
+            if let Event::Note { message } = e {
+                notes.push(message.clone());
+            }
+        })
+        .extract_data("SPR", &src)
+        .unwrap();
+    assert!(out.join("SPR-00.png").exists());
+    assert_eq!(
+        notes,
+        ["Not a fullscreen image, ignoring the size 160x200."]
+    );
+}
+
+#[test]
+fn repeated_screens_are_warned_about() {
+    let dir = scratch_dir("repeated");
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let src = planar_fullscreen(|x, y| ((x % 160 / 8 + y) % 16) as u8);
+    let mut warnings = Vec::new();
+    Extractor::new(&out)
+        .on_event(|e| {
+            if let Event::Warning { message } = e {
+                warnings.push(message.clone());
+            }
+        })
+        .extract_data("DOUBLED.EGA", &src)
+        .unwrap();
+    assert_eq!(
+        warnings,
+        ["The right half repeats the left half, it may be a 160x200 image padded to 320x200."]
+    );
+}
+
+#[test]
+fn downscaling_averages_areas_weighed_by_alpha() {
+    let red = [0xff, 0x00, 0x00, 0xff];
+    let clear = [0x00, 0x00, 0x00, 0x00];
+    let (data, width, height) = downscale(&[red, clear].concat(), 2, 1, 1);
+    assert_eq!((width, height), (1, 1));
+    assert_eq!(data, [0xff, 0x00, 0x00, 0x80]);
+
+    // Output pixels covering parts of input pixels.
+    let gray = |v: u8| [v, v, v, 0xff];
+    let (data, width, _) = downscale(&[gray(0), gray(90), gray(180)].concat(), 3, 1, 2);
+    assert_eq!(width, 2);
+    assert_eq!(data, [gray(30), gray(150)].concat());
+
+    let small = [red, clear].concat();
+    assert_eq!(downscale(&small, 2, 1, 4), (small.clone(), 2, 1));
+}
+
+#[test]
+fn thumbnails_fit_the_size_and_keep_the_aspect() {
+    let dir = scratch_dir("thumbnails");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[interleaved_frame(4, 20, |x, _| (x % 2) as u8 * 4)]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut thumbnails = Vec::new();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().thumbnails(Some(40)))
+        .on_event(|e| {
+            if let Event::ThumbnailWritten { index, path } = e {
+                thumbnails.push((*index, path.to_path_buf()));
+            }
+        })
+        .extract_file(&input)
+        .unwrap();
+
+    let path = out.join("SPR-00-thumb.png");
+    assert_eq!(thumbnails, [(0, path.clone())]);
+    // 16x20 is scaled to 80x120, which shrinks to 27x40.
+    let (rgba, width, height) = read_png(&path);
+    assert_eq!((width, height), (27, 40));
+    // Alternating transparent and red pixels blend into a translucent red.
+    let [r, g, b, a] = rgba[4 * 20 * width + 4 * 13..][..4] else {
+        unreachable!()
+    };
+    assert_eq!((r, g, b), (EGA_PAL[4][0], EGA_PAL[4][1], EGA_PAL[4][2]));
+    assert!((96..160).contains(&a), "{a}");
+}
+
+#[test]
+fn identical_inputs_are_extracted_once() {
+    let dir = scratch_dir("dedupe-inputs");
+    let sheet = sprite_sheet(&[
+        interleaved_frame(1, 2, |_, _| 1),
+        interleaved_frame(1, 2, |_, _| 2),
+    ]);
+    fs::write(dir.join("A.EGA"), &sheet).unwrap();
+    fs::write(dir.join("B.EGA"), &sheet).unwrap();
+    fs::write(
+        dir.join("C.EGA"),
+        sprite_sheet(&[interleaved_frame(1, 2, |_, _| 3)]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut duplicates = Vec::new();
+    let mut decoded = 0;
+    let mut extractor = Extractor::new(&out)
+        .options(ExtractOptions::new().dedupe_inputs(true))
+        .on_event(|e| match e {
+            Event::FileDuplicate { path, of } => {
+                duplicates.push((path.to_path_buf(), of.to_path_buf()))
+            }
+            Event::FrameDecoded { .. } => decoded += 1,
+            _ => {}
+        });
+    for name in ["A.EGA", "B.EGA", "C.EGA"] {
+        extractor.extract_file(dir.join(name)).unwrap();
+    }
+    drop(extractor);
+
+    assert_eq!(duplicates, [(dir.join("B.EGA"), dir.join("A.EGA"))]);
+    assert_eq!(decoded, 3, "B.EGA is not decoded");
+    for frame in ["00", "01"] {
+        let link = out.join(format!("B-{frame}.png"));
+        let original = out.join(format!("A-{frame}.png"));
+        assert_eq!(fs::read(&link).unwrap(), fs::read(&original).unwrap());
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new(&format!("A-{frame}.png"))
+        );
+    }
+    assert!(out.join("C-00.png").exists());
+}
+
+#[test]
+fn duplicates_follow_the_name_template() {
+    let dir = scratch_dir("dedupe-inputs-template");
+    let sheet = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)]);
+    fs::write(dir.join("A.EGA"), &sheet).unwrap();
+    fs::write(dir.join("B.EGA"), &sheet).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out).options(
+        ExtractOptions::new()
+            .dedupe_inputs(true)
+            .name_template(NameTemplate::parse("{name}/frame_{frame}")),
+    );
+    extractor.extract_file(dir.join("A.EGA")).unwrap();
+    extractor.extract_file(dir.join("B.EGA")).unwrap();
+
+    let link = out.join("B/frame_00.png");
+    assert_eq!(
+        fs::read(&link).unwrap(),
+        fs::read(out.join("A/frame_00.png")).unwrap()
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(&link).unwrap(),
+        Path::new("../A/frame_00.png")
+    );
+}
+
+#[test]
+fn duplicates_are_linked_across_folders() {
+    let dir = scratch_dir("dedupe-inputs-group-by");
+    let sheet = sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)]);
+    fs::write(dir.join("A.EGA"), &sheet).unwrap();
+    fs::write(dir.join("B.EGA"), &sheet).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out).options(
+        ExtractOptions::new()
+            .dedupe_inputs(true)
+            .group_by(Some(GroupBy::File)),
+    );
+    extractor.extract_file(dir.join("A.EGA")).unwrap();
+    extractor.extract_file(dir.join("B.EGA")).unwrap();
+
+    let link = out.join("B/B-00.png");
+    assert_eq!(
+        fs::read(&link).unwrap(),
+        fs::read(out.join("A/A-00.png")).unwrap()
+    );
+    #[cfg(unix)]
+    assert_eq!(fs::read_link(&link).unwrap(), Path::new("../A/A-00.png"));
+}
+
+#[test]
+fn outputs_are_grouped_by_file() {
+    let dir = scratch_dir("group-by-file");
+    fs::write(
+        dir.join("SPR.EGA"),
+        sprite_sheet(&[
+            interleaved_frame(1, 2, |_, _| 1),
+            interleaved_frame(1, 2, |_, _| 2),
+        ]),
+    )
+    .unwrap();
+    fs::write(dir.join("SCREEN.EGA"), planar_fullscreen(|_, _| 3)).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out).options(
+        ExtractOptions::new()
+            .group_by(Some(GroupBy::File))
+            .engine(Some(Engine::Generic))
+            .export_palette(true),
+    );
+    for name in ["SPR.EGA", "SCREEN.EGA"] {
+        extractor.extract_file(dir.join(name)).unwrap();
+    }
+
+    for path in [
+        "SPR/SPR-00.png",
+        "SPR/SPR-01.png",
+        "SPR/SPR-atlas.png",
+        "SPR/SPR.json",
+        "SPR/SPR.gpl",
+        "SCREEN/SCREEN.png",
+        "SCREEN/SCREEN.gpl",
+    ] {
+        assert!(out.join(path).exists(), "{path}");
+    }
+    assert!(!out.join("SPR-00.png").exists());
+}
+
+#[test]
+fn outputs_are_grouped_by_type() {
+    let dir = scratch_dir("group-by-type");
+    fs::write(
+        dir.join("SPR.EGA"),
+        sprite_sheet(&[interleaved_frame(1, 2, |_, _| 1)]),
+    )
+    .unwrap();
+    fs::write(dir.join("SCREEN.EGA"), planar_fullscreen(|_, _| 3)).unwrap();
+    fs::write(dir.join("JUNK.EGA"), [0xff; 320]).unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let mut extractor = Extractor::new(&out).options(
+        ExtractOptions::new()
+            .group_by(Some(GroupBy::Type))
+            .raw_planes(true),
+    );
+    for name in ["SPR.EGA", "SCREEN.EGA", "JUNK.EGA"] {
+        let _ = extractor.extract_file(dir.join(name));
+    }
+
+    for path in [
+        "sprites/SPR-00.png",
+        "fullscreen/SCREEN.png",
+        "fullscreen/SCREEN-planes.png",
+        "other/JUNK-planes.png",
+    ] {
+        assert!(out.join(path).exists(), "{path}");
+    }
+    assert!(!out.join("other/JUNK-00.png").exists());
+}
+
+#[test]
+fn options_are_shared_between_extractors() {
+    let dir = scratch_dir("extract-options");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[interleaved_frame(2, 3, |x, _| x as u8)]),
+    )
+    .unwrap();
+
+    let options = ExtractOptions::new().native(true).svg(true);
+    for name in ["a", "b"] {
+        let out = dir.join(name);
+        fs::create_dir_all(&out).unwrap();
+        Extractor::new(&out)
+            .options(options.clone())
+            .extract_file(&input)
+            .unwrap();
+        let (_, width, height) = read_png(&out.join("SPR-00.png"));
+        assert_eq!((width, height), (8, 3));
+        assert!(out.join("SPR-00.svg").exists());
+    }
+}
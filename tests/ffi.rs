@@ -0,0 +1,73 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::ptr;
+
+use common::*;
+use exxos_kult_extract::ega::EGA_PAL;
+use exxos_kult_extract::ffi::*;
+
+#[test]
+fn decodes_fullscreen() {
+    let src = planar_fullscreen(|x, _| (x % 16) as u8);
+
+    let (mut width, mut height) = (0, 0);
+    let ret = unsafe {
+        kult_decode_fullscreen(
+            src.as_ptr(),
+            src.len(),
+            ptr::null_mut(),
+            0,
+            &mut width,
+            &mut height,
+        )
+    };
+    assert_eq!(ret, -1);
+    assert_eq!((width, height), (320, 200));
+
+    let mut out = vec![0; width * height * 4];
+    let ret = unsafe {
+        kult_decode_fullscreen(
+            src.as_ptr(),
+            src.len(),
+            out.as_mut_ptr(),
+            out.len(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(ret, 0);
+    assert_eq!(&out[4..8], EGA_PAL[1]);
+}
+
+#[test]
+fn decodes_sheet_frames() {
+    let src = sprite_sheet(&[
+        interleaved_frame(1, 3, |_, _| 2),
+        interleaved_frame(2, 1, |_, _| 5),
+    ]);
+
+    unsafe {
+        assert!(kult_sheet_open(src.as_ptr(), 3).is_null());
+
+        let sheet = kult_sheet_open(src.as_ptr(), src.len());
+        assert!(!sheet.is_null());
+        assert_eq!(kult_sheet_frame_count(sheet), 2);
+
+        let (mut width, mut height) = (0, 0);
+        assert_eq!(kult_sheet_frame_size(sheet, 1, &mut width, &mut height), 0);
+        assert_eq!((width, height), (8, 1));
+        assert_eq!(kult_sheet_frame_size(sheet, 2, &mut width, &mut height), -1);
+
+        let mut out = vec![0; width * height * 4];
+        assert_eq!(kult_sheet_frame_rgba(sheet, 1, out.as_mut_ptr(), 4), -1);
+        assert_eq!(
+            kult_sheet_frame_rgba(sheet, 1, out.as_mut_ptr(), out.len()),
+            0
+        );
+        assert_eq!(&out[..4], EGA_PAL[5]);
+
+        kult_sheet_free(sheet);
+    }
+}
@@ -0,0 +1,59 @@
+use exxos_kult_extract::gallery::{Gallery, GalleryImage};
+
+#[test]
+fn groups_images_by_source() {
+    let mut gallery = Gallery::new();
+    gallery.start_section("SPR<1>.EGA");
+    for index in 0..2 {
+        gallery.add_image(GalleryImage {
+            index,
+            width: 16,
+            height: 8,
+            href: format!("SPR<1>-{index:02}.png"),
+            thumbnail: None,
+        });
+    }
+    gallery.start_section("EMPTY.EGA");
+    gallery.start_section("PIC.EGA");
+    gallery.add_image(GalleryImage {
+        index: 0,
+        width: 320,
+        height: 200,
+        href: "PIC.png".to_string(),
+        thumbnail: None,
+    });
+
+    let mut html = Vec::new();
+    gallery.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+
+    assert!(html.contains("<h2>SPR&lt;1&gt;.EGA</h2>"));
+    assert!(html.contains("<a href=\"SPR&lt;1&gt;-01.png\">"));
+    assert!(html.contains("01 &middot; 16x8"));
+    assert!(html.contains("00 &middot; 320x200"));
+    assert!(
+        !html.contains("EMPTY.EGA"),
+        "sections without images are left out"
+    );
+    assert!(html.find("SPR&lt;1&gt;.EGA") < html.find("PIC.EGA"));
+}
+
+#[test]
+fn thumbnails_are_shown_in_place_of_the_images() {
+    let mut gallery = Gallery::new();
+    gallery.start_section("SPR.EGA");
+    gallery.add_image(GalleryImage {
+        index: 0,
+        width: 16,
+        height: 8,
+        href: "SPR-00.png".to_string(),
+        thumbnail: None,
+    });
+    gallery.set_thumbnail("SPR-00-thumb.png");
+
+    let mut html = Vec::new();
+    gallery.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+
+    assert!(html.contains("<a href=\"SPR-00.png\"><img src=\"SPR-00-thumb.png\""));
+}
@@ -0,0 +1,87 @@
+use exxos_kult_extract::format::{Geometry, IndexedFrame};
+use exxos_kult_extract::sprite::HitBox;
+
+/// A 3x2 frame numbered row by row, with a hit box over the right column.
+fn frame() -> IndexedFrame {
+    IndexedFrame {
+        index: 4,
+        width: 3,
+        height: 2,
+        pixels: vec![1, 2, 3, 4, 5, 6],
+        hitboxes: vec![HitBox {
+            x: 2,
+            y: 0,
+            w: 1,
+            h: 2,
+        }],
+    }
+}
+
+#[test]
+fn rotations() {
+    let once = frame().rotated(1);
+    assert_eq!((once.width, once.height), (2, 3));
+    assert_eq!(once.pixels, [4, 1, 5, 2, 6, 3]);
+    assert_eq!(
+        once.hitboxes,
+        [HitBox {
+            x: 0,
+            y: 2,
+            w: 2,
+            h: 1
+        }]
+    );
+
+    let twice = frame().rotated(2);
+    assert_eq!(twice.pixels, [6, 5, 4, 3, 2, 1]);
+    assert_eq!(twice.hitboxes[0].x, 0);
+
+    let back = frame().rotated(3).rotated(1);
+    assert_eq!(back.pixels, frame().pixels);
+    assert_eq!(back.hitboxes, frame().hitboxes);
+    assert_eq!(frame().rotated(4).pixels, frame().pixels);
+}
+
+#[test]
+fn flips() {
+    assert_eq!(frame().flipped().pixels, [3, 2, 1, 6, 5, 4]);
+    let flipped = frame().flipped_vertically();
+    assert_eq!(flipped.pixels, [4, 5, 6, 1, 2, 3]);
+    assert_eq!(flipped.hitboxes, frame().hitboxes);
+}
+
+#[test]
+fn crops_clip_pixels_and_hitboxes() {
+    let cropped = frame().cropped(1, 1, 5, 5);
+    assert_eq!((cropped.width, cropped.height), (2, 1));
+    assert_eq!(cropped.pixels, [5, 6]);
+    assert_eq!(
+        cropped.hitboxes,
+        [HitBox {
+            x: 1,
+            y: 0,
+            w: 1,
+            h: 1
+        }]
+    );
+
+    let outside = frame().cropped(3, 0, 2, 2);
+    assert_eq!((outside.width, outside.pixels.len()), (0, 0));
+    assert!(outside.hitboxes.is_empty());
+}
+
+#[test]
+fn geometry_crops_before_flipping_and_rotating() {
+    let geometry = Geometry {
+        crop: Some((0, 0, 2, 2)),
+        flip_horizontally: true,
+        flip_vertically: false,
+        rotate: 1,
+    };
+    assert!(!geometry.is_identity());
+    // Cropped to [1, 2, 4, 5], flipped to [2, 1, 5, 4], then rotated.
+    let f = geometry.apply(&frame());
+    assert_eq!(f.pixels, [5, 2, 4, 1]);
+    assert_eq!(f.index, 4);
+    assert!(Geometry::default().is_identity());
+}
@@ -0,0 +1,29 @@
+use exxos_kult_extract::group::parse_manifest;
+
+#[test]
+fn manifests_list_named_groups() {
+    let groups = parse_manifest(
+        "# Raven\n\
+         walk_left = [SPR01.EGA:0-7, SPR02.EGA:0-3]\n\
+         \"idle\" = [\n  SPR03.EGA,\n]\n",
+    )
+    .unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].name, "walk_left");
+    assert_eq!(groups[0].items[0].frames, Some(0..=7));
+    assert_eq!(groups[1].name, "idle");
+    assert_eq!(groups[1].items[0].frames, None);
+}
+
+#[test]
+fn group_names_are_not_paths() {
+    for text in [
+        "../x = [SPR01.EGA]",
+        "a/b = [SPR01.EGA]",
+        "a\\b = [SPR01.EGA]",
+        "\"..\" = [SPR01.EGA]",
+    ] {
+        let err = parse_manifest(text).err().expect(text);
+        assert!(err.to_string().contains("paths"), "{err}");
+    }
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "png")]
+
+mod common;
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::*;
+use exxos_kult_extract::extract::{Event, ExtractOptions, Extractor};
+use exxos_kult_extract::incremental::{State, STATE_FILENAME};
+
+/// Extracts `inputs` with the state in `out`, returning the files that
+/// were left alone.
+fn run(out: &Path, inputs: &[PathBuf], mirror: bool) -> Vec<PathBuf> {
+    let unchanged = RefCell::new(Vec::new());
+    let mut extractor = Extractor::new(out)
+        .options(ExtractOptions::new().mirror(mirror))
+        .incremental(Some(State::load(out).unwrap()))
+        .on_event(|e| {
+            if let Event::FileUnchanged { path } = e {
+                unchanged.borrow_mut().push(path.to_path_buf());
+            }
+        });
+    for input in inputs {
+        let _ = extractor.extract_file(input);
+    }
+    extractor.take_state().unwrap().save(out).unwrap();
+    drop(extractor);
+    unchanged.into_inner()
+}
+
+#[test]
+fn only_changed_files_are_extracted_again() {
+    let dir = scratch_dir("incremental");
+    let sheet = dir.join("SPR.EGA");
+    let screen = dir.join("PIC.EGA");
+    let broken = dir.join("BAD.EGA");
+    fs::write(
+        &sheet,
+        sprite_sheet(&[interleaved_frame(1, 2, |x, _| x as u8)]),
+    )
+    .unwrap();
+    fs::write(&screen, planar_fullscreen(|_, _| 1)).unwrap();
+    fs::write(&broken, b"junk").unwrap();
+    let inputs = [sheet.clone(), screen.clone(), broken.clone()];
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    assert!(run(&out, &inputs, false).is_empty());
+    assert!(out.join(STATE_FILENAME).exists());
+
+    // Failed files are tried again.
+    assert_eq!(run(&out, &inputs, false), [sheet.clone(), screen.clone()]);
+
+    // Changed contents replace the outputs, missing outputs are written
+    // again.
+    fs::write(&screen, planar_fullscreen(|_, _| 2)).unwrap();
+    fs::remove_file(out.join("SPR-00.png")).unwrap();
+    assert!(run(&out, &inputs, false).is_empty());
+    let (rgba, _, _) = read_png(&out.join("PIC.png"));
+    assert_eq!(rgba_to_indices(&rgba[..4]), [2]);
+    assert!(out.join("SPR-00.png").exists());
+
+    // So do changed options.
+    assert!(run(&out, &inputs, true).is_empty());
+    assert!(out.join("SPR-00-flip.png").exists());
+    assert_eq!(run(&out, &inputs, true), [sheet, screen]);
+}
+
+#[test]
+fn state_roundtrip() {
+    let text = "fnv:00000000000000ff\tkult/SPR.EGA\tSPR-00.png\tSPR-01.png\n\
+                fnv:0000000000000001\tPIC.EGA\n";
+    let state = State::parse(text).unwrap();
+    assert!(!state.is_empty());
+
+    let dir = scratch_dir("incremental-state");
+    state.save(&dir).unwrap();
+    assert_eq!(fs::read_to_string(dir.join(STATE_FILENAME)).unwrap(), {
+        let mut lines = text.lines().collect::<Vec<_>>();
+        lines.sort();
+        lines.join("\n") + "\n"
+    });
+    assert_eq!(State::load(&dir).unwrap(), state);
+    assert!(State::load(dir.join("missing")).unwrap().is_empty());
+
+    assert!(State::parse("00ff\tPIC.EGA").is_err());
+    assert!(State::parse("fnv:00ff").is_err());
+    assert!(State::parse("fnv:00ff\tPIC%2.EGA").is_err());
+}
+
+#[test]
+fn odd_paths_survive_the_state_file() {
+    let mut paths = vec![
+        PathBuf::from("tab\tname.EGA"),
+        PathBuf::from("line\nbreak.EGA"),
+        PathBuf::from("100%.EGA"),
+    ];
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        paths.push(PathBuf::from(OsStr::from_bytes(b"SPR\xff.EGA")));
+    }
+
+    let mut state = State::default();
+    for (n, path) in paths.iter().enumerate() {
+        state.record(path, n as u64, vec![path.with_extension("png")]);
+    }
+    let dir = scratch_dir("incremental-paths");
+    state.save(&dir).unwrap();
+
+    let text = fs::read_to_string(dir.join(STATE_FILENAME)).unwrap();
+    assert_eq!(text.lines().count(), paths.len());
+    assert!(text.contains("tab%09name.EGA\ttab%09name.png"), "{text}");
+    assert!(text.contains("100%25.EGA"), "{text}");
+    assert_eq!(State::load(&dir).unwrap(), state);
+}
+
+#[test]
+fn duplicate_inputs_are_recorded_with_their_links() {
+    let dir = scratch_dir("incremental-duplicates");
+    let sheet = sprite_sheet(&[interleaved_frame(1, 2, |x, _| x as u8)]);
+    let inputs = [dir.join("A.EGA"), dir.join("B.EGA")];
+    for input in &inputs {
+        fs::write(input, &sheet).unwrap();
+    }
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    let extract = || {
+        let unchanged = RefCell::new(Vec::new());
+        let mut extractor = Extractor::new(&out)
+            .options(ExtractOptions::new().dedupe_inputs(true))
+            .incremental(Some(State::load(&out).unwrap()))
+            .on_event(|e| {
+                if let Event::FileUnchanged { path } = e {
+                    unchanged.borrow_mut().push(path.to_path_buf());
+                }
+            });
+        for input in &inputs {
+            extractor.extract_file(input).unwrap();
+        }
+        extractor.take_state().unwrap().save(&out).unwrap();
+        drop(extractor);
+        unchanged.into_inner()
+    };
+
+    assert!(extract().is_empty());
+    assert!(out.join("B-00.png").exists());
+    assert_eq!(extract(), inputs);
+
+    // A missing link is made again, while the original is left alone.
+    fs::remove_file(out.join("B-00.png")).unwrap();
+    assert_eq!(extract(), inputs[..1]);
+    assert!(out.join("B-00.png").exists());
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(out.join("B-00.png")).unwrap(),
+        Path::new("A-00.png")
+    );
+}
+
+#[test]
+fn catalog_changes_extract_again() {
+    use exxos_kult_extract::catalog::Catalog;
+
+    let dir = scratch_dir("incremental-catalog");
+    let sheet = dir.join("SPR.EGA");
+    fs::write(&sheet, sprite_sheet(&[interleaved_frame(1, 1, |_, _| 1)])).unwrap();
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+
+    let run_with = |description: &str| {
+        let catalog = Catalog::parse(&format!("SPR.EGA = \"{description}\"")).unwrap();
+        let mut unchanged = 0;
+        let mut extractor = Extractor::new(&out)
+            .catalog(catalog)
+            .incremental(Some(State::load(&out).unwrap()))
+            .on_event(|e| unchanged += matches!(e, Event::FileUnchanged { .. }) as usize);
+        extractor.extract_file(&sheet).unwrap();
+        extractor.take_state().unwrap().save(&out).unwrap();
+        drop(extractor);
+        unchanged
+    };
+
+    assert_eq!(run_with("Raven"), 0);
+    assert_eq!(run_with("Raven"), 1);
+    assert_eq!(run_with("Raven idle"), 0);
+    assert!(out.join("SPR-raven_idle-00.png").exists());
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use exxos_kult_extract::input::{Input, MAP_THRESHOLD};
+
+#[test]
+fn inputs_hold_the_file_contents() {
+    let dir = scratch_dir("input");
+
+    let small = planar_fullscreen(|x, y| ((x / 8 + y / 10) % 16) as u8);
+    fs::write(dir.join("SMALL.EGA"), &small).unwrap();
+    // SAFETY: nothing writes the scratch files while they are open.
+    let input = unsafe { Input::map(dir.join("SMALL.EGA")) }.unwrap();
+    assert!(!input.is_mapped());
+    assert_eq!(&input[..], &small[..]);
+
+    // A screen in a dump large enough to be mapped.
+    let mut large = vec![0x55; MAP_THRESHOLD as usize];
+    large[0x1000..0x1000 + small.len()].copy_from_slice(&small);
+    fs::write(dir.join("DUMP.BIN"), &large).unwrap();
+    let input = unsafe { Input::map(dir.join("DUMP.BIN")) }.unwrap();
+    assert_eq!(input.is_mapped(), cfg!(all(feature = "mmap", unix)));
+    assert_eq!(&input[..], &large[..]);
+    assert_eq!(&Input::read(dir.join("DUMP.BIN")).unwrap()[..], &large[..]);
+
+    fs::write(dir.join("EMPTY.EGA"), b"").unwrap();
+    assert!(unsafe { Input::map(dir.join("EMPTY.EGA")) }
+        .unwrap()
+        .is_empty());
+    assert!(Input::read(dir.join("MISSING.EGA")).is_err());
+}
@@ -0,0 +1,75 @@
+use exxos_kult_extract::ega::{decode_interleaved_ega_to_rgba, EGA_PAL};
+use exxos_kult_extract::sprite::SpriteSheet;
+
+fn indices(rgba: &[u8]) -> Vec<usize> {
+    rgba.chunks(4)
+        .map(|px| EGA_PAL.iter().position(|c| c == px).unwrap())
+        .collect()
+}
+
+#[test]
+fn odd_width_masks_padding_nibble() {
+    // Three pixels per row stored in two bytes, the last nibble is padding.
+    let src = [0x12, 0x3f, 0x45, 0x6f];
+    let rgba = decode_interleaved_ega_to_rgba(&src, 2, 3, 2);
+
+    assert_eq!(rgba.len(), 4 * 3 * 2);
+    assert_eq!(indices(&rgba), [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn single_pixel_wide_sprite() {
+    let src = [0x9f, 0xaf, 0xbf];
+    let rgba = decode_interleaved_ega_to_rgba(&src, 1, 1, 3);
+
+    assert_eq!(indices(&rgba), [9, 10, 11]);
+}
+
+#[test]
+fn width_narrower_than_span_skips_row_padding() {
+    // Rows are four bytes apart but only the first five pixels are used.
+    let src = [0x12, 0x34, 0x5f, 0xff, 0x67, 0x89, 0xaf, 0xff];
+    let rgba = decode_interleaved_ega_to_rgba(&src, 4, 5, 2);
+
+    assert_eq!(indices(&rgba), [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+}
+
+#[test]
+fn narrow_sheet_frame_is_four_pixels_per_width_unit() {
+    // A header width of 1 is one 16-bit word, so two bytes and four pixels.
+    let mut src = vec![0, 0, 0, 8];
+    src.extend_from_slice(&[8, 0, 1, 2, 0x12, 0x34, 0x56, 0x78]);
+
+    let sheet = SpriteSheet::parse(&src).unwrap();
+    let frames = sheet.frames().collect::<Vec<_>>();
+
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].span, 2);
+    assert_eq!(frames[0].width, 4);
+    assert_eq!(frames[0].height, 2);
+    assert_eq!(indices(&frames[0].to_rgba()), [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn odd_width_sheet_frame_keeps_padding_columns() {
+    // Five pixels per row padded to two words. The header only knows the
+    // words, so the transparent columns stay and frames of an animation
+    // keep the same width.
+    let mut src = vec![0, 0, 0, 12];
+    src.extend_from_slice(&[12, 0, 2, 2, 0x12, 0x34, 0x50, 0x00, 0x67, 0x89, 0xa0, 0x00]);
+
+    let sheet = SpriteSheet::parse(&src).unwrap();
+    let frames = sheet.frames().collect::<Vec<_>>();
+
+    assert_eq!((frames[0].span, frames[0].width), (4, 8));
+    assert_eq!(
+        indices(&frames[0].to_rgba()),
+        [1, 2, 3, 4, 5, 0, 0, 0, 6, 7, 8, 9, 10, 0, 0, 0]
+    );
+}
+
+#[test]
+fn width_beyond_span_decodes_as_zero() {
+    let rgba = decode_interleaved_ega_to_rgba(&[0x12], 1, 4, 1);
+    assert_eq!(indices(&rgba), [1, 2, 0, 0]);
+}
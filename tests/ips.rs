@@ -0,0 +1,36 @@
+use exxos_kult_extract::ips::{apply, diff};
+
+fn roundtrip(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let patch = diff(original, modified).unwrap();
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+    patch
+}
+
+#[test]
+fn patch_roundtrips() {
+    let original = (0..4000u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+
+    let mut modified = original.clone();
+    modified[10] ^= 0xff;
+    modified[12] ^= 0xff;
+    modified[1000..1100].fill(3);
+    modified[3999] = 0;
+    let patch = roundtrip(&original, &modified);
+
+    // Two nearby changes share a record and the fill becomes a run.
+    assert!(patch.len() < 40, "{} bytes", patch.len());
+
+    // Growing and shrinking files.
+    let mut longer = original.clone();
+    longer.extend_from_slice(&[1, 2, 3]);
+    roundtrip(&original, &longer);
+    roundtrip(&original, &original[..100]);
+    roundtrip(&original, &original);
+}
+
+#[test]
+fn rejects_broken_patches() {
+    assert!(apply(b"abc", b"PATCX").is_err());
+    assert!(apply(b"abc", b"PATCH\x00\x00\x01\x00\x05ab").is_err());
+    assert!(apply(b"abc", b"PATCHEOF\x00").is_err());
+}
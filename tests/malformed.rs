@@ -0,0 +1,98 @@
+//! Malformed input has to be rejected or decoded partially, never panic or
+//! allocate without bound. The `fuzz` directory has cargo-fuzz targets
+//! exploring the same decoders; these are cheap deterministic versions of
+//! them and regressions they found.
+
+mod common;
+
+use common::*;
+use exxos_kult_extract::ega;
+use exxos_kult_extract::format::FormatRegistry;
+use exxos_kult_extract::sprite::SpriteSheet;
+
+/// xorshift64, so failures reproduce.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+fn decode_everything(src: &[u8]) {
+    let registry = FormatRegistry::default();
+    let _ = registry.extract(src);
+}
+
+#[test]
+fn random_bytes() {
+    let mut rng = Rng(0x4b756c74);
+    for _ in 0..2000 {
+        let len = rng.below(300);
+        decode_everything(&rng.bytes(len));
+    }
+}
+
+#[test]
+fn mutated_sprite_sheets() {
+    let frames = (0..4)
+        .map(|n| interleaved_frame(2, 6, move |x, y| ((x + y + n) % 16) as u8))
+        .collect::<Vec<_>>();
+    let sheet = sprite_sheet(&frames);
+
+    let mut rng = Rng(0x45474121);
+    for _ in 0..2000 {
+        let mut src = sheet.clone();
+        for _ in 0..1 + rng.below(4) {
+            let i = rng.below(src.len());
+            src[i] = rng.next() as u8;
+        }
+        src.truncate(src.len() - rng.below(8));
+        decode_everything(&src);
+    }
+}
+
+#[test]
+fn mutated_screens() {
+    let mut rng = Rng(0x53435245);
+    let screen = planar_fullscreen(|x, y| ((x / 8 + y) % 16) as u8);
+    for trim in [0, 1, 31, 32, 34] {
+        let mut src = screen.clone();
+        src.truncate(src.len() - trim);
+        let i = rng.below(src.len());
+        src[i] ^= 0xff;
+        decode_everything(&src);
+    }
+}
+
+#[test]
+fn sheet_size_at_the_limit() {
+    let src = [0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+    assert!(SpriteSheet::parse(&src).is_err());
+}
+
+#[test]
+fn short_image_data_decodes_as_zero() {
+    let pixels = ega::decode_planar_ega_to_indexed(&[0xff; 4], 16, 2);
+    assert_eq!(pixels.len(), 32);
+    assert!(pixels.iter().all(|&v| v <= 1));
+
+    let pixels = ega::decode_interleaved_ega_to_indexed(&[0x12], 2, 4, 2);
+    assert_eq!(pixels, [1, 2, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn huge_layouts_match_no_file() {
+    assert_eq!(ega::planar_size(usize::MAX, usize::MAX), usize::MAX);
+}
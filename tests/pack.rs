@@ -0,0 +1,138 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+use exxos_kult_extract::format::{Extract, IndexedFrame, Sprites};
+use exxos_kult_extract::sprite::{encode_sheet, HitBox};
+
+#[test]
+fn encoded_sheets_decode_to_the_same_frames() {
+    let src = sprite_sheet(&[
+        interleaved_frame(1, 2, |x, y| (x + y) as u8),
+        interleaved_frame(2, 3, |x, _| (x % 16) as u8),
+    ]);
+    let extracted = Sprites::new().extract(&src).unwrap();
+    assert_eq!(encode_sheet(&extracted.frames).unwrap(), src);
+}
+
+#[test]
+fn frames_are_padded_and_keep_their_hitboxes() {
+    let frame = IndexedFrame {
+        index: 0,
+        width: 3,
+        height: 1,
+        pixels: vec![1, 2, 3],
+        hitboxes: vec![HitBox {
+            x: 1,
+            y: 0,
+            w: 2,
+            h: 1,
+        }],
+    };
+    let src = encode_sheet(&[frame]).unwrap();
+    assert_eq!(src, [0, 0, 0, 10, 10, 0, 1, 1, 0x12, 0x30, 1, 0, 2, 1]);
+
+    let extracted = Sprites::new().hitboxes(true).extract(&src).unwrap();
+    assert_eq!(extracted.frames[0].pixels, [1, 2, 3, 0]);
+    assert_eq!(extracted.frames[0].hitboxes.len(), 1);
+
+    let too_wide = IndexedFrame {
+        index: 3,
+        width: 1024,
+        height: 1,
+        pixels: vec![0; 1024],
+        hitboxes: Vec::new(),
+    };
+    let err = encode_sheet(&[too_wide]).unwrap_err();
+    assert!(err.to_string().starts_with("frame 3:"), "{err}");
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn extracted_frames_pack_into_a_new_sheet() {
+    use exxos_kult_extract::extract::{ExtractOptions, Extractor};
+    use exxos_kult_extract::pack::{pack, parse_manifest};
+
+    let dir = scratch_dir("pack");
+    let input = dir.join("SPR.EGA");
+    let frames = [
+        interleaved_frame(1, 2, |x, y| (x + 4 * y) as u8),
+        interleaved_frame(2, 3, |x, _| (x % 16) as u8),
+    ];
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+    Extractor::new(&dir).extract_file(&input).unwrap();
+
+    // Reordered, with a frame added twice and padded.
+    let manifest = parse_manifest(
+        "# Reordered\n\
+         SPR-01.png\n\
+         SPR-00.png\n\
+         SPR-00.png size=8x3 offset=4,1 hitbox=4,1,4,2  # moved down\n",
+    )
+    .unwrap();
+    let packed = pack(&manifest, &dir, false).unwrap();
+    assert!(packed.warnings.is_empty(), "{:?}", packed.warnings);
+
+    let mut moved = interleaved_frame(2, 3, |x, y| {
+        if x >= 4 && y >= 1 {
+            (x - 4 + 4 * (y - 1)) as u8
+        } else {
+            0
+        }
+    });
+    // The frame size covers the hit box trailer too.
+    moved[0] += 4;
+    moved.extend_from_slice(&[4, 1, 4, 2]);
+    assert_eq!(
+        packed.data,
+        sprite_sheet(&[frames[1].clone(), frames[0].clone(), moved])
+    );
+
+    // Unscaled images need --native.
+    Extractor::new(dir.join("native"))
+        .options(ExtractOptions::new().native(true))
+        .extract_file(&input)
+        .unwrap();
+    let manifest = parse_manifest("native/SPR-00.png").unwrap();
+    assert!(pack(&manifest, &dir, false).is_err());
+    let packed = pack(&manifest, &dir, true).unwrap();
+    assert_eq!(packed.data, sprite_sheet(&frames[..1]));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn black_stays_opaque_when_packed() {
+    use exxos_kult_extract::extract::Extractor;
+    use exxos_kult_extract::pack::{pack, parse_manifest};
+
+    // Index 8 is black like transparent index 0, only its alpha differs.
+    let dir = scratch_dir("pack-black");
+    let input = dir.join("BLACK.EGA");
+    let frames = [interleaved_frame(1, 2, |x, y| [0, 8, 15][(x + y) % 3])];
+    fs::write(&input, sprite_sheet(&frames)).unwrap();
+    Extractor::new(&dir).extract_file(&input).unwrap();
+
+    let manifest = parse_manifest("BLACK-00.png").unwrap();
+    let packed = pack(&manifest, &dir, false).unwrap();
+    assert!(packed.warnings.is_empty(), "{:?}", packed.warnings);
+    assert_eq!(packed.data, sprite_sheet(&frames));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn invalid_pack_manifests() {
+    use exxos_kult_extract::pack::parse_manifest;
+
+    for text in [
+        "",
+        "# nothing",
+        "A.png size=0x4",
+        "A.png offset=1",
+        "A.png hitbox=1,2,3",
+        "A.png scale=2",
+        "A.png 32x40",
+    ] {
+        assert!(parse_manifest(text).is_err(), "{text}");
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "png")]
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use exxos_kult_extract::ega::EGA_PAL;
+use exxos_kult_extract::extract::{ExtractOptions, Extractor};
+use exxos_kult_extract::palette::PaletteFormat;
+
+fn written(format: PaletteFormat, pal: &[[u8; 4]; 16]) -> Vec<u8> {
+    let mut out = Vec::new();
+    format.write(&mut out, pal, "SPR").unwrap();
+    out
+}
+
+fn gray_palette() -> [[u8; 4]; 16] {
+    std::array::from_fn(|i| [17 * i as u8, 17 * i as u8, 17 * i as u8, 0xff])
+}
+
+#[test]
+fn gimp_palettes_list_every_color() {
+    let text = String::from_utf8(written(PaletteFormat::Gpl, &EGA_PAL)).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[..4],
+        ["GIMP Palette", "Name: SPR", "Columns: 16", "#"]
+    );
+    assert_eq!(lines.len(), 4 + 16);
+    assert_eq!(lines[4], "  0   0   0\tIndex 0, transparent");
+    let [r, g, b, _] = EGA_PAL[1];
+    assert_eq!(lines[5], format!("{r:3} {g:3} {b:3}\tIndex 1"));
+
+    let text = String::from_utf8(written(PaletteFormat::Gpl, &gray_palette())).unwrap();
+    assert_eq!(text.lines().nth(4), Some("  0   0   0\tIndex 0"));
+    assert_eq!(text.lines().last(), Some("255 255 255\tIndex 15"));
+}
+
+#[test]
+fn jasc_palettes_use_crlf() {
+    let text = String::from_utf8(written(PaletteFormat::JascPal, &gray_palette())).unwrap();
+    assert!(text.starts_with("JASC-PAL\r\n0100\r\n16\r\n0 0 0\r\n17 17 17\r\n"));
+    assert!(text.ends_with("255 255 255\r\n"));
+    assert_eq!(text.matches("\r\n").count(), 3 + 16);
+}
+
+#[test]
+fn photoshop_tables_record_the_transparent_index() {
+    let act = written(PaletteFormat::Act, &EGA_PAL);
+    assert_eq!(act.len(), 772);
+    assert_eq!(act[3..6], EGA_PAL[1][..3]);
+    assert_eq!(act[45..48], EGA_PAL[15][..3]);
+    assert!(act[48..768].iter().all(|&b| b == 0));
+    assert_eq!(act[768..], [0, 16, 0, 0]);
+
+    let act = written(PaletteFormat::Act, &gray_palette());
+    assert_eq!(act[768..], [0, 16, 0xff, 0xff]);
+}
+
+#[test]
+fn palettes_are_exported_along_with_the_frames() {
+    let dir = scratch_dir("export-palette");
+    let input = dir.join("SPR.EGA");
+    fs::write(
+        &input,
+        sprite_sheet(&[interleaved_frame(1, 2, |x, _| x as u8)]),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().export_palette(true))
+        .extract_file(&input)
+        .unwrap();
+
+    assert!(out.join("SPR-00.png").exists());
+    let gpl = fs::read_to_string(out.join("SPR.gpl")).unwrap();
+    assert!(gpl.contains("Index 0, transparent"), "{gpl}");
+    assert!(fs::read_to_string(out.join("SPR.pal"))
+        .unwrap()
+        .starts_with("JASC-PAL"));
+    assert_eq!(fs::read(out.join("SPR.act")).unwrap().len(), 772);
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use exxos_kult_extract::progress::Progress;
+
+#[test]
+fn renders_counts_and_estimates() {
+    let mut progress = Progress::new(4, 4000).disabled();
+
+    let line = progress.render(Duration::ZERO, Duration::ZERO);
+    assert_eq!(
+        line,
+        "[                    ] 0/4 files, 0 frames, total ETA -:--"
+    );
+
+    progress.start_file("A.EGA", 1000);
+    progress.frame();
+    progress.finish_file();
+    progress.start_file("B.EGA", 2000);
+    progress.frame();
+    progress.frame();
+
+    // 1000 bytes in 10 seconds, 5 seconds into the second file.
+    let line = progress.render(Duration::from_secs(10), Duration::from_secs(5));
+    assert_eq!(
+        line,
+        "[#####               ] 1/4 files, 3 frames, B.EGA ETA 0:15, total ETA 0:30"
+    );
+}
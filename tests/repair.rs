@@ -0,0 +1,107 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::ega::{decode_planar_ega_to_indexed, FULLSCREEN_SIZE};
+use exxos_kult_extract::repair::{repair_planes, Damage};
+
+const PLANE_SIZE: usize = FULLSCREEN_SIZE / 4;
+
+fn columns(x: usize, _: usize) -> u8 {
+    (x / 8 % 16) as u8
+}
+
+#[test]
+fn lost_sectors_are_filled_from_neighbouring_rows() {
+    let original = planar_fullscreen(columns);
+    let mut src = original.clone();
+    let start = 2 * PLANE_SIZE + 40 * 100;
+    src[start..start + 512].fill(0xf6);
+
+    let damages = repair_planes(&mut src, 320, 200);
+    assert_eq!(
+        damages,
+        [Damage {
+            plane: 2,
+            first_row: 100,
+            last_row: 112,
+            len: 512,
+            fill: 0xf6,
+        }]
+    );
+    assert_eq!(src, original);
+    assert_eq!(
+        damages[0].describe(),
+        "Plane 2, rows 100-112: 512 bytes of 0xF6, likely an unreadable sector, repaired from the neighbouring rows."
+    );
+}
+
+#[test]
+fn short_runs_and_other_bytes_are_left_alone() {
+    let mut src = planar_fullscreen(columns);
+    src[..511].fill(0xf6);
+    src[PLANE_SIZE..PLANE_SIZE + 1024].fill(0x55);
+    let original = src.clone();
+    assert!(repair_planes(&mut src, 320, 200).is_empty());
+    assert_eq!(src, original);
+}
+
+#[test]
+fn whole_planes_become_zeros() {
+    let mut src = planar_fullscreen(|_, _| 15);
+    src[3 * PLANE_SIZE..].fill(0xe5);
+    let damages = repair_planes(&mut src, 320, 200);
+    assert_eq!(damages.len(), 1);
+    assert_eq!((damages[0].first_row, damages[0].last_row), (0, 199));
+
+    let pixels = decode_planar_ega_to_indexed(&src, 320, 200);
+    assert!(pixels.iter().all(|&p| p == 7));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn repaired_screens_are_extracted_with_a_warning() {
+    use exxos_kult_extract::extract::{Event, ExtractOptions, Extractor};
+
+    let dir = scratch_dir("repair");
+    // Bands of 50 rows, so the halves differ.
+    let bands = |x: usize, y: usize| ((x / 8 + y / 50) % 16) as u8;
+    let mut src = planar_fullscreen(bands);
+    src[PLANE_SIZE..PLANE_SIZE + 1000].fill(0xf6);
+
+    let mut warnings = Vec::new();
+    Extractor::new(&dir)
+        .options(ExtractOptions::new().repair(true))
+        .on_event(|e| {
+            if let Event::Warning { message } = e {
+                warnings.push(message.clone());
+            }
+        })
+        .extract_data("BROKEN.EGA", &src)
+        .unwrap();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].starts_with("Plane 1, rows 0-24: 1000 bytes"));
+
+    let (rgba, width, height) = read_png(&dir.join("BROKEN.png"));
+    let indices = rgba_to_indices(&rgba);
+    let (sx, sy) = (width / 320, height / 200);
+    for y in [0, 20, 100] {
+        for x in [0, 8, 100] {
+            assert_eq!(indices[y * sy * width + x * sx], bands(x, y));
+        }
+    }
+}
+
+#[test]
+fn formats_say_where_their_planes_are() {
+    use exxos_kult_extract::format::{Format, PlanarScreen, PrefixedScreen, Sprites};
+
+    let screen = planar_fullscreen(columns);
+    assert_eq!(PlanarScreen::new().planes_offset(&screen), Some(0));
+
+    let mut prefixed = vec![0; 34];
+    prefixed.extend_from_slice(&screen);
+    assert_eq!(PrefixedScreen.planes_offset(&prefixed), Some(34));
+
+    let sheet = sprite_sheet(&[interleaved_frame(1, 1, columns)]);
+    assert_eq!(Sprites::new().planes_offset(&sheet), None);
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::scan::{scan, CandidateKind};
+
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut x = seed;
+    (0..len)
+        .map(|_| {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345);
+            (x >> 16) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn finds_embedded_screen_and_sheet() {
+    let screen = planar_fullscreen(|x, y| ((x / 16 + y / 20) % 16) as u8);
+    let sheet = sprite_sheet(&[interleaved_frame(2, 4, |x, _| 1 + (x / 4) as u8)]);
+
+    let mut src = noise(2000, 1);
+    src.extend_from_slice(&screen);
+    src.extend(noise(517, 2));
+    src.extend_from_slice(&sheet);
+    src.extend(noise(100, 3));
+
+    let found = scan(&src, 1000)
+        .into_iter()
+        .map(|c| (c.offset, c.kind))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        found,
+        [
+            (
+                2000,
+                CandidateKind::Screen {
+                    width: 320,
+                    height: 200
+                }
+            ),
+            (34517, CandidateKind::Sheet { frames: 1 }),
+        ]
+    );
+}
+
+#[test]
+fn noise_has_no_candidates() {
+    assert!(scan(&noise(40000, 4), 1000).is_empty());
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::*;
+use exxos_kult_extract::format::IndexedFrame;
+use exxos_kult_extract::tiles::{self, parse_tile_size};
+
+fn frame(width: usize, height: usize, f: impl Fn(usize, usize) -> u8) -> IndexedFrame {
+    IndexedFrame {
+        index: 0,
+        width,
+        height,
+        pixels: (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect(),
+        hitboxes: Vec::new(),
+    }
+}
+
+#[test]
+fn repeated_tiles_are_stored_once() {
+    // Two kinds of 2x2 tiles in a checkerboard, 3x2 tiles large.
+    let frame = frame(6, 4, |x, y| ((x / 2 + y / 2) % 2) as u8 + 1);
+    let tiling = tiles::split(&frame, 2, 2);
+    assert_eq!((tiling.columns, tiling.rows), (3, 2));
+    assert_eq!(tiling.tiles, [vec![1; 4], vec![2; 4]]);
+    assert_eq!(tiling.map, [0, 1, 0, 1, 0, 1]);
+
+    let tileset = tiling.tileset();
+    assert_eq!((tileset.width, tileset.height), (4, 2));
+    assert_eq!(tileset.pixels, [1, 1, 2, 2, 1, 1, 2, 2]);
+
+    let mut csv = Vec::new();
+    tiling.write_csv(&mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "0,1,0\n1,0,1\n");
+}
+
+#[test]
+fn partial_tiles_are_padded() {
+    let frame = frame(3, 3, |_, _| 5);
+    let tiling = tiles::split(&frame, 2, 2);
+    assert_eq!((tiling.columns, tiling.rows), (2, 2));
+    assert_eq!(
+        tiling.tiles,
+        [
+            vec![5; 4],
+            vec![5, 0, 5, 0],
+            vec![5, 5, 0, 0],
+            vec![5, 0, 0, 0]
+        ]
+    );
+    assert_eq!(tiling.map, [0, 1, 2, 3]);
+}
+
+#[test]
+fn tilesets_wrap_after_sixteen_tiles() {
+    let frame = frame(40, 1, |x, _| (x / 2) as u8);
+    let tileset = tiles::split(&frame, 2, 1).tileset();
+    assert_eq!((tileset.width, tileset.height), (32, 2));
+    assert_eq!(tileset.pixels[32..40], [16, 16, 17, 17, 18, 18, 19, 19]);
+    assert_eq!(tileset.pixels[40..], [0; 24]);
+}
+
+#[test]
+fn tile_sizes_are_parsed() {
+    assert_eq!(parse_tile_size("8x8"), Some((8, 8)));
+    assert_eq!(parse_tile_size("16x12"), Some((16, 12)));
+    assert_eq!(parse_tile_size("0x8"), None);
+    assert_eq!(parse_tile_size("8"), None);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn screens_are_split_into_tiles() {
+    use exxos_kult_extract::extract::{ExtractOptions, Extractor};
+    use std::fs;
+
+    let dir = scratch_dir("tiles");
+    let input = dir.join("ROOM.EGA");
+    fs::write(
+        &input,
+        planar_fullscreen(|x, y| ((x / 8 + y / 8) % 3) as u8),
+    )
+    .unwrap();
+
+    let out = dir.join("png");
+    fs::create_dir_all(&out).unwrap();
+    Extractor::new(&out)
+        .options(ExtractOptions::new().tiles(Some((8, 8))))
+        .extract_file(&input)
+        .unwrap();
+
+    let (tileset, width, height) = read_png(&out.join("ROOM-tiles.png"));
+    assert_eq!((width, height), (24, 8));
+    assert_eq!(
+        rgba_to_indices(&tileset[..4 * 24]),
+        [[0; 8], [1; 8], [2; 8]].concat()
+    );
+
+    let json = fs::read_to_string(out.join("ROOM-tiles.json")).unwrap();
+    assert!(json.contains("\"tileset\": \"ROOM-tiles.png\""), "{json}");
+    assert!(json.contains("\"columns\": 40,"), "{json}");
+    assert!(json.contains("\"rows\": 25,"), "{json}");
+    let csv = fs::read_to_string(out.join("ROOM-tiles.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 25);
+    assert!(csv.starts_with("0,1,2,0,1,2,"), "{csv}");
+}
@@ -0,0 +1,35 @@
+use exxos_kult_extract::ega::EGA_PAL;
+use exxos_kult_extract::format::IndexedFrame;
+use exxos_kult_extract::output::{SCALE_FACTOR_HEIGHT, SCALE_FACTOR_WIDTH};
+use exxos_kult_extract::video::video_frames;
+
+fn frame(width: usize, height: usize, v: u8) -> IndexedFrame {
+    IndexedFrame {
+        index: 0,
+        width,
+        height,
+        pixels: vec![v; width * height],
+        hitboxes: Vec::new(),
+    }
+}
+
+#[test]
+fn frames_share_an_even_canvas() {
+    let frames = [frame(1, 1, 15), frame(3, 2, 0)];
+    let (width, height, rgb) = video_frames(&frames, &EGA_PAL, 2);
+
+    assert_eq!(width, (3 * 2 * SCALE_FACTOR_WIDTH).next_multiple_of(2));
+    assert_eq!(height, 2 * 2 * SCALE_FACTOR_HEIGHT);
+    assert_eq!(rgb.len(), 2);
+
+    // The small frame sits in the top left corner.
+    let row = 3 * width;
+    assert_eq!(&rgb[0][..3], [0xff, 0xff, 0xff]);
+    let last = 3 * (2 * SCALE_FACTOR_WIDTH - 1);
+    assert_eq!(&rgb[0][last..last + 3], [0xff, 0xff, 0xff]);
+    assert_eq!(&rgb[0][last + 3..last + 6], [0, 0, 0]);
+    assert_eq!(&rgb[0][row * 2 * SCALE_FACTOR_HEIGHT..][..3], [0, 0, 0]);
+
+    // Transparent pixels are black.
+    assert!(rgb[1].iter().all(|&c| c == 0));
+}
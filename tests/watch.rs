@@ -0,0 +1,55 @@
+mod common;
+
+use std::fs;
+use std::time::Duration;
+
+use common::*;
+use exxos_kult_extract::watch::{wait_for_changes, Snapshot};
+
+#[test]
+fn snapshots_notice_changed_files() {
+    let dir = scratch_dir("watch");
+    fs::create_dir_all(dir.join("frames")).unwrap();
+    fs::write(dir.join("frames/SPR-00.png"), b"a").unwrap();
+    fs::write(dir.join("frames/.hidden"), b"a").unwrap();
+    fs::write(dir.join("SPR.EGA"), b"a").unwrap();
+
+    let paths = [dir.join("frames"), dir.join("SPR.EGA"), dir.join("NEW.EGA")];
+    let before = Snapshot::take(&paths).unwrap();
+    assert_eq!(before.len(), 2);
+    assert!(before.changes(&Snapshot::take(&paths).unwrap()).is_empty());
+
+    fs::write(dir.join("frames/SPR-00.png"), b"ab").unwrap();
+    fs::write(dir.join("frames/.hidden"), b"ab").unwrap();
+    fs::remove_file(dir.join("SPR.EGA")).unwrap();
+    fs::write(dir.join("NEW.EGA"), b"a").unwrap();
+
+    let after = Snapshot::take(&paths).unwrap();
+    assert_eq!(
+        before.changes(&after),
+        [
+            dir.join("NEW.EGA"),
+            dir.join("SPR.EGA"),
+            dir.join("frames/SPR-00.png")
+        ]
+    );
+}
+
+#[test]
+fn waiting_returns_once_files_settle() {
+    let dir = scratch_dir("watch-wait");
+    let file = dir.join("SPR.EGA");
+    fs::write(&file, b"a").unwrap();
+    let paths = [&dir];
+    let before = Snapshot::take(&paths).unwrap();
+
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(30));
+        fs::write(&file, b"ab").unwrap();
+    });
+    let (after, changes) = wait_for_changes(&paths, &before, Duration::from_millis(10)).unwrap();
+    writer.join().unwrap();
+
+    assert_eq!(changes, [dir.join("SPR.EGA")]);
+    assert_eq!(after, Snapshot::take(&paths).unwrap());
+}